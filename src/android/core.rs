@@ -14,9 +14,9 @@ use jni::{
     JNIEnv,
     objects::{JClass, JObject, JString, JValue},
     strings::JavaStr,
-    sys::{jboolean, jint, jintArray, jlong, jstring},
+    sys::{jboolean, jint, jintArray, jlong, jlongArray, jshort, jstring},
 };
-use jni_sys::{jbyte, jbyteArray, jfloat, jfloatArray, JNI_FALSE, JNI_TRUE, jobject};
+use jni_sys::{jbyte, jbyteArray, jfloat, jfloatArray, JNI_FALSE, JNI_TRUE, jobject, jobjectArray};
 use libc::{size_t};
 use log::{debug, info};
 use log::Level;
@@ -48,7 +48,7 @@ use crate::android::bitmap::{
     ANDROID_BITMAP_RESULT_SUCCESS, AndroidBitmap_getInfo, AndroidBitmap_lockPixels, AndroidBitmap_unlockPixels,
     AndroidBitmapInfo,
 };
-use crate::common::{add_path_to_path, add_path_to_path_with_matrix, adjust_end_angle, arc, arc_to, begin_path, bezier_curve_to, CanvasCompositeOperationType, CanvasNative, CanvasState, CanvasStateItem, clear_canvas, clear_rect, clip, clip_path_rule, clip_rule, close_path, COLOR_TRANSPARENT, create_image_asset, create_image_data, create_matrix, create_path_2d_from_path_data, create_path_from_path, create_pattern, create_pattern_encoded, draw_image, draw_image_dw, draw_image_dw_encoded, draw_image_encoded, draw_image_sw, draw_image_sw_encoded, draw_rect, draw_text, ellipse, ellipse_no_rotation, fill, fill_path_rule, fill_rule, flush, free_matrix, free_path_2d, free_pattern, free_snapshot, get_current_transform, get_direction, get_image_data, get_matrix, get_measure_text, image_asset_flip_x, image_asset_flip_y, image_asset_flip_y_in_place_owned, image_asset_free_bytes, image_asset_get_bytes, image_asset_get_error, image_asset_height, image_asset_load_from_path, image_asset_load_from_raw, image_asset_load_from_slice_i8, image_asset_release, image_asset_save_path, image_asset_scale, image_asset_width, is_font_size, is_font_style, is_font_weight, is_point_in_path, is_point_in_stroke, line_to, move_to, NativeByteArray, NativeImageAsset, put_image_data, quadratic_curve_to, rect, reset_transform, restore, rotate, save, scale, set_current_transform, set_direction, set_fill_color, set_fill_color_rgba, set_fill_pattern, set_font, set_global_alpha, set_global_composite_operation, set_gradient_linear, set_gradient_radial, set_image_smoothing_enabled, set_image_smoothing_quality, set_line_cap, set_line_dash, set_line_dash_offset, set_line_join, set_line_width, set_matrix, set_miter_limit, set_pattern_transform, set_shadow_blur, set_shadow_color, set_shadow_offset_x, set_shadow_offset_y, set_stroke_color, set_stroke_color_rgba, set_stroke_pattern, set_text_align, set_transform, snapshot_canvas, stroke, stroke_path, text_decoder_decode, text_decoder_get_encoding, text_encoder_encode, text_encoder_get_encoding, TextDecoder, TextEncoder, to_byte_slice, to_data, to_data_url, transform, translate, SurfaceKind, flush_custom_surface};
+use crate::common::{add_path_to_path, add_path_to_path_with_matrix, adjust_end_angle, arc, arc_to, begin_path, bezier_curve_to, CanvasCompositeOperationType, CanvasNative, CanvasState, CanvasStateItem, clear_canvas, clear_rect, clip, clip_path_rule, clip_rule, close_path, COLOR_TRANSPARENT, create_image_asset, create_image_data, create_matrix, create_path_2d_from_path_data, create_path_from_path, create_pattern, create_pattern_encoded, draw_image, draw_image_dw, draw_image_dw_encoded, draw_image_encoded, draw_image_sw, draw_image_sw_encoded, draw_rect, draw_text, ellipse, ellipse_no_rotation, fill, fill_path_rule, fill_rule, flush, free_matrix, free_path_2d, free_pattern, free_snapshot, get_current_transform, get_direction, get_image_data, get_matrix, get_measure_text, image_asset_flip_x, image_asset_flip_y, image_asset_flip_y_in_place_owned, image_asset_free_bytes, image_asset_get_bytes, image_asset_get_error, image_asset_height, image_asset_load_from_path, image_asset_load_from_raw, image_asset_load_from_slice_i8, image_asset_release, image_asset_save_path, image_asset_scale, image_asset_width, is_font_size, is_font_style, is_font_weight, is_point_in_path, is_point_in_stroke, line_to, move_to, NativeByteArray, NativeImageAsset, put_image_data, quadratic_curve_to, rect, reset_transform, restore, rotate, save, scale, set_current_transform, set_direction, set_fill_color, set_fill_color_rgba, set_fill_pattern, set_font, set_global_alpha, set_global_composite_operation, set_gradient_linear, set_gradient_radial, set_image_smoothing_enabled, set_image_smoothing_quality, set_line_cap, set_line_dash, set_line_dash_offset, set_line_join, set_line_width, set_matrix, set_miter_limit, set_pattern_transform, set_shadow_blur, set_shadow_color, set_shadow_offset_x, set_shadow_offset_y, set_stroke_color, set_stroke_color_rgba, set_stroke_pattern, set_text_align, set_transform, snapshot_canvas, stroke, stroke_path, text_decoder_decode, text_decoder_get_encoding, text_encoder_encode, text_encoder_get_encoding, TextDecoder, TextEncoder, to_byte_slice, to_data, to_data_url, transform, translate, SurfaceKind, flush_custom_surface, stroke_to_path, hit_test_paths, add_hit_region, hit_region_at, remove_hit_region, clear_hit_regions, path_flatten, path_triangulate, get_line_dash, set_corner_path_effect, set_discrete_path_effect, set_stamp_path_effect, clear_path_effects, set_fill_color_string, set_stroke_color_string, set_shadow_color_string, get_fill_style, get_stroke_style, get_shadow_color, PaintStyle, serialize_color, create_linear_gradient, create_radial_gradient, gradient_add_color_stop, free_gradient, set_fill_gradient, set_stroke_gradient, CanvasGradientStopResult, create_conic_gradient, set_filter, get_filter, get_global_composite_operation, get_global_alpha, create_pattern_from_canvas, create_pattern_from_asset, create_pattern_from_image_data, set_pattern_transform_2d, draw_image_bitmap, draw_image_bitmap_dw, draw_image_bitmap_sw, create_pattern_from_image_bitmap, draw_image_keyed, image_cache_contains, image_cache_remove, image_cache_set_budget, image_cache_purge, image_cache_stats, create_pattern_data_url, draw_image_data_url, draw_image_dw_data_url, draw_image_sw_data_url, gradient_handle, pattern_handle};
 
 #[no_mangle]
 pub extern "system" fn JNI_OnLoad() -> jint {
@@ -171,7 +171,17 @@ fn init(
         line_join: "miter".to_string(),
         direction,
         miter_limit: 10.0,
+        global_alpha: 1.0,
+        filter: "none".to_string(),
+        image_filter: None,
+        stroke_style: Default::default(),
+        fill_style: Default::default(),
+        path_effects: Default::default(),
+        line_dash: vec![],
+        clip_paths: vec![],
         surface_kind,
+        hit_regions: vec![],
+        image_cache: Default::default(),
     };
 
     native_canvas
@@ -179,8 +189,9 @@ fn init(
 
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_FileReader_nativeRead(env: JNIEnv, _: JClass, file: JString) -> jbyteArray {
-    let empty = env.new_string("").unwrap();
-    let mut value = env.get_string(file).unwrap_or(JavaStr::from_env(&env, empty).unwrap());
+    let mut value = env
+        .get_string(file)
+        .unwrap_or_else(|_| JavaStr::from_env(&env, env.new_string("").unwrap()).unwrap());
     let mut real_file = std::fs::File::open(std::path::Path::new(value.to_str().unwrap()));
     let result = match real_file {
         Ok(mut file) => {
@@ -306,6 +317,8 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasView_nativeResize
         Some(&surface_props),
     );
     let mut surface = surface_holder.unwrap();
+    // The new surface starts without a clip.
+    canvas_native.clip_paths.clear();
     canvas_native.surface = surface;
     canvas_native.context = Some(ctx);
     Box::into_raw(canvas_native) as *mut _ as i64
@@ -356,6 +369,10 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasView_nativeRecrea
     new_surface.canvas().draw_rect(Rect::new(0f32, 0f32, width as f32, height as f32), &paint);
     new_surface.canvas().flush();
     new_surface.flush();
+    // Cached textures belong to the old context.
+    canvas_native.image_cache.purge();
+    // The new surface starts without a clip.
+    canvas_native.clip_paths.clear();
     canvas_native.surface = new_surface;
     canvas_native.context = Some(ctx);
     Box::into_raw(Box::new(canvas_native)) as *mut _ as i64
@@ -445,9 +462,9 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasView_nativeToData
     format: JString,
     quality: jfloat,
 ) -> jstring {
-    let default = env.new_string("image/png").unwrap();
-    let javaStr = JavaStr::from_env(&env, default);
-    let format = env.get_string(format).unwrap_or(javaStr.unwrap());
+    let format = env
+        .get_string(format)
+        .unwrap_or_else(|_| JavaStr::from_env(&env, env.new_string("image/png").unwrap()).unwrap());
     let result = to_data_url(canvas_native_ptr, format.as_ptr(), (quality * 100f32) as i32);
     let string = CStr::from_ptr(result).to_str();
     env.new_string(string.unwrap()).unwrap().into_inner()
@@ -620,8 +637,9 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_Pattern_nativeCreatePat
     let _ = env.get_byte_array_region(image, 0, pixels_to_draw.as_mut_slice());
     let mut buf = to_byte_slice(pixels_to_draw.as_mut_slice());
     let image_pixels_ptr = buf.as_mut_ptr();
-    let default = env.new_string("repeat").unwrap();
-    let rep = env.get_string(repetition).unwrap_or(JavaStr::from_env(&env, default).unwrap());
+    let rep = env
+        .get_string(repetition)
+        .unwrap_or_else(|_| JavaStr::from_env(&env, env.new_string("repeat").unwrap()).unwrap());
     create_pattern(image_pixels_ptr, pixels_to_draw.len(), original_width, original_height, rep.get_raw())
 }
 
@@ -632,15 +650,29 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_Pattern_nativeCreatePat
     let _ = env.get_byte_array_region(image, 0, pixels_to_draw.as_mut_slice());
     let mut buf = to_byte_slice(pixels_to_draw.as_mut_slice());
     let image_pixels_ptr = buf.as_mut_ptr();
-    let default = env.new_string("repeat").unwrap();
-    let rep = env.get_string(repetition).unwrap_or(JavaStr::from_env(&env, default).unwrap());
+    let rep = env
+        .get_string(repetition)
+        .unwrap_or_else(|_| JavaStr::from_env(&env, env.new_string("repeat").unwrap()).unwrap());
     create_pattern_encoded(image_pixels_ptr, pixels_to_draw.len(), rep.get_raw())
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_Pattern_nativeCreatePatternDataUrl(env: JNIEnv, _: JClass, data_url: JString, repetition: JString) -> jlong {
+    let data_url = match env.get_string(data_url) {
+        Ok(data_url) => data_url,
+        _ => return 0,
+    };
+    let rep = env
+        .get_string(repetition)
+        .unwrap_or_else(|_| JavaStr::from_env(&env, env.new_string("repeat").unwrap()).unwrap());
+    create_pattern_data_url(data_url.get_raw(), rep.get_raw())
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_Pattern_nativeCreatePattern(env: JNIEnv, _: JClass, image: JObject, repetition: JString) -> jlong {
-    let default = env.new_string("repeat").unwrap();
-    let rep = env.get_string(repetition).unwrap_or(JavaStr::from_env(&env, default).unwrap());
+    let rep = env
+        .get_string(repetition)
+        .unwrap_or_else(|_| JavaStr::from_env(&env, env.new_string("repeat").unwrap()).unwrap());
 
     let native_interface = env.get_native_interface();
     let bitmap_to_draw = image.into_inner();
@@ -679,6 +711,44 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_Pattern_nativeCreatePat
     return ptr;
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_Pattern_nativeCreatePatternFromCanvas(env: JNIEnv, _: JClass, canvas_native_ptr: jlong, repetition: JString) -> jlong {
+    let rep = env
+        .get_string(repetition)
+        .unwrap_or_else(|_| JavaStr::from_env(&env, env.new_string("repeat").unwrap()).unwrap());
+    create_pattern_from_canvas(canvas_native_ptr, rep.get_raw())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_Pattern_nativeCreatePatternFromAsset(env: JNIEnv, _: JClass, asset: jlong, repetition: JString) -> jlong {
+    let rep = env
+        .get_string(repetition)
+        .unwrap_or_else(|_| JavaStr::from_env(&env, env.new_string("repeat").unwrap()).unwrap());
+    create_pattern_from_asset(asset, rep.get_raw())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_Pattern_nativeCreatePatternFromImageData(env: JNIEnv, _: JClass, data: jbyteArray,
+                                                                                                  width: jint,
+                                                                                                  height: jint, repetition: JString) -> jlong {
+    let length = env.get_array_length(data).unwrap_or(0);
+    let mut pixels = vec![0i8; length as usize];
+    let _ = env.get_byte_array_region(data, 0, pixels.as_mut_slice());
+    let buf = to_byte_slice(pixels.as_mut_slice());
+    let rep = env
+        .get_string(repetition)
+        .unwrap_or_else(|_| JavaStr::from_env(&env, env.new_string("repeat").unwrap()).unwrap());
+    create_pattern_from_image_data(buf.as_ptr(), buf.len(), width, height, rep.get_raw())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_Pattern_nativeCreatePatternFromImageBitmap(env: JNIEnv, _: JClass, bitmap: jlong, repetition: JString) -> jlong {
+    let rep = env
+        .get_string(repetition)
+        .unwrap_or_else(|_| JavaStr::from_env(&env, env.new_string("repeat").unwrap()).unwrap());
+    create_pattern_from_image_bitmap(bitmap, rep.get_raw())
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_Pattern_nativeFreePattern(_: JNIEnv, _: JClass, pattern: jlong) {
     free_pattern(pattern)
@@ -689,6 +759,11 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_Pattern_nativeSetPatter
     set_pattern_transform(pattern, matrix)
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_Pattern_nativeSetPatternTransform2D(_: JNIEnv, _: JClass, pattern: jlong, a: jfloat, b: jfloat, c: jfloat, d: jfloat, e: jfloat, f: jfloat) -> jlong {
+    set_pattern_transform_2d(pattern, a, b, c, d, e, f)
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeSetFillPattern(_: JNIEnv, _: JClass, canvas_native_ptr: jlong, pattern: jlong) -> jlong {
     set_fill_pattern(canvas_native_ptr, pattern)
@@ -724,6 +799,32 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2
     set_fill_color(canvas_native_ptr, color as u32)
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeSetFillColorString(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    color: JString,
+) -> jlong {
+    match env.get_string(color) {
+        Ok(color) => set_fill_color_string(canvas_native_ptr, color.get_raw()),
+        _ => canvas_native_ptr,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeSetStrokeColorString(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    color: JString,
+) -> jlong {
+    match env.get_string(color) {
+        Ok(color) => set_stroke_color_string(canvas_native_ptr, color.get_raw()),
+        _ => canvas_native_ptr,
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeSetStrokeColorRgba(
     _env: JNIEnv,
@@ -893,6 +994,196 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2
 
 // drawImage()
 
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeDrawImageBitmap(
+    _env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    bitmap: jlong,
+    dx: jfloat,
+    dy: jfloat,
+) -> jlong {
+    draw_image_bitmap(canvas_native_ptr, bitmap, dx, dy)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeDrawImageBitmapDw(
+    _env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    bitmap: jlong,
+    dx: jfloat,
+    dy: jfloat,
+    d_width: jfloat,
+    d_height: jfloat,
+) -> jlong {
+    draw_image_bitmap_dw(canvas_native_ptr, bitmap, dx, dy, d_width, d_height)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeDrawImageBitmapSw(
+    _env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    bitmap: jlong,
+    sx: jfloat,
+    sy: jfloat,
+    s_width: jfloat,
+    s_height: jfloat,
+    dx: jfloat,
+    dy: jfloat,
+    d_width: jfloat,
+    d_height: jfloat,
+) -> jlong {
+    draw_image_bitmap_sw(canvas_native_ptr, bitmap, sx, sy, s_width, s_height, dx, dy, d_width, d_height)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeDrawImageDataUrl(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    data_url: JString,
+    dx: jfloat,
+    dy: jfloat,
+) -> jlong {
+    match env.get_string(data_url) {
+        Ok(data_url) => draw_image_data_url(canvas_native_ptr, data_url.get_raw(), dx, dy),
+        _ => canvas_native_ptr,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeDrawImageDataUrlDw(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    data_url: JString,
+    dx: jfloat,
+    dy: jfloat,
+    d_width: jfloat,
+    d_height: jfloat,
+) -> jlong {
+    match env.get_string(data_url) {
+        Ok(data_url) => draw_image_dw_data_url(canvas_native_ptr, data_url.get_raw(), dx, dy, d_width, d_height),
+        _ => canvas_native_ptr,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeDrawImageDataUrlSw(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    data_url: JString,
+    sx: jfloat,
+    sy: jfloat,
+    s_width: jfloat,
+    s_height: jfloat,
+    dx: jfloat,
+    dy: jfloat,
+    d_width: jfloat,
+    d_height: jfloat,
+) -> jlong {
+    match env.get_string(data_url) {
+        Ok(data_url) => draw_image_sw_data_url(canvas_native_ptr, data_url.get_raw(), sx, sy, s_width, s_height, dx, dy, d_width, d_height),
+        _ => canvas_native_ptr,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeDrawImageKeyed(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    key: JString,
+    image: jbyteArray,
+    sx: jfloat,
+    sy: jfloat,
+    s_width: jfloat,
+    s_height: jfloat,
+    dx: jfloat,
+    dy: jfloat,
+    d_width: jfloat,
+    d_height: jfloat,
+) -> jlong {
+    let mut data = vec![];
+    if !image.is_null() {
+        let len = env.get_array_length(image).unwrap_or(0);
+        data = vec![0i8; len as usize];
+        let _ = env.get_byte_array_region(image, 0, data.as_mut_slice());
+    }
+    let key = if key.is_null() { None } else { env.get_string(key).ok() };
+    let key_ptr = key.as_ref().map_or(std::ptr::null(), |key| key.get_raw());
+    draw_image_keyed(canvas_native_ptr, key_ptr, data.as_ptr() as *const u8, data.len(), sx, sy, s_width, s_height, dx, dy, d_width, d_height)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeImageCacheContains(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    key: JString,
+) -> jboolean {
+    match env.get_string(key) {
+        Ok(key) if image_cache_contains(canvas_native_ptr, key.get_raw()) => JNI_TRUE,
+        _ => JNI_FALSE,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeImageCacheRemove(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    key: JString,
+) -> jboolean {
+    match env.get_string(key) {
+        Ok(key) if image_cache_remove(canvas_native_ptr, key.get_raw()) => JNI_TRUE,
+        _ => JNI_FALSE,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeImageCacheSetBudget(
+    _env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    budget: jlong,
+) -> jlong {
+    image_cache_set_budget(canvas_native_ptr, budget.max(0) as size_t)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeImageCachePurge(
+    _env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+) -> jlong {
+    image_cache_purge(canvas_native_ptr)
+}
+
+/// Returns `[entries, bytes, budget, hits, misses, evictions]`.
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeImageCacheStats(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+) -> jlongArray {
+    let stats = image_cache_stats(canvas_native_ptr);
+    let values = [
+        stats.entries as jlong,
+        stats.bytes as jlong,
+        stats.budget as jlong,
+        stats.hits as jlong,
+        stats.misses as jlong,
+        stats.evictions as jlong,
+    ];
+    let array = env.new_long_array(values.len() as i32).unwrap();
+    let _ = env.set_long_array_region(array, 0, &values);
+    array
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeDrawImageCanvas(
     env: JNIEnv,
@@ -1319,6 +1610,133 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2
     line_to(canvas_native_ptr, true, x, y)
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasGradient_nativeCreateLinearGradient(
+    _env: JNIEnv,
+    _: JClass,
+    x0: jfloat,
+    y0: jfloat,
+    x1: jfloat,
+    y1: jfloat,
+) -> jlong {
+    create_linear_gradient(x0, y0, x1, y1)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasGradient_nativeCreateRadialGradient(
+    _env: JNIEnv,
+    _: JClass,
+    x0: jfloat,
+    y0: jfloat,
+    r0: jfloat,
+    x1: jfloat,
+    y1: jfloat,
+    r1: jfloat,
+) -> jlong {
+    create_radial_gradient(x0, y0, r0, x1, y1, r1)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasGradient_nativeCreateConicGradient(
+    _env: JNIEnv,
+    _: JClass,
+    start_angle: jfloat,
+    x: jfloat,
+    y: jfloat,
+) -> jlong {
+    create_conic_gradient(start_angle, x, y)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasGradient_nativeAddColorStop(
+    env: JNIEnv,
+    _: JClass,
+    gradient: jlong,
+    offset: jfloat,
+    color: JString,
+) -> jint {
+    let result = match env.get_string(color) {
+        Ok(color) => gradient_add_color_stop(gradient, offset, color.get_raw()),
+        _ => CanvasGradientStopResult::InvalidColor,
+    };
+    result as jint
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasGradient_nativeFree(
+    _env: JNIEnv,
+    _: JClass,
+    gradient: jlong,
+) {
+    free_gradient(gradient)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeSetFillGradient(
+    _env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    gradient: jlong,
+) -> jlong {
+    set_fill_gradient(canvas_native_ptr, gradient)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeSetStrokeGradient(
+    _env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    gradient: jlong,
+) -> jlong {
+    set_stroke_gradient(canvas_native_ptr, gradient)
+}
+
+static CANVAS_PAINT_STYLE: &str = "com/github/triniwiz/canvas/CanvasPaintStyle";
+
+unsafe fn paint_style_to_object(env: &JNIEnv, style: PaintStyle) -> jobject {
+    let result = env.new_object(CANVAS_PAINT_STYLE, "()V", &[]).unwrap();
+    let (style_type, handle) = match style {
+        PaintStyle::Color(color) => {
+            let color = env.new_string(serialize_color(color)).unwrap();
+            let _ = env.set_field(result, "color", "Ljava/lang/String;", JValue::from(JObject::from(color)));
+            (0, 0)
+        }
+        // New handles, which the style object frees like any other.
+        PaintStyle::Gradient(gradient) => (1, gradient_handle(&gradient)),
+        PaintStyle::Pattern(pattern) => (2, pattern_handle(&pattern)),
+    };
+    let _ = env.set_field(result, "type", "I", JValue::from(style_type as jint));
+    let _ = env.set_field(result, "handle", "J", JValue::from(handle as jlong));
+    result.into_inner()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeGetFillStyle(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+) -> jobject {
+    paint_style_to_object(&env, get_fill_style(canvas_native_ptr))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeGetStrokeStyle(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+) -> jobject {
+    paint_style_to_object(&env, get_stroke_style(canvas_native_ptr))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeGetShadowColor(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+) -> jstring {
+    env.new_string(get_shadow_color(canvas_native_ptr)).unwrap().into_inner()
+}
+
 static CANVAS_TEXT_METRICS: &str = "com/github/triniwiz/canvas/CanvasTextMetrics";
 
 #[no_mangle]
@@ -1426,6 +1844,65 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2
     set_line_dash(canvas_native_ptr, size, buffer.as_ptr())
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeGetLineDash(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+) -> jfloatArray {
+    let line_dash = get_line_dash(canvas_native_ptr);
+    let array = env.new_float_array(line_dash.len() as i32).unwrap();
+    let _ = env.set_float_array_region(array, 0, line_dash.as_slice());
+    array
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeSetCornerPathEffect(
+    _env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    radius: jfloat,
+) -> jlong {
+    set_corner_path_effect(canvas_native_ptr, radius)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeSetDiscretePathEffect(
+    _env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    segment_length: jfloat,
+    deviation: jfloat,
+    seed: jint,
+) -> jlong {
+    set_discrete_path_effect(canvas_native_ptr, segment_length, deviation, seed as u32)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeSetStampPathEffect(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    path: jlong,
+    advance: jfloat,
+    phase: jfloat,
+    style: JString,
+) -> jlong {
+    let style = env
+        .get_string(style)
+        .unwrap_or_else(|_| JavaStr::from_env(&env, env.new_string("translate").unwrap()).unwrap());
+    set_stamp_path_effect(canvas_native_ptr, path, advance, phase, style.get_raw())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeClearPathEffects(
+    _env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+) -> jlong {
+    clear_path_effects(canvas_native_ptr)
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeSetLineDashOffset(_env: JNIEnv,
                                                                                                           _: JClass,
@@ -1537,10 +2014,19 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2
     canvas_native_ptr: jlong,
     composite: JString,
 ) -> jlong {
-    set_global_composite_operation(
-        canvas_native_ptr,
-        env.get_string(composite).unwrap().as_ptr() as _,
-    )
+    match env.get_string(composite) {
+        Ok(composite) => set_global_composite_operation(canvas_native_ptr, composite.get_raw()),
+        _ => canvas_native_ptr,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeGetGlobalCompositeOperation(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+) -> jstring {
+    env.new_string(get_global_composite_operation(canvas_native_ptr)).unwrap().into_inner()
 }
 
 #[no_mangle]
@@ -1548,11 +2034,20 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2
     _env: JNIEnv,
     _: JClass,
     canvas_native_ptr: jlong,
-    alpha: u8,
+    alpha: jfloat,
 ) -> jlong {
     set_global_alpha(canvas_native_ptr, alpha)
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeGetGlobalAlpha(
+    _env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+) -> jfloat {
+    get_global_alpha(canvas_native_ptr)
+}
+
 
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeSetLineCap(
@@ -1601,6 +2096,41 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2
     set_shadow_color(canvas_native_ptr, color as u32)
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeSetShadowColorString(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    color: JString,
+) -> jlong {
+    match env.get_string(color) {
+        Ok(color) => set_shadow_color_string(canvas_native_ptr, color.get_raw()),
+        _ => canvas_native_ptr,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeSetFilter(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    filter: JString,
+) -> jlong {
+    match env.get_string(filter) {
+        Ok(filter) => set_filter(canvas_native_ptr, filter.get_raw()),
+        _ => canvas_native_ptr,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeGetFilter(
+    env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+) -> jstring {
+    env.new_string(get_filter(canvas_native_ptr)).unwrap().into_inner()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeSetShadowOffsetX(
     _env: JNIEnv,
@@ -1765,8 +2295,9 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2
     let path = canvas_native.path.clone();
     let _ = Box::into_raw(canvas_native);
     let path = Box::into_raw(Box::new(path)) as i64;
-    let default = env.new_string("nonzero").unwrap();
-    let rule = env.get_string(fill_rule).unwrap_or(JavaStr::from_env(&env, default).unwrap());
+    let rule = env
+        .get_string(fill_rule)
+        .unwrap_or_else(|_| JavaStr::from_env(&env, env.new_string("nonzero").unwrap()).unwrap());
     let result = is_point_in_path(canvas_ptr, path, x, y, rule.get_raw());
     let _ = Box::from_raw(path as *mut c_void);
     if result { return JNI_TRUE; }
@@ -1777,8 +2308,9 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeIsPointInPathWithPathRule(env: JNIEnv,
                                                                                                                   _: JClass, canvas_ptr: i64, path: jlong, x: f32, y: f32, fill_rule: JString) -> jboolean {
-    let default = env.new_string("nonzero").unwrap();
-    let rule = env.get_string(fill_rule).unwrap_or(JavaStr::from_env(&env, default).unwrap());
+    let rule = env
+        .get_string(fill_rule)
+        .unwrap_or_else(|_| JavaStr::from_env(&env, env.new_string("nonzero").unwrap()).unwrap());
     let result = is_point_in_path(canvas_ptr, path, x, y, rule.get_raw());
     if result { return JNI_TRUE; }
     return JNI_FALSE;
@@ -1808,6 +2340,72 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2
 }
 
 
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeHitTestPaths(env: JNIEnv,
+                                                                                                   _: JClass, canvas_ptr: i64, paths: jlongArray, x: f32, y: f32, tolerance: f32, fill_rule: JString, stroke: jboolean, all: jboolean) -> jintArray {
+    let size = env.get_array_length(paths).unwrap_or(0);
+    let mut handles: Vec<jlong> = vec![0; size as usize];
+    let _ = env.get_long_array_region(paths, 0, handles.as_mut_slice());
+    let rule = env
+        .get_string(fill_rule)
+        .unwrap_or_else(|_| JavaStr::from_env(&env, env.new_string("nonzero").unwrap()).unwrap());
+    let hits = hit_test_paths(canvas_ptr, handles.as_slice(), x, y, tolerance, rule.get_raw(), stroke == JNI_TRUE, all == JNI_TRUE);
+    let array = env.new_int_array(hits.len() as i32).unwrap();
+    let _ = env.set_int_array_region(array, 0, hits.as_slice());
+    array
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeAddHitRegion(env: JNIEnv,
+                                                                                                  _: JClass, canvas_ptr: i64, path: jlong, id: JString, stroke: jboolean, fill_rule: JString) -> jlong {
+    let id = match env.get_string(id) {
+        Ok(id) => id,
+        _ => return canvas_ptr,
+    };
+    let rule = env
+        .get_string(fill_rule)
+        .unwrap_or_else(|_| JavaStr::from_env(&env, env.new_string("nonzero").unwrap()).unwrap());
+    add_hit_region(canvas_ptr, path, id.get_raw(), stroke == JNI_TRUE, rule.get_raw())
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeHitRegionAt(env: JNIEnv,
+                                                                                                 _: JClass, canvas_ptr: i64, x: f32, y: f32) -> jstring {
+    let id = hit_region_at(canvas_ptr, x, y);
+    if id.is_null() {
+        return JObject::null().into_inner();
+    }
+    let id = CString::from_raw(id as *mut _);
+    env.new_string(id.to_str().unwrap_or("")).unwrap().into_inner()
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeRemoveHitRegion(env: JNIEnv,
+                                                                                                     _: JClass, canvas_ptr: i64, id: JString) -> jlong {
+    match env.get_string(id) {
+        Ok(id) => remove_hit_region(canvas_ptr, id.get_raw()),
+        _ => canvas_ptr,
+    }
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeClearHitRegions(_env: JNIEnv,
+                                                                                                     _: JClass, canvas_ptr: i64) -> jlong {
+    clear_hit_regions(canvas_ptr)
+}
+
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeStrokeToPath(_env: JNIEnv,
+                                                                                                 _: JClass, canvas_ptr: i64, path: jlong) -> jlong {
+    stroke_to_path(canvas_ptr, path)
+}
+
+
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasPath2D_nativeFreePath(
     _env: JNIEnv,
@@ -1993,6 +2591,70 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasPath2D_nativeRect
     rect(path_native_ptr, false, x, y, width, height)
 }
 
+static CANVAS_PATH_CONTOUR: &str = "com/github/triniwiz/canvas/CanvasPathContour";
+static CANVAS_PATH_TRIANGULATION: &str = "com/github/triniwiz/canvas/CanvasPathTriangulation";
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasPath2D_nativeFlatten(
+    env: JNIEnv,
+    _: JClass,
+    path_native_ptr: jlong,
+    tolerance: jfloat,
+) -> jobjectArray {
+    let contours = path_flatten(path_native_ptr, tolerance);
+    let array = env
+        .new_object_array(contours.len() as i32, CANVAS_PATH_CONTOUR, JObject::null())
+        .unwrap();
+    for (index, contour) in contours.iter().enumerate() {
+        let item = env.new_object(CANVAS_PATH_CONTOUR, "()V", &[]).unwrap();
+        let points = env.new_float_array(contour.points.len() as i32).unwrap();
+        let _ = env.set_float_array_region(points, 0, contour.points.as_slice());
+        let _ = env.set_field(item, "points", "[F", JValue::from(JObject::from(points)));
+        let _ = env.set_field(item, "closed", "Z", JValue::from(contour.closed));
+        let _ = env.set_object_array_element(array, index as i32, item);
+        let _ = env.delete_local_ref(JObject::from(points));
+        let _ = env.delete_local_ref(item);
+    }
+    array
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasPath2D_nativeTriangulate(
+    env: JNIEnv,
+    _: JClass,
+    path_native_ptr: jlong,
+    fill_rule: JString,
+    tolerance: jfloat,
+    prefer_short: jboolean,
+) -> jobject {
+    let rule = env
+        .get_string(fill_rule)
+        .unwrap_or_else(|_| JavaStr::from_env(&env, env.new_string("nonzero").unwrap()).unwrap());
+    let triangulation = path_triangulate(path_native_ptr, rule.get_raw(), tolerance);
+    let result = env.new_object(CANVAS_PATH_TRIANGULATION, "()V", &[]).unwrap();
+    let vertices = env.new_float_array(triangulation.vertices.len() as i32).unwrap();
+    let _ = env.set_float_array_region(vertices, 0, triangulation.vertices.as_slice());
+    let _ = env.set_field(result, "vertices", "[F", JValue::from(JObject::from(vertices)));
+    // Short indices are left null unless asked for and every vertex fits; WebGL1 can
+    // only draw those without OES_element_index_uint.
+    let short_indices = if prefer_short == JNI_TRUE { triangulation.indices_u16() } else { None };
+    match short_indices {
+        Some(indices) => {
+            let indices: Vec<jshort> = indices.iter().map(|index| *index as jshort).collect();
+            let indices_array = env.new_short_array(indices.len() as i32).unwrap();
+            let _ = env.set_short_array_region(indices_array, 0, indices.as_slice());
+            let _ = env.set_field(result, "shortIndices", "[S", JValue::from(JObject::from(indices_array)));
+        }
+        None => {
+            let indices: Vec<jint> = triangulation.indices.iter().map(|index| *index as jint).collect();
+            let indices_array = env.new_int_array(indices.len() as i32).unwrap();
+            let _ = env.set_int_array_region(indices_array, 0, indices.as_slice());
+            let _ = env.set_field(result, "indices", "[I", JValue::from(JObject::from(indices_array)));
+        }
+    }
+    result.into_inner()
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_CanvasRenderingContext2D_nativeSetCurrentTransform(_env: JNIEnv,
                                                                                                             _: JClass, canvas_ptr: jlong, matrix: jlong) -> jlong {
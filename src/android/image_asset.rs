@@ -7,11 +7,11 @@ use std::ffi::CStr;
 
 use jni::{
     JNIEnv,
-    objects::{JClass, JString}
+    objects::{JClass, JObject, JString, JValue}
 };
-use jni_sys::{jboolean, jbyteArray, jint, jlong, jstring};
+use jni_sys::{jboolean, jbyteArray, jfloat, jint, jlong, jobject, jstring, JNI_TRUE};
 
-use crate::common::{create_image_asset, image_asset_flip_x, image_asset_flip_y, image_asset_free_bytes, image_asset_get_bytes, image_asset_get_error, image_asset_height, image_asset_load_from_path, image_asset_load_from_slice_i8, image_asset_release, image_asset_save_path, image_asset_scale, image_asset_width};
+use crate::common::{create_image_asset, image_asset_flip_x, image_asset_flip_y, image_asset_free_bytes, image_asset_get_bytes, image_asset_get_error, image_asset_height, image_asset_load_from_path, image_asset_load_from_slice_i8, image_asset_release, image_asset_save_path, image_asset_scale, image_asset_width, image_asset_frame_count, image_asset_loop_count, image_asset_frame_delay, image_asset_get_frame_bytes, image_asset_select_frame, image_asset_set_auto_orient, image_asset_get_orientation, image_asset_get_exif_metadata, image_asset_resize, image_asset_crop, image_asset_rotate, image_asset_grayscale, image_asset_blur, image_asset_brighten, image_asset_contrast, image_asset_hue_rotate, image_asset_encode, image_asset_save_path_with_options, ImageAssetEncodeOptions, image_asset_load_async, image_asset_load_status, image_asset_load_cancel, image_asset_load_take, image_asset_load_release, ImageAssetLoadCompletion, ImageAssetSource, image_asset_load_from_data_url, image_asset_load_svg, to_byte_slice};
 
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeInit(_env: JNIEnv, _: JClass) -> jlong {
@@ -42,6 +42,52 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeScale(
     image_asset_scale(asset, x as u32, y as u32)
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeResize(_env: JNIEnv, _: JClass, asset: jlong, width: jint, height: jint, filter: jint, mode: jint) -> jboolean {
+    if width <= 0 || height <= 0 {
+        return 0;
+    }
+    image_asset_resize(asset, width as u32, height as u32, filter as u32, mode as u32) as u8
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeCrop(_env: JNIEnv, _: JClass, asset: jlong, x: jint, y: jint, width: jint, height: jint) -> jboolean {
+    if x < 0 || y < 0 || width <= 0 || height <= 0 {
+        return 0;
+    }
+    image_asset_crop(asset, x as u32, y as u32, width as u32, height as u32) as u8
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeRotate(_env: JNIEnv, _: JClass, asset: jlong, degrees: jfloat) -> jboolean {
+    image_asset_rotate(asset, degrees) as u8
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeGrayscale(_env: JNIEnv, _: JClass, asset: jlong) -> jboolean {
+    image_asset_grayscale(asset) as u8
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeBlur(_env: JNIEnv, _: JClass, asset: jlong, sigma: jfloat) -> jboolean {
+    image_asset_blur(asset, sigma) as u8
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeBrighten(_env: JNIEnv, _: JClass, asset: jlong, value: jint) -> jboolean {
+    image_asset_brighten(asset, value) as u8
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeContrast(_env: JNIEnv, _: JClass, asset: jlong, value: jfloat) -> jboolean {
+    image_asset_contrast(asset, value) as u8
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeHueRotate(_env: JNIEnv, _: JClass, asset: jlong, degrees: jint) -> jboolean {
+    image_asset_hue_rotate(asset, degrees) as u8
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeFlipX(_env: JNIEnv, _: JClass, asset: jlong) -> jlong {
     image_asset_flip_x(asset)
@@ -53,6 +99,36 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeSave(e
     image_asset_save_path(asset, real_path.get_raw(), format as u32) as u8
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeSaveWithOptions(env: JNIEnv, _: JClass, asset: jlong, path: JString, format: jint, jpeg_quality: jint, png_compression: jint, png_filter: jint) -> jboolean {
+    let options = ImageAssetEncodeOptions {
+        jpeg_quality: jpeg_quality.max(0) as u32,
+        png_compression: png_compression.max(0) as u32,
+        png_filter: png_filter.max(0) as u32,
+    };
+    match env.get_string(path) {
+        Ok(real_path) => image_asset_save_path_with_options(asset, real_path.get_raw(), format as u32, options) as u8,
+        _ => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeEncode(env: JNIEnv, _: JClass, asset: jlong, format: jint, jpeg_quality: jint, png_compression: jint, png_filter: jint) -> jbyteArray {
+    let options = ImageAssetEncodeOptions {
+        jpeg_quality: jpeg_quality.max(0) as u32,
+        png_compression: png_compression.max(0) as u32,
+        png_filter: png_filter.max(0) as u32,
+    };
+    let array = image_asset_encode(asset, format as u32, options);
+    if array.array.is_null() {
+        return env.new_byte_array(0).unwrap();
+    }
+    let bytes = std::slice::from_raw_parts(array.array as *const u8, array.length);
+    let result = env.byte_array_from_slice(bytes).unwrap_or(env.new_byte_array(0).unwrap());
+    image_asset_free_bytes(array);
+    result
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeFlipY(_env: JNIEnv, _: JClass, asset: jlong) -> jlong {
     image_asset_flip_y(asset)
@@ -77,6 +153,14 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeLoadAs
     image_asset_load_from_path(asset, real_path.get_raw()) as u8
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeLoadAssetDataUrl(env: JNIEnv, _: JClass, asset: jlong, url: JString) -> jboolean {
+    match env.get_string(url) {
+        Ok(url) => image_asset_load_from_data_url(asset, url.get_raw()) as u8,
+        _ => 0,
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeLoadAssetBuffer(env: JNIEnv, _: JClass, asset: jlong, buffer: jbyteArray) -> jboolean {
     let size = env.get_array_length(buffer).unwrap_or(0);
@@ -84,3 +168,144 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeLoadAs
     let _ = env.get_byte_array_region(buffer, 0, buf.as_mut_slice());
     image_asset_load_from_slice_i8(asset, buf.as_mut_slice()) as u8
 }
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeLoadAssetSvg(env: JNIEnv, _: JClass, asset: jlong, svg: jbyteArray, width: jint, height: jint) -> jboolean {
+    let size = env.get_array_length(svg).unwrap_or(0);
+    let mut buf = vec![0i8; size as usize];
+    let _ = env.get_byte_array_region(svg, 0, buf.as_mut_slice());
+    let buf = to_byte_slice(buf.as_mut_slice());
+    image_asset_load_svg(asset, buf.as_ptr(), buf.len(), width.max(0) as u32, height.max(0) as u32) as u8
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeGetFrameCount(_env: JNIEnv, _: JClass, asset: jlong) -> jint {
+    image_asset_frame_count(asset) as i32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeGetLoopCount(_env: JNIEnv, _: JClass, asset: jlong) -> jint {
+    image_asset_loop_count(asset) as i32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeGetFrameDelay(_env: JNIEnv, _: JClass, asset: jlong, index: jint) -> jint {
+    if index < 0 {
+        return 0;
+    }
+    image_asset_frame_delay(asset, index as u32) as i32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeGetFrameBytes(env: JNIEnv, _: JClass, asset: jlong, index: jint) -> jbyteArray {
+    if index < 0 {
+        return env.new_byte_array(0).unwrap();
+    }
+    let array = image_asset_get_frame_bytes(asset, index as u32);
+    if array.array.is_null() {
+        return env.new_byte_array(0).unwrap();
+    }
+    let bytes = std::slice::from_raw_parts(array.array as *const u8, array.length);
+    let result = env.byte_array_from_slice(bytes).unwrap_or(env.new_byte_array(0).unwrap());
+    image_asset_free_bytes(array);
+    result
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeSelectFrame(_env: JNIEnv, _: JClass, asset: jlong, index: jint) -> jboolean {
+    if index < 0 {
+        return 0;
+    }
+    image_asset_select_frame(asset, index as u32) as u8
+}
+
+static IMAGE_ASSET_EXIF: &str = "com/github/triniwiz/canvas/ImageAssetExif";
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeSetAutoOrient(_env: JNIEnv, _: JClass, asset: jlong, auto_orient: jboolean) -> jlong {
+    image_asset_set_auto_orient(asset, auto_orient == JNI_TRUE)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeGetOrientation(_env: JNIEnv, _: JClass, asset: jlong) -> jint {
+    image_asset_get_orientation(asset) as i32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeGetExif(env: JNIEnv, _: JClass, asset: jlong) -> jobject {
+    let metadata = match image_asset_get_exif_metadata(asset) {
+        Some(metadata) => metadata,
+        _ => return JObject::null().into_inner(),
+    };
+    let result = env.new_object(IMAGE_ASSET_EXIF, "()V", &[]).unwrap();
+    let _ = env.set_field(result, "orientation", "I", JValue::from(metadata.orientation as jint));
+    let _ = env.set_field(result, "hasGps", "Z", JValue::from(metadata.has_gps));
+    let strings = [
+        ("dateTime", &metadata.date_time),
+        ("cameraMake", &metadata.camera_make),
+        ("cameraModel", &metadata.camera_model),
+    ];
+    for (field, value) in strings.iter() {
+        if let Some(value) = value {
+            let value = env.new_string(value).unwrap();
+            let _ = env.set_field(result, *field, "Ljava/lang/String;", JValue::from(JObject::from(value)));
+        }
+    }
+    result.into_inner()
+}
+
+// Calls `onComplete(long load, int status)` on the listener from the decoder thread,
+// attaching that thread to the VM for the call.
+fn load_listener(env: &JNIEnv, listener: JObject) -> Option<ImageAssetLoadCompletion> {
+    if listener.is_null() {
+        return None;
+    }
+    let vm = env.get_java_vm().ok()?;
+    let listener = env.new_global_ref(listener).ok()?;
+    Some(Box::new(move |load, status| {
+        if let Ok(env) = vm.attach_current_thread() {
+            let result = env.call_method(listener.as_obj(), "onComplete", "(JI)V", &[JValue::Long(load), JValue::Int(status as jint)]);
+            if result.is_err() && env.exception_check().unwrap_or(false) {
+                let _ = env.exception_clear();
+            }
+        }
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeLoadAssetPathAsync(env: JNIEnv, _: JClass, path: JString, auto_orient: jboolean, listener: JObject) -> jlong {
+    let real_path = match env.get_string(path) {
+        Ok(real_path) => CStr::from_ptr(real_path.get_raw()).to_owned(),
+        _ => return 0,
+    };
+    image_asset_load_async(ImageAssetSource::Path(real_path), auto_orient == JNI_TRUE, load_listener(&env, listener))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeLoadAssetBufferAsync(env: JNIEnv, _: JClass, buffer: jbyteArray, auto_orient: jboolean, listener: JObject) -> jlong {
+    let bytes = match env.convert_byte_array(buffer) {
+        Ok(bytes) => bytes,
+        _ => return 0,
+    };
+    image_asset_load_async(ImageAssetSource::Bytes(bytes), auto_orient == JNI_TRUE, load_listener(&env, listener))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeLoadStatus(_env: JNIEnv, _: JClass, load: jlong) -> jint {
+    image_asset_load_status(load) as jint
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeLoadCancel(_env: JNIEnv, _: JClass, load: jlong) -> jboolean {
+    image_asset_load_cancel(load) as u8
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeLoadTake(_env: JNIEnv, _: JClass, load: jlong) -> jlong {
+    image_asset_load_take(load)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageAsset_nativeLoadRelease(_env: JNIEnv, _: JClass, load: jlong) {
+    image_asset_load_release(load)
+}
@@ -0,0 +1,170 @@
+#![allow(non_upper_case_globals)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+extern crate libc;
+
+use jni::{
+    JNIEnv,
+    objects::JClass,
+};
+use jni_sys::{jboolean, jbyteArray, jfloat, jint, jlong, JNI_TRUE};
+
+use crate::common::{create_image_bitmap_encoded, create_image_bitmap_from_asset, create_image_bitmap_from_bitmap, create_image_bitmap_from_canvas, create_image_bitmap_from_image_data, image_bitmap_close, image_bitmap_height, image_bitmap_width, ImageBitmapOptions, to_byte_slice};
+
+fn to_options(
+    crop: jboolean,
+    sx: jfloat,
+    sy: jfloat,
+    sw: jfloat,
+    sh: jfloat,
+    flip_y: jboolean,
+    premultiply_alpha: jint,
+    color_space_conversion: jint,
+    resize_width: jfloat,
+    resize_height: jfloat,
+    resize_quality: jint,
+) -> ImageBitmapOptions {
+    ImageBitmapOptions {
+        crop: crop == JNI_TRUE,
+        sx,
+        sy,
+        sw,
+        sh,
+        flip_y: flip_y == JNI_TRUE,
+        premultiply_alpha: premultiply_alpha.into(),
+        color_space_conversion: color_space_conversion.into(),
+        resize_width,
+        resize_height,
+        resize_quality: resize_quality.into(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageBitmap_nativeCreateFromEncoded(
+    env: JNIEnv,
+    _: JClass,
+    image: jbyteArray,
+    crop: jboolean,
+    sx: jfloat,
+    sy: jfloat,
+    sw: jfloat,
+    sh: jfloat,
+    flip_y: jboolean,
+    premultiply_alpha: jint,
+    color_space_conversion: jint,
+    resize_width: jfloat,
+    resize_height: jfloat,
+    resize_quality: jint,
+) -> jlong {
+    let length = env.get_array_length(image).unwrap_or(0);
+    let mut bytes = vec![0i8; length as usize];
+    let _ = env.get_byte_array_region(image, 0, bytes.as_mut_slice());
+    let buf = to_byte_slice(bytes.as_mut_slice());
+    let options = to_options(crop, sx, sy, sw, sh, flip_y, premultiply_alpha, color_space_conversion, resize_width, resize_height, resize_quality);
+    create_image_bitmap_encoded(buf.as_ptr(), buf.len(), options)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageBitmap_nativeCreateFromAsset(
+    _env: JNIEnv,
+    _: JClass,
+    asset: jlong,
+    crop: jboolean,
+    sx: jfloat,
+    sy: jfloat,
+    sw: jfloat,
+    sh: jfloat,
+    flip_y: jboolean,
+    premultiply_alpha: jint,
+    color_space_conversion: jint,
+    resize_width: jfloat,
+    resize_height: jfloat,
+    resize_quality: jint,
+) -> jlong {
+    let options = to_options(crop, sx, sy, sw, sh, flip_y, premultiply_alpha, color_space_conversion, resize_width, resize_height, resize_quality);
+    create_image_bitmap_from_asset(asset, options)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageBitmap_nativeCreateFromImageData(
+    env: JNIEnv,
+    _: JClass,
+    data: jbyteArray,
+    width: jint,
+    height: jint,
+    crop: jboolean,
+    sx: jfloat,
+    sy: jfloat,
+    sw: jfloat,
+    sh: jfloat,
+    flip_y: jboolean,
+    premultiply_alpha: jint,
+    color_space_conversion: jint,
+    resize_width: jfloat,
+    resize_height: jfloat,
+    resize_quality: jint,
+) -> jlong {
+    let length = env.get_array_length(data).unwrap_or(0);
+    let mut pixels = vec![0i8; length as usize];
+    let _ = env.get_byte_array_region(data, 0, pixels.as_mut_slice());
+    let buf = to_byte_slice(pixels.as_mut_slice());
+    let options = to_options(crop, sx, sy, sw, sh, flip_y, premultiply_alpha, color_space_conversion, resize_width, resize_height, resize_quality);
+    create_image_bitmap_from_image_data(buf.as_ptr(), buf.len(), width, height, options)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageBitmap_nativeCreateFromCanvas(
+    _env: JNIEnv,
+    _: JClass,
+    canvas_native_ptr: jlong,
+    crop: jboolean,
+    sx: jfloat,
+    sy: jfloat,
+    sw: jfloat,
+    sh: jfloat,
+    flip_y: jboolean,
+    premultiply_alpha: jint,
+    color_space_conversion: jint,
+    resize_width: jfloat,
+    resize_height: jfloat,
+    resize_quality: jint,
+) -> jlong {
+    let options = to_options(crop, sx, sy, sw, sh, flip_y, premultiply_alpha, color_space_conversion, resize_width, resize_height, resize_quality);
+    create_image_bitmap_from_canvas(canvas_native_ptr, options)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageBitmap_nativeCreateFromImageBitmap(
+    _env: JNIEnv,
+    _: JClass,
+    bitmap: jlong,
+    crop: jboolean,
+    sx: jfloat,
+    sy: jfloat,
+    sw: jfloat,
+    sh: jfloat,
+    flip_y: jboolean,
+    premultiply_alpha: jint,
+    color_space_conversion: jint,
+    resize_width: jfloat,
+    resize_height: jfloat,
+    resize_quality: jint,
+) -> jlong {
+    let options = to_options(crop, sx, sy, sw, sh, flip_y, premultiply_alpha, color_space_conversion, resize_width, resize_height, resize_quality);
+    create_image_bitmap_from_bitmap(bitmap, options)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageBitmap_nativeGetWidth(_env: JNIEnv, _: JClass, bitmap: jlong) -> jint {
+    image_bitmap_width(bitmap)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageBitmap_nativeGetHeight(_env: JNIEnv, _: JClass, bitmap: jlong) -> jint {
+    image_bitmap_height(bitmap)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_ImageBitmap_nativeClose(_env: JNIEnv, _: JClass, bitmap: jlong) {
+    image_bitmap_close(bitmap)
+}
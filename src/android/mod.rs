@@ -4,4 +4,5 @@ pub mod core;
 pub mod text_decoder;
 pub mod text_encoder;
 pub mod image_asset;
+pub mod image_bitmap;
 pub mod webgl_rendering_context;
\ No newline at end of file
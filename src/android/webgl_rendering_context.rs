@@ -11,7 +11,7 @@ use jni::{
     JNIEnv,
     objects::{JClass, JObject},
 };
-use jni_sys::{jboolean, jbyteArray, jint, JNI_TRUE, jobject};
+use jni_sys::{jboolean, jbyteArray, jint, jlong, JNI_TRUE, jobject};
 use log::{debug};
 
 use crate::android::bitmap::{
@@ -20,6 +20,8 @@ use crate::android::bitmap::{
 };
 use jni::objects::JByteBuffer;
 
+use crate::common::{free_byte_array, image_bitmap_get_bytes};
+
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_WebGLRenderingContext_nativeFlipInPlace3D(env: JNIEnv, _: JClass, pixels: jbyteArray, width: jint, height: jint, depth: jint) {
     self::super::core::flip_in_place_3d(env, pixels, width, height, depth);
@@ -93,6 +95,18 @@ pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_WebGLRenderingContext_n
     storage
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_WebGLRenderingContext_nativeBytesFromImageBitmap(env: JNIEnv, _: JClass, bitmap: jlong, flipY: jboolean, premultiplyAlpha: jboolean) -> jbyteArray {
+    let array = image_bitmap_get_bytes(bitmap, flipY == JNI_TRUE, premultiplyAlpha == JNI_TRUE);
+    if array.array.is_null() {
+        return env.new_byte_array(0).unwrap();
+    }
+    let bytes = std::slice::from_raw_parts(array.array as *const u8, array.length);
+    let storage = env.byte_array_from_slice(bytes).unwrap_or(env.new_byte_array(0).unwrap());
+    free_byte_array(array);
+    storage
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn Java_com_github_triniwiz_canvas_WebGLRenderingContext_nativeGetVertexAttribOffset(env: JNIEnv, _: JClass, index: jint, pname: jint, buffer: JByteBuffer) {
     let buf = env.get_direct_buffer_address(buffer).unwrap();
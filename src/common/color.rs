@@ -0,0 +1,496 @@
+use std::os::raw::c_char;
+use std::ffi::CStr;
+
+use skia_safe::Color;
+
+/// Parses a CSS Color Level 4 value: hex, `rgb()`/`rgba()`, `hsl()`/`hsla()`, `hwb()`,
+/// named colors and `transparent`.
+pub(crate) fn parse_color(value: &str) -> Result<Color, String> {
+    let value = value.trim().to_ascii_lowercase();
+    if value.is_empty() {
+        return Err("Empty color".to_string());
+    }
+    let (r, g, b, a) = if value.starts_with('#') {
+        parse_hex(&value[1..])
+    } else if let Some(open) = value.find('(') {
+        if !value.ends_with(')') {
+            return Err(format!("Invalid color: {}", value));
+        }
+        let name = value[..open].trim();
+        let (args, legacy) = parse_arguments(&value[open + 1..value.len() - 1])
+            .ok_or_else(|| format!("Invalid color: {}", value))?;
+        match name {
+            "rgb" | "rgba" => parse_rgb(&args, legacy),
+            "hsl" | "hsla" => parse_hsl(&args, legacy),
+            // hwb() never had the comma syntax.
+            "hwb" if !legacy => parse_hwb(&args),
+            _ => None,
+        }
+    } else {
+        named_color(&value)
+    }
+    .ok_or_else(|| format!("Invalid color: {}", value))?;
+    Ok(Color::from_argb(a, r, g, b))
+}
+
+pub(crate) fn parse_color_ptr(value: *const c_char) -> Result<Color, String> {
+    if value.is_null() {
+        return Err("Empty color".to_string());
+    }
+    match unsafe { CStr::from_ptr(value) }.to_str() {
+        Ok(value) => parse_color(value),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+type Rgba = (u8, u8, u8, u8);
+
+fn parse_hex(digits: &str) -> Option<Rgba> {
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let nibble = |i: usize| u8::from_str_radix(&digits[i..i + 1], 16).ok().map(|v| v * 17);
+    let byte = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+    match digits.len() {
+        3 => Some((nibble(0)?, nibble(1)?, nibble(2)?, 255)),
+        4 => Some((nibble(0)?, nibble(1)?, nibble(2)?, nibble(3)?)),
+        6 => Some((byte(0)?, byte(2)?, byte(4)?, 255)),
+        8 => Some((byte(0)?, byte(2)?, byte(4)?, byte(6)?)),
+        _ => None,
+    }
+}
+
+// Splits the arguments of a color function in either the legacy comma syntax
+// `a, b, c[, alpha]` or the modern space syntax `a b c[ / alpha]`, and says which one
+// it was. Only the modern syntax allows `none`.
+fn parse_arguments(args: &str) -> Option<(Vec<&str>, bool)> {
+    let args = args.trim();
+    let legacy = args.contains(',');
+    let parts: Vec<&str> = if legacy {
+        if args.contains('/') {
+            return None;
+        }
+        let parts: Vec<&str> = args.split(',').map(|part| part.trim()).collect();
+        if parts.contains(&"none") {
+            return None;
+        }
+        parts
+    } else {
+        let mut split = args.splitn(2, '/');
+        let channels = split.next().unwrap_or("");
+        let mut parts: Vec<&str> = channels.split_whitespace().collect();
+        if let Some(alpha) = split.next() {
+            let alpha = alpha.trim();
+            if alpha.is_empty() || alpha.contains(char::is_whitespace) || parts.len() != 3 {
+                return None;
+            }
+            parts.push(alpha);
+        } else if parts.len() != 3 {
+            // Alpha only follows a slash here.
+            return None;
+        }
+        parts
+    };
+    if (parts.len() == 3 || parts.len() == 4) && parts.iter().all(|part| !part.is_empty()) {
+        Some((parts, legacy))
+    } else {
+        None
+    }
+}
+
+fn parse_float(value: &str) -> Option<f32> {
+    match value.parse::<f32>() {
+        Ok(number) if number.is_finite() => Some(number),
+        _ => None,
+    }
+}
+
+// A plain number, or `none`, which counts as zero.
+fn parse_number(value: &str) -> Option<f32> {
+    if value == "none" {
+        return Some(0.0);
+    }
+    parse_float(value)
+}
+
+fn parse_percentage(value: &str) -> Option<f32> {
+    if value.ends_with('%') {
+        parse_float(&value[..value.len() - 1])
+    } else {
+        None
+    }
+}
+
+// A percentage mapped onto 0..=`scale`, or a plain number used as is.
+fn parse_number_or_percentage(value: &str, scale: f32) -> Option<f32> {
+    match parse_percentage(value) {
+        Some(percentage) => Some(percentage / 100.0 * scale),
+        _ => parse_number(value),
+    }
+}
+
+fn parse_alpha(args: &[&str]) -> Option<u8> {
+    match args.get(3) {
+        Some(alpha) => {
+            let alpha = parse_number_or_percentage(alpha, 1.0)?;
+            Some(to_channel(alpha.max(0.0).min(1.0) * 255.0))
+        }
+        _ => Some(255),
+    }
+}
+
+fn parse_hue(value: &str) -> Option<f32> {
+    let units = [("deg", 1.0), ("grad", 0.9), ("rad", 180.0 / std::f32::consts::PI), ("turn", 360.0)];
+    for (unit, factor) in units.iter() {
+        if value.ends_with(unit) {
+            return parse_float(&value[..value.len() - unit.len()]).map(|hue| hue * factor);
+        }
+    }
+    parse_number(value)
+}
+
+fn to_channel(value: f32) -> u8 {
+    (value.max(0.0).min(255.0) + 0.5).floor() as u8
+}
+
+fn parse_rgb(args: &[&str], legacy: bool) -> Option<Rgba> {
+    // The comma syntax can't mix numbers and percentages across the channels.
+    let percentages = args[..3].iter().filter(|arg| arg.ends_with('%')).count();
+    if legacy && percentages != 0 && percentages != 3 {
+        return None;
+    }
+    let r = parse_number_or_percentage(args[0], 255.0)?;
+    let g = parse_number_or_percentage(args[1], 255.0)?;
+    let b = parse_number_or_percentage(args[2], 255.0)?;
+    Some((to_channel(r), to_channel(g), to_channel(b), parse_alpha(args)?))
+}
+
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> (f32, f32, f32) {
+    let hue = hue.rem_euclid(360.0);
+    let f = |n: f32| {
+        let k = (n + hue / 30.0) % 12.0;
+        let a = saturation * lightness.min(1.0 - lightness);
+        lightness - a * (k - 3.0).min(9.0 - k).min(1.0).max(-1.0)
+    };
+    (f(0.0), f(8.0), f(4.0))
+}
+
+fn parse_hsl(args: &[&str], legacy: bool) -> Option<Rgba> {
+    // The comma syntax needs percentages for saturation and lightness.
+    if legacy && !(args[1].ends_with('%') && args[2].ends_with('%')) {
+        return None;
+    }
+    let hue = parse_hue(args[0])?;
+    let saturation = (parse_number_or_percentage(args[1], 100.0)? / 100.0).max(0.0).min(1.0);
+    let lightness = (parse_number_or_percentage(args[2], 100.0)? / 100.0).max(0.0).min(1.0);
+    let (r, g, b) = hsl_to_rgb(hue, saturation, lightness);
+    Some((to_channel(r * 255.0), to_channel(g * 255.0), to_channel(b * 255.0), parse_alpha(args)?))
+}
+
+fn parse_hwb(args: &[&str]) -> Option<Rgba> {
+    let hue = parse_hue(args[0])?;
+    let mut whiteness = (parse_number_or_percentage(args[1], 100.0)? / 100.0).max(0.0).min(1.0);
+    let mut blackness = (parse_number_or_percentage(args[2], 100.0)? / 100.0).max(0.0).min(1.0);
+    if whiteness + blackness >= 1.0 {
+        let gray = whiteness / (whiteness + blackness);
+        whiteness = gray;
+        blackness = 1.0 - gray;
+    }
+    let (r, g, b) = hsl_to_rgb(hue, 1.0, 0.5);
+    let channel = |value: f32| to_channel((value * (1.0 - whiteness - blackness) + whiteness) * 255.0);
+    Some((channel(r), channel(g), channel(b), parse_alpha(args)?))
+}
+
+// The CSS named colors, sorted by name.
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+fn named_color(name: &str) -> Option<Rgba> {
+    if name == "transparent" {
+        return Some((0, 0, 0, 0));
+    }
+    let index = NAMED_COLORS.binary_search_by_key(&name, |(name, _)| *name).ok()?;
+    let rgb = NAMED_COLORS[index].1;
+    Some(((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 255))
+}
+
+/// Serializes a color the way the canvas spec does for fillStyle, strokeStyle and
+/// shadowColor: `#rrggbb` when opaque, `rgba(r, g, b, a)` otherwise.
+pub(crate) fn serialize_color(color: Color) -> String {
+    let alpha = color.a();
+    if alpha == 255 {
+        return format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b());
+    }
+    // Use the fewest decimals that still round trip to the same 8-bit alpha.
+    let value = alpha as f32 / 255.0;
+    let mut formatted = format!("{:.2}", value);
+    if formatted.parse::<f32>().map(|v| (v * 255.0).round() as u8) != Ok(alpha) {
+        formatted = format!("{:.3}", value);
+    }
+    let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+    let formatted = if formatted.is_empty() { "0" } else { formatted };
+    format!("rgba({}, {}, {}, {})", color.r(), color.g(), color.b(), formatted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba(value: &str) -> Option<(u8, u8, u8, u8)> {
+        parse_color(value).ok().map(|color| (color.r(), color.g(), color.b(), color.a()))
+    }
+
+    #[test]
+    fn parses_hex() {
+        assert_eq!(rgba("#f80"), Some((255, 136, 0, 255)));
+        assert_eq!(rgba("#f808"), Some((255, 136, 0, 136)));
+        assert_eq!(rgba("#FF8000"), Some((255, 128, 0, 255)));
+        assert_eq!(rgba("#ff800080"), Some((255, 128, 0, 128)));
+        for value in ["#", "#f", "#ff", "#fffff", "#fffffff", "#fffffffff", "#ggg", "#12 34 56", "f80"].iter() {
+            assert_eq!(rgba(value), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn parses_rgb_in_both_syntaxes() {
+        assert_eq!(rgba("rgb(255, 128, 0)"), Some((255, 128, 0, 255)));
+        assert_eq!(rgba("rgba(255,128,0,0.5)"), Some((255, 128, 0, 128)));
+        assert_eq!(rgba("rgb(100%, 50%, 0%, 50%)"), Some((255, 128, 0, 128)));
+        assert_eq!(rgba("rgb(255 128 0)"), Some((255, 128, 0, 255)));
+        assert_eq!(rgba("rgb(255 128 0 / 25%)"), Some((255, 128, 0, 64)));
+        assert_eq!(rgba("rgba(100% 50% 0 / 0.5)"), Some((255, 128, 0, 128)));
+        assert_eq!(rgba("RGB( 300 -5 127.6 / 2 )"), Some((255, 0, 128, 255)));
+        assert_eq!(rgba("rgb(none 128 none / none)"), Some((0, 128, 0, 0)));
+    }
+
+    #[test]
+    fn parses_hsl_and_hwb() {
+        assert_eq!(rgba("hsl(0, 100%, 50%)"), Some((255, 0, 0, 255)));
+        assert_eq!(rgba("hsla(120, 100%, 25%, 0.5)"), Some((0, 128, 0, 128)));
+        assert_eq!(rgba("hsl(240deg 100% 50%)"), Some((0, 0, 255, 255)));
+        assert_eq!(rgba("hsl(0.5turn 100 50 / 1)"), Some((0, 255, 255, 255)));
+        assert_eq!(rgba("hsl(-120 100% 50%)"), Some((0, 0, 255, 255)));
+        assert_eq!(rgba("hsl(none 0% 100%)"), Some((255, 255, 255, 255)));
+        assert_eq!(rgba("hwb(0 0% 0%)"), Some((255, 0, 0, 255)));
+        assert_eq!(rgba("hwb(120 20% 30% / 50%)"), Some((51, 179, 51, 128)));
+        assert_eq!(rgba("hwb(0 60% 60%)"), Some((128, 128, 128, 255)));
+    }
+
+    #[test]
+    fn rejects_none_in_the_comma_syntax() {
+        for value in ["rgb(none, 0, 0)", "rgba(0, 0, 0, none)", "hsl(none, 50%, 50%)"].iter() {
+            assert_eq!(rgba(value), None, "{}", value);
+        }
+        assert_eq!(rgba("rgb(none% 0 0)"), None);
+        assert_eq!(rgba("hsl(nonedeg 0% 0%)"), None);
+    }
+
+    #[test]
+    fn rejects_mixed_legacy_units() {
+        let values = [
+            "rgb(255, 50%, 0)",
+            "rgb(100%, 0, 0)",
+            "rgba(0, 0, 100%, 1)",
+            "hsl(0, 100, 50%)",
+            "hsl(0, 100%, 50)",
+            "hwb(0, 0%, 0%)",
+        ];
+        for value in values.iter() {
+            assert_eq!(rgba(value), None, "{}", value);
+        }
+        // The modern syntax mixes them freely.
+        assert_eq!(rgba("rgb(255 50% 0)"), Some((255, 128, 0, 255)));
+        assert_eq!(rgba("hsl(0 100 50%)"), Some((255, 0, 0, 255)));
+    }
+
+    #[test]
+    fn rejects_malformed_functions() {
+        let values = [
+            "",
+            "rgb(0, 0)",
+            "rgb(0, 0, 0, 0, 0)",
+            "rgb(0 0 0 0)",
+            "rgb(0, 0, 0 / 1)",
+            "rgb(0 0 / 1)",
+            "rgb(0 0 0 /)",
+            "rgb(0, , 0)",
+            "rgb(0 0 0",
+            "rgb(a b c)",
+            "cmyk(0 0 0)",
+            "notacolor",
+        ];
+        for value in values.iter() {
+            assert_eq!(rgba(value), None, "{}", value);
+        }
+    }
+
+    #[test]
+    fn parses_every_named_color() {
+        assert!(NAMED_COLORS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        for (name, rgb) in NAMED_COLORS.iter() {
+            let expected = Some(((rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8, 255));
+            assert_eq!(rgba(name), expected, "{}", name);
+            assert_eq!(rgba(&name.to_ascii_uppercase()), expected, "{}", name);
+        }
+        assert_eq!(rgba("rebeccapurple"), Some((102, 51, 153, 255)));
+        assert_eq!(rgba("transparent"), Some((0, 0, 0, 0)));
+        assert_eq!(rgba("grey"), rgba("gray"));
+        assert_eq!(rgba("bluish"), None);
+    }
+
+    #[test]
+    fn serializes_like_the_canvas_spec() {
+        assert_eq!(serialize_color(Color::from_argb(255, 255, 128, 0)), "#ff8000");
+        assert_eq!(serialize_color(Color::from_argb(128, 255, 128, 0)), "rgba(255, 128, 0, 0.5)");
+        assert_eq!(serialize_color(Color::from_argb(0, 0, 0, 0)), "rgba(0, 0, 0, 0)");
+        assert_eq!(serialize_color(Color::from_argb(1, 0, 0, 0)), "rgba(0, 0, 0, 0.004)");
+    }
+}
@@ -5,13 +5,16 @@ use std::ffi::{CStr, CString};
 use std::mem;
 use std::os::raw::{c_char, c_uint, c_void};
 use std::ptr::{null, null_mut};
+use std::sync::{Arc, Mutex};
 use libc::{c_float, c_int, c_longlong, size_t};
-use skia_safe::{AlphaType, BlendMode, BlurStyle, ClipOp, Color, ColorType, Data, EncodedImageFormat, FilterQuality, Font, FontStyle, Image, ImageFilter, ImageInfo, IPoint, ISize, MaskFilter, Matrix, Paint, Path, PathEffect, Point, Rect, Shader, Surface, TileMode, Typeface, Vector, canvas::SrcRectConstraint, gpu::Context, gradient_shader::GradientShaderColors, image::CachingHint, image_filters::drop_shadow, paint::{Cap, Join, Style}, path::FillType, utils::text_utils::Align, Size};
+use skia_safe::{AlphaType, BlendMode, BlurStyle, Canvas, ClipOp, Color, ColorType, CornerPathEffect, Data, DiscretePathEffect, EncodedImageFormat, FilterQuality, Font, FontStyle, Image, ImageFilter, ImageInfo, IPoint, IRect, ISize, MaskFilter, Matrix, Paint, Path, Path1DPathEffect, PathEffect, Point, Rect, Shader, Surface, TileMode, Typeface, Vector, canvas::SrcRectConstraint, gpu::Context, gradient_shader::GradientShaderColors, image::CachingHint, image_filters::drop_shadow, paint::{Cap, Join, Style}, path::FillType, path_1d_path_effect::Style as Path1DStyle, utils::text_utils::Align, Size};
 //use skia_safe::wrapper::PointerWrapper;
 use skia_safe::wrapper::ValueWrapper;
 //use skia_safe::wrapper::NativeTransmutableWrapper;
 //use skia_safe::wrapper::RefWrapper;
 
+use crate::common::{CanvasGradient, CanvasGradientKind, ImageBitmap, ImageCache, SharedGradient, NativeImageAsset, cached_data_url_image, cached_encoded_image, image_from_asset, parse_color_ptr, parse_data_url_ptr, parse_filter, parse_filter_mapped, serialize_color};
+
 pub const COLOR_BLACK: usize = 0xff000000 as usize;
 pub const COLOR_WHITE: usize = 0xffffffff as usize;
 pub const COLOR_TRANSPARENT: usize = 0x00000000 as usize;
@@ -120,13 +123,106 @@ pub(crate) fn adjust_end_angle(
     return round_32(new_end_angle as f64, 3);
 }
 
-fn det(matrix: &Matrix) -> f32 {
-    let transform = matrix.to_affine().unwrap();
-    return transform[0] * transform[3] - transform[1] * transform[2];
+pub(crate) fn fill_type_from_rule(fill_rule: *const c_char) -> FillType {
+    if fill_rule.is_null() {
+        return FillType::Winding;
+    }
+    match unsafe { CStr::from_ptr(fill_rule) }
+        .to_str()
+        .unwrap_or("nonzero")
+    {
+        "evenodd" => FillType::EvenOdd,
+        _ => FillType::Winding,
+    }
 }
 
-fn is_invertible(matrix: &Matrix) -> bool {
-    return det(matrix) != 0.0;
+// Paths are tested in device space so the tolerance is a radius in device pixels,
+// matching what a touch target looks like on screen whatever the current transform.
+fn device_path_contains(path: &Path, point: Point, tolerance: f32) -> bool {
+    let bounds = path.bounds();
+    if point.x < bounds.left - tolerance
+        || point.x > bounds.right + tolerance
+        || point.y < bounds.top - tolerance
+        || point.y > bounds.bottom + tolerance
+    {
+        return false;
+    }
+    if path.contains(point) {
+        return true;
+    }
+    if tolerance > 0.0 {
+        let mut paint = Paint::default();
+        paint.set_anti_alias(true);
+        paint.set_style(Style::Stroke);
+        paint.set_stroke_width(tolerance * 2.0);
+        paint.set_stroke_cap(Cap::Round);
+        paint.set_stroke_join(Join::Round);
+        if let Some(mut area) = paint.get_fill_path(path, None, None) {
+            area.set_fill_type(FillType::Winding);
+            return area.contains(point);
+        }
+    }
+    false
+}
+
+/// Tests `paths` against the point (x, y) in canvas coordinates and returns the
+/// indices of the paths that contain it, topmost (last) first. Unless `find_all`
+/// is set only the topmost match is returned.
+#[inline]
+pub(crate) fn hit_test_paths(
+    canvas_ptr: c_longlong,
+    paths: &[c_longlong],
+    x: f32,
+    y: f32,
+    tolerance: f32,
+    fill_rule: *const c_char,
+    is_stroke: bool,
+    find_all: bool,
+) -> Vec<c_int> {
+    let mut hits: Vec<c_int> = Vec::new();
+    if canvas_ptr == 0 || !x.is_finite() || !y.is_finite() {
+        return hits;
+    }
+    let tolerance = if tolerance.is_finite() && tolerance > 0.0 {
+        tolerance
+    } else {
+        0.0
+    };
+    let mut canvas_native: Box<CanvasNative> = unsafe { Box::from_raw(canvas_ptr as *mut _) };
+    let matrix = canvas_native.surface.canvas().total_matrix();
+    if matrix.invert().is_some() {
+        let fill_type = fill_type_from_rule(fill_rule);
+        let point = Point::new(x, y);
+        for (index, path) in paths.iter().enumerate().rev() {
+            if *path == 0 {
+                continue;
+            }
+            let path: Box<Path> = unsafe { Box::from_raw(*path as *mut _) };
+            let device_path = if is_stroke {
+                stroke_outline(&canvas_native.stroke_paint, &path, &matrix)
+            } else {
+                let mut fill_path = path.as_ref().clone();
+                fill_path.set_fill_type(fill_type);
+                Some(fill_path)
+            };
+            Box::into_raw(path);
+            let hit = match device_path {
+                Some(mut device_path) => {
+                    device_path.transform(&matrix);
+                    device_path_contains(&device_path, point, tolerance)
+                }
+                _ => false,
+            };
+            if hit {
+                hits.push(index as c_int);
+                if !find_all {
+                    break;
+                }
+            }
+        }
+    }
+    Box::into_raw(canvas_native);
+    hits
 }
 
 #[inline]
@@ -137,60 +233,172 @@ pub(crate) fn is_point_in_path(
     y: f32,
     fill_rule: *const c_char,
 ) -> bool {
-    let mut canvas_native: Box<CanvasNative> = unsafe { Box::from_raw(canvas_ptr as *mut _) };
-    let surface = &mut canvas_native.surface;
-    let canvas = surface.canvas();
-    let invertible = is_invertible(&canvas.total_matrix());
-    if !invertible {
-        return false;
+    !hit_test_paths(canvas_ptr, &[path], x, y, 0.0, fill_rule, false, false).is_empty()
+}
+
+#[inline]
+pub(crate) fn is_point_in_stroke(canvas_ptr: i64, path: i64, x: f32, y: f32) -> bool {
+    !hit_test_paths(canvas_ptr, &[path], x, y, 0.0, null(), true, false).is_empty()
+}
+
+// Mirrors SkMatrixPriv::ComputeResScaleForStroking so curves in the outline are
+// subdivided finely enough for the scale they will be drawn at.
+fn compute_res_scale_for_stroking(matrix: &Matrix) -> f32 {
+    let sx = (matrix.scale_x() * matrix.scale_x() + matrix.skew_y() * matrix.skew_y()).sqrt();
+    let sy = (matrix.skew_x() * matrix.skew_x() + matrix.scale_y() * matrix.scale_y()).sqrt();
+    if sx.is_finite() && sy.is_finite() {
+        let scale = sx.max(sy);
+        if scale > 0.0 {
+            return scale;
+        }
     }
-    if !x.is_finite() || !y.is_finite() {
-        return false;
+    1.0
+}
+
+pub(crate) fn stroke_outline(paint: &Paint, path: &Path, matrix: &Matrix) -> Option<Path> {
+    let mut outline = paint.get_fill_path(path, None, Some(compute_res_scale_for_stroking(matrix)))?;
+    outline.set_fill_type(FillType::Winding);
+    Some(outline)
+}
+
+#[inline]
+pub(crate) fn stroke_to_path(canvas_native_ptr: c_longlong, path: c_longlong) -> c_longlong {
+    if canvas_native_ptr == 0 || path == 0 {
+        return 0;
     }
-    let matrix = canvas.total_matrix().clone();
-    let inverse = matrix.invert().unwrap();
-    let point = Point::new(x, y);
-    let transformed_point = inverse.map_point(point);
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
     let path: Box<Path> = unsafe { Box::from_raw(path as *mut _) };
-    let mut path_to_compare = path.clone();
-    let fill = match unsafe { CStr::from_ptr(fill_rule) }
-        .to_str()
-        .unwrap_or("nonzero")
-    {
-        "evenodd" => FillType::EvenOdd,
-        _ => FillType::Winding,
-    };
-    path_to_compare.set_fill_type(fill);
-    let result = path_to_compare.contains(transformed_point);
-    let _ = Box::into_raw(canvas_native);
-    let _ = Box::into_raw(path);
-    result
+    let matrix = canvas_native.surface.canvas().total_matrix();
+    let outline = stroke_outline(&canvas_native.stroke_paint, &path, &matrix);
+    Box::into_raw(path);
+    Box::into_raw(canvas_native);
+    match outline {
+        Some(outline) => Box::into_raw(Box::new(outline)) as *mut _ as i64,
+        _ => 0,
+    }
 }
 
 #[inline]
-pub(crate) fn is_point_in_stroke(canvas_ptr: i64, path: i64, x: f32, y: f32) -> bool {
-    let mut canvas_native: Box<CanvasNative> = unsafe { Box::from_raw(canvas_ptr as *mut _) };
-    let surface = &mut canvas_native.surface;
-    let canvas = surface.canvas();
-    let invertible = is_invertible(&canvas.total_matrix());
-    if !invertible {
-        return false;
+pub(crate) fn add_hit_region(
+    canvas_native_ptr: c_longlong,
+    path: c_longlong,
+    id: *const c_char,
+    is_stroke: bool,
+    fill_rule: *const c_char,
+) -> c_longlong {
+    if canvas_native_ptr == 0 || id.is_null() {
+        return canvas_native_ptr;
     }
-    if !x.is_finite() || !y.is_finite() {
-        return false;
+    let id = unsafe { CStr::from_ptr(id) }.to_str().unwrap_or("");
+    if id.is_empty() {
+        return canvas_native_ptr;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let source = if path == 0 {
+        canvas_native.path.clone()
+    } else {
+        let path: Box<Path> = unsafe { Box::from_raw(path as *mut _) };
+        let source = path.as_ref().clone();
+        Box::into_raw(path);
+        source
+    };
+    let canvas = canvas_native.surface.canvas();
+    let matrix = canvas.total_matrix();
+    let clip = canvas.device_clip_bounds();
+    let region_path = if is_stroke {
+        stroke_outline(&canvas_native.stroke_paint, &source, &matrix)
+    } else {
+        let mut fill_path = source;
+        fill_path.set_fill_type(fill_type_from_rule(fill_rule));
+        Some(fill_path)
+    };
+    canvas_native.hit_regions.retain(|region| region.id != id);
+    if let (Some(mut region_path), Some(clip)) = (region_path, clip) {
+        region_path.transform(&matrix);
+        let mut bounds = region_path.bounds().clone();
+        if bounds.intersect(Rect::from(clip)) {
+            let clip = canvas_native.clip_paths.clone();
+            canvas_native.hit_regions.push(HitRegion {
+                id: id.to_string(),
+                path: region_path,
+                clip,
+                bounds,
+            });
+        }
     }
-    let matrix = canvas.total_matrix().clone();
-    let inverse = matrix.invert().unwrap();
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
+
+/// Returns the id of the topmost hit region containing (x, y), or null when there
+/// is none. The returned string must be released with `free_char`.
+#[inline]
+pub(crate) fn hit_region_at(canvas_native_ptr: c_longlong, x: c_float, y: c_float) -> *const c_char {
+    if canvas_native_ptr == 0 || !x.is_finite() || !y.is_finite() {
+        return null();
+    }
+    let canvas_native: Box<CanvasNative> = unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
     let point = Point::new(x, y);
-    let transformed_point = inverse.map_point(point);
-    let path: Box<Path> = unsafe { Box::from_raw(path as *mut _) };
-    let path_to_compare = path.clone();
-    let result = path_to_compare.contains(transformed_point);
-    let _ = Box::into_raw(canvas_native);
-    let _ = Box::into_raw(path);
+    let mut result = null();
+    for region in canvas_native.hit_regions.iter().rev() {
+        if !region.bounds.contains(point) {
+            continue;
+        }
+        if region.path.contains(point) && region.clip.iter().all(|clip| clip.contains(point)) {
+            result = CString::new(region.id.as_str()).unwrap().into_raw() as *const c_char;
+            break;
+        }
+    }
+    Box::into_raw(canvas_native);
     result
 }
 
+#[inline]
+pub(crate) fn remove_hit_region(canvas_native_ptr: c_longlong, id: *const c_char) -> c_longlong {
+    if canvas_native_ptr == 0 || id.is_null() {
+        return canvas_native_ptr;
+    }
+    let id = unsafe { CStr::from_ptr(id) }.to_str().unwrap_or("");
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    canvas_native.hit_regions.retain(|region| region.id != id);
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
+
+#[inline]
+pub(crate) fn clear_hit_regions(canvas_native_ptr: c_longlong) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    canvas_native.hit_regions.clear();
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
+
+// Drops every region whose area lies entirely inside the cleared rect. The rect is
+// mapped to device space as a quad so rotated clears are handled too.
+fn clear_hit_regions_in_rect(canvas_native: &mut CanvasNative, rect: &Rect) {
+    if canvas_native.hit_regions.is_empty() {
+        return;
+    }
+    let matrix = canvas_native.surface.canvas().total_matrix();
+    let mut cleared = Path::new();
+    cleared.add_rect(rect, None);
+    cleared.transform(&matrix);
+    canvas_native.hit_regions.retain(|region| {
+        let bounds = &region.bounds;
+        let corners = [
+            Point::new(bounds.left, bounds.top),
+            Point::new(bounds.right, bounds.top),
+            Point::new(bounds.right, bounds.bottom),
+            Point::new(bounds.left, bounds.bottom),
+        ];
+        !corners.iter().all(|corner| cleared.contains(*corner))
+    });
+}
+
 pub struct CanvasStateItem {
     pub(crate) state: i64,
     pub(crate) count: usize,
@@ -219,6 +427,46 @@ pub enum SurfaceKind {
     GPU,
 }
 
+/// What fillStyle or strokeStyle currently holds. Colors keep the value that was
+/// assigned since the paint alpha also carries globalAlpha. Gradients and patterns
+/// hold a reference of their own, so freeing the handle they were set from doesn't
+/// invalidate them.
+#[derive(Clone)]
+pub enum PaintStyle {
+    Color(Color),
+    Gradient(SharedGradient),
+    Pattern(SharedPattern),
+}
+
+impl PaintStyle {
+    /// The shader a gradient or pattern style draws with right now, so stops added
+    /// and pattern transforms set after the style was assigned are drawn. `None`
+    /// leaves the paint's own shader.
+    pub(crate) fn current_shader(&self) -> Option<Shader> {
+        match self {
+            PaintStyle::Gradient(gradient) => gradient.lock().unwrap().shader(),
+            PaintStyle::Pattern(pattern) => Some(pattern.lock().unwrap().to_shader()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for PaintStyle {
+    fn default() -> Self {
+        PaintStyle::Color(Color::BLACK)
+    }
+}
+
+/// A shape registered with `add_hit_region`. The path is kept in device space so
+/// later transforms don't move it, and hits are limited to the clip paths in effect
+/// when the region was added.
+pub struct HitRegion {
+    pub(crate) id: String,
+    pub(crate) path: Path,
+    pub(crate) clip: Vec<Path>,
+    pub(crate) bounds: Rect,
+}
+
 #[repr(C)]
 pub struct CanvasNative {
     pub(crate) surface: Surface,
@@ -243,7 +491,18 @@ pub struct CanvasNative {
     pub(crate) line_join: String,
     pub(crate) direction: String,
     pub(crate) miter_limit: f32,
+    pub(crate) global_alpha: f32,
+    pub(crate) filter: String,
+    pub(crate) image_filter: Option<ImageFilter>,
+    pub(crate) stroke_style: PaintStyle,
+    pub(crate) fill_style: PaintStyle,
+    pub(crate) path_effects: CanvasPathEffects,
+    pub(crate) line_dash: Vec<f32>,
+    /// The clip paths applied so far, in device space. The clip is their intersection.
+    pub(crate) clip_paths: Vec<Path>,
     pub(crate) surface_kind: SurfaceKind,
+    pub(crate) hit_regions: Vec<HitRegion>,
+    pub(crate) image_cache: ImageCache,
 }
 
 impl CanvasNative {
@@ -267,6 +526,14 @@ impl CanvasNative {
         self.line_join = state.line_join;
         self.direction = state.direction;
         self.miter_limit = state.miter_limit;
+        self.global_alpha = state.global_alpha;
+        self.filter = state.filter;
+        self.image_filter = state.image_filter;
+        self.stroke_style = state.stroke_style;
+        self.fill_style = state.fill_style;
+        self.path_effects = state.path_effects;
+        self.line_dash = state.line_dash;
+        self.clip_paths = state.clip_paths;
         self.surface_kind = state.surface_kind;
     }
 
@@ -290,6 +557,14 @@ impl CanvasNative {
         self.line_join = state.line_join;
         self.direction = state.direction;
         self.miter_limit = state.miter_limit;
+        self.global_alpha = state.global_alpha;
+        self.filter = state.filter;
+        self.image_filter = state.image_filter;
+        self.stroke_style = state.stroke_style;
+        self.fill_style = state.fill_style;
+        self.path_effects = state.path_effects;
+        self.line_dash = state.line_dash;
+        self.clip_paths = state.clip_paths;
         self.surface_kind = state.surface_kind;
     }
 
@@ -315,6 +590,14 @@ impl CanvasNative {
         self.line_join = canvas.line_join;
         self.direction = canvas.direction;
         self.miter_limit = canvas.miter_limit;
+        self.global_alpha = canvas.global_alpha;
+        self.filter = canvas.filter;
+        self.image_filter = canvas.image_filter;
+        self.stroke_style = canvas.stroke_style;
+        self.fill_style = canvas.fill_style;
+        self.path_effects = canvas.path_effects;
+        self.line_dash = canvas.line_dash;
+        self.clip_paths = canvas.clip_paths;
         self.surface_kind = canvas.surface_kind;
     }
 }
@@ -340,6 +623,14 @@ pub struct CanvasState {
     pub(crate) line_join: String,
     pub(crate) direction: String,
     pub(crate) miter_limit: f32,
+    pub(crate) global_alpha: f32,
+    pub(crate) filter: String,
+    pub(crate) image_filter: Option<ImageFilter>,
+    pub(crate) stroke_style: PaintStyle,
+    pub(crate) fill_style: PaintStyle,
+    pub(crate) path_effects: CanvasPathEffects,
+    pub(crate) line_dash: Vec<f32>,
+    pub(crate) clip_paths: Vec<Path>,
     pub(crate) surface_kind: SurfaceKind,
 }
 
@@ -367,7 +658,7 @@ pub fn is_font_size(text: &str) -> bool {
     return text.contains("px");
 }
 
-#[derive(Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum CanvasCompositeOperationType {
     SourceOver,
     SourceIn,
@@ -398,8 +689,11 @@ pub enum CanvasCompositeOperationType {
 }
 
 impl CanvasCompositeOperationType {
-    pub fn value_from_str(value: &str) -> Self {
-        match value {
+    /// Returns `None` for anything that isn't a known operation, which the setter
+    /// ignores.
+    pub fn value_from_str(value: &str) -> Option<Self> {
+        let operation = match value {
+            "source-over" => CanvasCompositeOperationType::SourceOver,
             "source-in" => CanvasCompositeOperationType::SourceIn,
             "source-out" => CanvasCompositeOperationType::SourceOut,
             "source-atop" => CanvasCompositeOperationType::SourceAtop,
@@ -425,12 +719,45 @@ impl CanvasCompositeOperationType {
             "saturation" => CanvasCompositeOperationType::Saturation,
             "color" => CanvasCompositeOperationType::Color,
             "luminosity" => CanvasCompositeOperationType::Luminosity,
-            _ => CanvasCompositeOperationType::SourceOver,
+            _ => return None,
+        };
+        Some(operation)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CanvasCompositeOperationType::SourceOver => "source-over",
+            CanvasCompositeOperationType::SourceIn => "source-in",
+            CanvasCompositeOperationType::SourceOut => "source-out",
+            CanvasCompositeOperationType::SourceAtop => "source-atop",
+            CanvasCompositeOperationType::DestinationOver => "destination-over",
+            CanvasCompositeOperationType::DestinationIn => "destination-in",
+            CanvasCompositeOperationType::DestinationOut => "destination-out",
+            CanvasCompositeOperationType::DestinationAtop => "destination-atop",
+            CanvasCompositeOperationType::Lighter => "lighter",
+            CanvasCompositeOperationType::Copy => "copy",
+            CanvasCompositeOperationType::Xor => "xor",
+            CanvasCompositeOperationType::Multiply => "multiply",
+            CanvasCompositeOperationType::Screen => "screen",
+            CanvasCompositeOperationType::Overlay => "overlay",
+            CanvasCompositeOperationType::Darken => "darken",
+            CanvasCompositeOperationType::Lighten => "lighten",
+            CanvasCompositeOperationType::ColorDodge => "color-dodge",
+            CanvasCompositeOperationType::ColorBurn => "color-burn",
+            CanvasCompositeOperationType::HardLight => "hard-light",
+            CanvasCompositeOperationType::SoftLight => "soft-light",
+            CanvasCompositeOperationType::Difference => "difference",
+            CanvasCompositeOperationType::Exclusion => "exclusion",
+            CanvasCompositeOperationType::Hue => "hue",
+            CanvasCompositeOperationType::Saturation => "saturation",
+            CanvasCompositeOperationType::Color => "color",
+            CanvasCompositeOperationType::Luminosity => "luminosity",
         }
     }
 
     pub fn get_blend_mode(&self) -> BlendMode {
         match self {
+            CanvasCompositeOperationType::SourceOver => BlendMode::SrcOver,
             CanvasCompositeOperationType::SourceIn => BlendMode::SrcIn,
             CanvasCompositeOperationType::SourceOut => BlendMode::SrcOut,
             CanvasCompositeOperationType::SourceAtop => BlendMode::SrcATop,
@@ -438,7 +765,7 @@ impl CanvasCompositeOperationType {
             CanvasCompositeOperationType::DestinationIn => BlendMode::DstIn,
             CanvasCompositeOperationType::DestinationOut => BlendMode::DstOut,
             CanvasCompositeOperationType::DestinationAtop => BlendMode::DstATop,
-            CanvasCompositeOperationType::Lighter => BlendMode::Lighten,
+            CanvasCompositeOperationType::Lighter => BlendMode::Plus,
             CanvasCompositeOperationType::Copy => BlendMode::Src,
             CanvasCompositeOperationType::Xor => BlendMode::Xor,
             CanvasCompositeOperationType::Multiply => BlendMode::Multiply,
@@ -456,7 +783,6 @@ impl CanvasCompositeOperationType {
             CanvasCompositeOperationType::Saturation => BlendMode::Saturation,
             CanvasCompositeOperationType::Color => BlendMode::Color,
             CanvasCompositeOperationType::Luminosity => BlendMode::Luminosity,
-            _ => BlendMode::SrcOver,
         }
     }
 }
@@ -481,13 +807,61 @@ pub struct CanvasPattern {
     matrix: Matrix,
 }
 
+impl CanvasPattern {
+    pub fn to_shader(&self) -> Shader {
+        let mode: (TileMode, TileMode) = match self.repetition.as_str() {
+            "no-repeat" => (TileMode::Clamp, TileMode::Clamp),
+            "repeat-x" => (TileMode::Repeat, TileMode::Clamp),
+            "repeat-y" => (TileMode::Clamp, TileMode::Repeat),
+            _ => (TileMode::Repeat, TileMode::Repeat),
+        };
+        self.image.to_shader(mode, &self.matrix)
+    }
+}
+
+/// Pattern handles point at one of these. Each handle holds its own reference, so
+/// the pattern lives until every handle is freed and no style uses it.
+pub type SharedPattern = Arc<Mutex<CanvasPattern>>;
+
+/// A new handle to `pattern`, released with `free_pattern`.
+pub(crate) fn pattern_handle(pattern: &SharedPattern) -> c_longlong {
+    Box::into_raw(Box::new(pattern.clone())) as *mut _ as i64
+}
+
 #[inline]
 pub(crate) fn free_pattern(pattern: c_longlong) {
     if pattern != 0 {
-        let _: Box<CanvasPattern> = unsafe { Box::from_raw(pattern as *mut _) };
+        let _: Box<SharedPattern> = unsafe { Box::from_raw(pattern as *mut _) };
     }
 }
 
+// Returns 0 for a missing or zero-sized image and for an invalid repetition, the cases
+// where createPattern returns null or throws.
+fn new_pattern(image: Option<Image>, repetition: *const c_char) -> c_longlong {
+    let image = match image {
+        Some(image) if image.width() > 0 && image.height() > 0 => image,
+        _ => return 0,
+    };
+    let rep = if repetition.is_null() {
+        ""
+    } else {
+        match unsafe { CStr::from_ptr(repetition) }.to_str() {
+            Ok(rep) => rep,
+            _ => return 0,
+        }
+    };
+    let rep = match rep {
+        "" | "repeat" => "repeat",
+        "repeat-x" | "repeat-y" | "no-repeat" => rep,
+        _ => return 0,
+    };
+    pattern_handle(&Arc::new(Mutex::new(CanvasPattern {
+        image,
+        repetition: String::from(rep),
+        matrix: Matrix::default(),
+    })))
+}
+
 #[inline]
 pub(crate) fn create_pattern(
     image_data: *const u8,
@@ -505,14 +879,7 @@ pub(crate) fn create_pattern(
         None,
     );
     let image_new = Image::from_raster_data(&info, data, (original_width * 4) as usize);
-    let rep = unsafe { CStr::from_ptr(repetition) }
-        .to_str()
-        .unwrap_or("repeat");
-    Box::into_raw(Box::new(CanvasPattern {
-        image: image_new.unwrap(),
-        repetition: String::from(rep),
-        matrix: Matrix::default(),
-    })) as i64
+    new_pattern(image_new, repetition)
 }
 
 
@@ -525,15 +892,91 @@ pub(crate) fn create_pattern_encoded(
     let image_slice: &[u8] = unsafe { std::slice::from_raw_parts(image_data, image_size) };
     let data = Data::new_copy(image_slice);
     let image_new = Image::from_encoded(data, None);
-    let rep = unsafe { CStr::from_ptr(repetition) }
-        .to_str()
-        .unwrap_or("repeat");
+    new_pattern(image_new, repetition)
+}
 
-    Box::into_raw(Box::new(CanvasPattern {
-        image: image_new.unwrap(),
-        repetition: String::from(rep),
-        matrix: Matrix::default(),
-    })) as i64
+/// Creates a pattern from a `data:` URL or a bare base64 string. Returns 0 when the
+/// URL can't be parsed or doesn't hold an image.
+#[inline]
+pub(crate) fn create_pattern_data_url(data_url: *const c_char, repetition: *const c_char) -> c_longlong {
+    match parse_data_url_ptr(data_url).and_then(|url| url.image_data()) {
+        Ok(data) => create_pattern_encoded(data.as_ptr(), data.len(), repetition),
+        _ => 0,
+    }
+}
+
+/// Creates a pattern from the current contents of another canvas. Raster snapshots
+/// share pixels with the surface until it's drawn to again; GPU snapshots are read
+/// back so the pattern can be used on contexts that don't share the GPU context.
+#[inline]
+pub(crate) fn create_pattern_from_canvas(
+    canvas_native_ptr: c_longlong,
+    repetition: *const c_char,
+) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let snapshot = canvas_native.surface.image_snapshot();
+    let image = match canvas_native.surface_kind {
+        SurfaceKind::GPU => snapshot.new_raster_image(),
+        SurfaceKind::CPU => Some(snapshot),
+    };
+    Box::into_raw(canvas_native);
+    new_pattern(image, repetition)
+}
+
+/// Creates a pattern from a decoded image asset. The pattern shares the asset's Skia
+/// image, so making several patterns from one asset doesn't copy its pixels again.
+#[inline]
+pub(crate) fn create_pattern_from_asset(asset: c_longlong, repetition: *const c_char) -> c_longlong {
+    if asset == 0 {
+        return 0;
+    }
+    let mut asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let image = image_from_asset(&mut asset);
+    Box::into_raw(asset);
+    new_pattern(image, repetition)
+}
+
+#[inline]
+pub(crate) fn create_pattern_from_image_bitmap(bitmap: c_longlong, repetition: *const c_char) -> c_longlong {
+    if bitmap == 0 {
+        return 0;
+    }
+    let image_bitmap: &ImageBitmap = unsafe { &*(bitmap as *const ImageBitmap) };
+    new_pattern(Some(image_bitmap.image.clone()), repetition)
+}
+
+/// Creates a pattern from an ImageData buffer, which holds unpremultiplied RGBA.
+#[inline]
+pub(crate) fn create_pattern_from_image_data(
+    data: *const u8,
+    data_size: size_t,
+    width: c_int,
+    height: c_int,
+    repetition: *const c_char,
+) -> c_longlong {
+    if data.is_null() || width <= 0 || height <= 0 {
+        return 0;
+    }
+    let size = match (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(4))
+    {
+        Some(size) if size <= data_size => size,
+        _ => return 0,
+    };
+    let array = unsafe { std::slice::from_raw_parts(data, size) };
+    let info = ImageInfo::new(
+        ISize::new(width, height),
+        ColorType::RGBA8888,
+        AlphaType::Unpremul,
+        None,
+    );
+    let image = Image::from_raster_data(&info, Data::new_copy(array), width as usize * 4);
+    new_pattern(image, repetition)
 }
 
 
@@ -552,23 +995,23 @@ fn set_pattern(canvas_native_ptr: c_longlong, pattern: c_longlong, is_fill: bool
     if canvas_native_ptr == 0 {
         return 0;
     }
+    if pattern == 0 {
+        return canvas_native_ptr;
+    }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    let pat: Box<CanvasPattern> = unsafe { Box::from_raw(pattern as *mut _) };
-    let mode: (TileMode, TileMode) = match pat.repetition.as_str() {
-        "no-repeat" => (TileMode::Clamp, TileMode::Clamp),
-        "repeat-x" => (TileMode::Repeat, TileMode::Clamp),
-        "repeat-y" => (TileMode::Clamp, TileMode::Repeat),
-        _ => (TileMode::Repeat, TileMode::Repeat),
-    };
-    let shader = pat.image.to_shader(mode, &pat.matrix);
+    let shared: Box<SharedPattern> = unsafe { Box::from_raw(pattern as *mut _) };
+    let shared_pattern = (*shared).clone();
+    Box::into_raw(shared);
+    let shader = shared_pattern.lock().unwrap().to_shader();
     if is_fill {
         canvas_native.fill_paint.set_shader(Some(shader));
+        canvas_native.fill_style = PaintStyle::Pattern(shared_pattern);
     } else {
         canvas_native.stroke_paint.set_shader(Some(shader));
+        canvas_native.stroke_style = PaintStyle::Pattern(shared_pattern);
     }
 
-    Box::into_raw(pat);
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
@@ -577,12 +1020,36 @@ pub(crate) fn set_pattern_transform(pattern: c_longlong, matrix: c_longlong) ->
     if pattern == 0 || matrix == 0 {
         return pattern;
     }
-    let mut pat: Box<CanvasPattern> = unsafe { Box::from_raw(pattern as *mut _) };
+    let shared: Box<SharedPattern> = unsafe { Box::from_raw(pattern as *mut _) };
     let mat: Box<Matrix> = unsafe { Box::from_raw(matrix as *mut _) };
-    let affine = &mat.to_affine().unwrap();
-    pat.matrix.set_affine(affine);
+    if let Some(affine) = mat.to_affine() {
+        shared.lock().unwrap().matrix.set_affine(&affine);
+    }
     Box::into_raw(mat);
-    Box::into_raw(pat) as i64
+    Box::into_raw(shared) as i64
+}
+
+/// setTransform taking the six DOMMatrix2DInit values (a, b, c, d, e, f). The call is
+/// ignored when any value isn't finite.
+#[inline]
+pub(crate) fn set_pattern_transform_2d(
+    pattern: c_longlong,
+    a: c_float,
+    b: c_float,
+    c: c_float,
+    d: c_float,
+    e: c_float,
+    f: c_float,
+) -> c_longlong {
+    if pattern == 0 {
+        return pattern;
+    }
+    if ![a, b, c, d, e, f].iter().all(|value| value.is_finite()) {
+        return pattern;
+    }
+    let shared: Box<SharedPattern> = unsafe { Box::from_raw(pattern as *mut _) };
+    shared.lock().unwrap().matrix = Matrix::new_all(a, c, e, b, d, f, 0.0, 0.0, 1.0);
+    Box::into_raw(shared) as i64
 }
 
 #[inline]
@@ -852,38 +1319,18 @@ pub(crate) fn draw_rect(
 
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let tmp_paint = if is_stoke {
+        canvas_native.stroke_paint_for_draw()
+    } else {
+        canvas_native.fill_paint_for_draw()
+    };
     let surface = &mut canvas_native.surface;
     let canvas = surface.canvas();
     let rect = Rect::new(x, y, width + x, height + y);
-    let mut filter: Option<ImageFilter> = None;
-    if canvas_native.shadow_color > 0 && canvas_native.shadow_blur > 0.0
-    {
-        // sigma
-        // canvas_native.shadow_blur * 0.5
-        filter = drop_shadow(
-            Vector::new(canvas_native.shadow_offset_x, canvas_native.shadow_offset_y),
-            (canvas_native.shadow_blur, canvas_native.shadow_blur),
-            Color::new(canvas_native.shadow_color),
-            None,
-            None,
-        )
-    }
-
-    if is_stoke {
-        &canvas_native.stroke_paint.set_image_filter(filter);
-    } else {
-        &canvas_native.fill_paint.set_image_filter(filter);
-    }
 
     let valid_w = width > 0.0;
     let valid_h = height > 0.0;
     if valid_w && valid_h {
-        let tmp_paint;
-        if is_stoke {
-            tmp_paint = canvas_native.stroke_paint.clone();
-        } else {
-            tmp_paint = canvas_native.fill_paint.clone();
-        }
         &canvas.draw_rect(rect, &tmp_paint);
     } else if valid_w || valid_h {
         // we are expected to respect the lineJoin, so we can't just call
@@ -893,12 +1340,6 @@ pub(crate) fn draw_rect(
         path.line_to(Point::new(rect.right, rect.bottom));
         path.close();
 
-        let tmp_paint;
-        if is_stoke {
-            tmp_paint = canvas_native.stroke_paint.clone();
-        } else {
-            tmp_paint = canvas_native.fill_paint.clone();
-        }
         &canvas.draw_path(&path, &tmp_paint);
     }
     Box::into_raw(canvas_native) as *mut _ as i64
@@ -925,49 +1366,17 @@ pub(crate) fn draw_text(
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let tmp_paint = if is_stoke {
+        canvas_native.stroke_paint_for_draw()
+    } else {
+        canvas_native.fill_paint_for_draw()
+    };
     let surface = &mut canvas_native.surface;
     let canvas = surface.canvas();
     let font = &mut canvas_native.font;
     let text_to_draw = unsafe { CStr::from_ptr(text as *mut _).to_str().unwrap_or("") };
 
     if !text_to_draw.is_empty() {
-        let mut blur: Option<Paint> = None;
-        if canvas_native.shadow_color > 0 && canvas_native.shadow_blur > 0.0
-        {
-            let mut paint = Paint::default();
-            paint.set_color(Color::from(canvas_native.shadow_color));
-            paint.set_anti_alias(false);
-            if canvas_native.shadow_blur > 0.0 {
-                let filter = MaskFilter::blur(BlurStyle::Normal, canvas_native.shadow_blur, None);
-                paint.set_mask_filter(filter);
-            }
-
-            if is_stoke {
-                paint.set_style(Style::Stroke);
-            } else {
-                paint.set_style(Style::Fill);
-            }
-
-            let line_join = match canvas_native.line_join.as_ref() {
-                "round" => Join::Round,
-                "bevel" => Join::Bevel,
-                _ => Join::Miter
-            };
-
-            let line_cap = match canvas_native.line_cap.as_ref() {
-                "round" => Cap::Round,
-                "square" => Cap::Square,
-                _ => Cap::Butt
-            };
-
-
-            paint.set_stroke_miter(canvas_native.miter_limit);
-            paint.set_stroke_cap(line_cap);
-            paint.set_stroke_join(line_join);
-
-            blur = Some(paint);
-        }
-
         let mut position = Point::new(x, y);
         let mut align = Align::Left;
         match canvas_native.text_align.as_ref() {
@@ -1017,49 +1426,13 @@ pub(crate) fn draw_text(
             }
         }
 
-        if is_stoke {
-            let tmp_paint = canvas_native.stroke_paint.clone();
-            &canvas.draw_str_align(
-                text_to_draw,
-                (position.x, position.y),
-                font,
-                &tmp_paint,
-                align,
-            );
-            if blur.is_some() {
-                let mut shadow = blur.unwrap();
-                shadow.set_style(Style::Stroke);
-                blur = Some(shadow);
-            }
-        } else {
-            let tmp_paint = canvas_native.fill_paint.clone();
-            if blur.is_some() {
-                let mut fill_shadow = blur.unwrap();
-                fill_shadow.set_style(Style::Fill);
-                blur = Some(fill_shadow);
-            }
-            &canvas.draw_str_align(
-                text_to_draw,
-                (position.x, position.y),
-                font,
-                &tmp_paint,
-                align,
-            );
-        }
-
-        if blur.is_some() {
-            let shadow = blur.unwrap();
-            canvas.draw_str_align(
-                text_to_draw,
-                (
-                    x + canvas_native.shadow_offset_x,
-                    y + canvas_native.shadow_offset_y,
-                ),
-                &canvas_native.font,
-                &shadow,
-                align,
-            );
-        }
+        &canvas.draw_str_align(
+            text_to_draw,
+            (position.x, position.y),
+            font,
+            &tmp_paint,
+            align,
+        );
     }
     Box::into_raw(canvas_native) as *mut _ as i64
 }
@@ -1279,26 +1652,12 @@ pub(crate) fn stroke_path(canvas_native_ptr: c_longlong, path: c_longlong) -> c_
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let paint = canvas_native.stroke_paint_for_draw();
     let surface = &mut canvas_native.surface;
     let canvas = surface.canvas();
 
-    let mut filter: Option<ImageFilter> = None;
-    if canvas_native.shadow_color > 0
-        && (canvas_native.shadow_blur > 0.0
-        || canvas_native.shadow_offset_x > 0.0
-        || canvas_native.shadow_offset_y > 0.0)
-    {
-        filter = drop_shadow(
-            Vector::new(canvas_native.shadow_offset_x, canvas_native.shadow_offset_y),
-            (canvas_native.shadow_blur, canvas_native.shadow_blur),
-            Color::new(canvas_native.shadow_color),
-            None,
-            None,
-        );
-        &canvas_native.stroke_paint.set_image_filter(filter);
-    }
     let path: Box<Path> = unsafe { Box::from_raw(path as *mut _) };
-    canvas.draw_path(&path, &canvas_native.stroke_paint);
+    canvas.draw_path(&path, &paint);
     Box::into_raw(path);
     Box::into_raw(canvas_native) as *mut _ as i64
 }
@@ -1321,22 +1680,11 @@ pub(crate) fn stroke(canvas_native_ptr: c_longlong) -> c_longlong {
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let paint = canvas_native.stroke_paint_for_draw();
     let surface = &mut canvas_native.surface;
     let canvas = surface.canvas();
 
-    let mut filter: Option<ImageFilter> = None;
-    if canvas_native.shadow_color > 0 && canvas_native.shadow_blur > 0.0
-    {
-        filter = drop_shadow(
-            Vector::new(canvas_native.shadow_offset_x, canvas_native.shadow_offset_y),
-            (canvas_native.shadow_blur, canvas_native.shadow_blur),
-            Color::new(canvas_native.shadow_color),
-            None,
-            None,
-        );
-        &canvas_native.stroke_paint.set_image_filter(filter);
-    }
-    canvas.draw_path(&canvas_native.path, &canvas_native.stroke_paint);
+    canvas.draw_path(&canvas_native.path, &paint);
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
@@ -1351,7 +1699,7 @@ pub(crate) fn fill_path_rule(
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-
+    let paint = canvas_native.fill_paint_for_draw();
     let surface = &mut canvas_native.surface;
     let canvas = surface.canvas();
     let fill_type: FillType;
@@ -1367,19 +1715,7 @@ pub(crate) fn fill_path_rule(
 
     let mut path: Box<Path> = unsafe { Box::from_raw(path as *mut _) };
     path.set_fill_type(fill_type);
-    let mut filter: Option<ImageFilter> = None;
-    if canvas_native.shadow_color > 0 && canvas_native.shadow_blur > 0.0
-    {
-        filter = drop_shadow(
-            Vector::new(canvas_native.shadow_offset_x, canvas_native.shadow_offset_y),
-            (canvas_native.shadow_blur, canvas_native.shadow_blur),
-            Color::new(canvas_native.shadow_color),
-            None,
-            None,
-        );
-        &canvas_native.fill_paint.set_image_filter(filter);
-    }
-    canvas.draw_path(&path, &canvas_native.fill_paint);
+    canvas.draw_path(&path, &paint);
     Box::into_raw(path);
     Box::into_raw(canvas_native) as *mut _ as i64
 }
@@ -1391,23 +1727,10 @@ pub(crate) fn fill(canvas_native_ptr: c_longlong) -> c_longlong {
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-
+    let paint = canvas_native.fill_paint_for_draw();
     let surface = &mut canvas_native.surface;
     let canvas = surface.canvas();
-    let mut filter: Option<ImageFilter> = None;
-    if canvas_native.shadow_color > 0 && canvas_native.shadow_blur > 0.0
-    {
-        filter = drop_shadow(
-            Vector::new(canvas_native.shadow_offset_x, canvas_native.shadow_offset_y),
-            (canvas_native.shadow_blur, canvas_native.shadow_blur),
-            Color::new(canvas_native.shadow_color),
-            None,
-            None,
-        );
-        &canvas_native.fill_paint.set_image_filter(filter);
-    }
-
-    canvas.draw_path(&canvas_native.path, &canvas_native.fill_paint);
+    canvas.draw_path(&canvas_native.path, &paint);
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
@@ -1418,7 +1741,7 @@ pub(crate) fn fill_rule(canvas_native_ptr: c_longlong, fill_rule: *const c_char)
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-
+    let paint = canvas_native.fill_paint_for_draw();
     let surface = &mut canvas_native.surface;
     let canvas = surface.canvas();
     let mut fill_type: FillType;
@@ -1432,19 +1755,7 @@ pub(crate) fn fill_rule(canvas_native_ptr: c_longlong, fill_rule: *const c_char)
         _ => fill_type = FillType::Winding,
     };
     canvas_native.path.set_fill_type(fill_type);
-    let mut filter: Option<ImageFilter> = None;
-    if canvas_native.shadow_color > 0 && canvas_native.shadow_blur > 0.0
-    {
-        filter = drop_shadow(
-            Vector::new(canvas_native.shadow_offset_x, canvas_native.shadow_offset_y),
-            (canvas_native.shadow_blur, canvas_native.shadow_blur),
-            Color::new(canvas_native.shadow_color),
-            None,
-            None,
-        );
-        &canvas_native.fill_paint.set_image_filter(filter);
-    }
-    canvas.draw_path(&canvas_native.path, &canvas_native.fill_paint);
+    canvas.draw_path(&canvas_native.path, &paint);
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
@@ -1619,7 +1930,7 @@ pub(crate) fn set_fill_color_rgba(
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    canvas_native.fill_paint.set_argb(alpha, red, green, blue);
+    set_style_color(&mut canvas_native, Color::from_argb(alpha, red, green, blue), true);
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
@@ -1630,8 +1941,7 @@ pub(crate) fn set_fill_color(canvas_native_ptr: c_longlong, color: u32) -> c_lon
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    canvas_native.fill_paint.set_color(color);
-    //canvas_native.fill_paint.set_color(Color::from(color));
+    set_style_color(&mut canvas_native, Color::from(color), true);
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
@@ -1682,6 +1992,13 @@ pub(crate) fn set_gradient_radial(
         None,
     );
     paint.set_shader(gradient_shader);
+    // Recorded as a gradient object so the style getters can hand it out like any other.
+    let gradient = CanvasGradient::from_stops(
+        CanvasGradientKind::Radial { x0, y0, r0: radius_0, x1, y1, r1: radius_1 },
+        &new_color_vec,
+        new_positions_array,
+    );
+    set_legacy_gradient_style(&mut canvas_native, gradient, is_stroke);
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
@@ -1728,6 +2045,12 @@ pub(crate) fn set_gradient_linear(
         None,
     );
     paint.set_shader(gradient_shader);
+    let gradient = CanvasGradient::from_stops(
+        CanvasGradientKind::Linear { x0, y0, x1, y1 },
+        &new_color_vec,
+        new_positions_array,
+    );
+    set_legacy_gradient_style(&mut canvas_native, gradient, is_stroke);
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
@@ -1744,59 +2067,419 @@ pub(crate) fn set_stroke_color_rgba(
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    &canvas_native.stroke_paint.set_argb(alpha, red, green, blue);
+    set_style_color(&mut canvas_native, Color::from_argb(alpha, red, green, blue), false);
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
+
+#[inline]
+pub(crate) fn set_stroke_color(canvas_native_ptr: c_longlong, color: u32) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    set_style_color(&mut canvas_native, Color::from(color), false);
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
+
+fn set_legacy_gradient_style(canvas_native: &mut CanvasNative, gradient: CanvasGradient, is_stroke: bool) {
+    let style = PaintStyle::Gradient(Arc::new(Mutex::new(gradient)));
+    if is_stroke {
+        canvas_native.stroke_style = style;
+    } else {
+        canvas_native.fill_style = style;
+    }
+}
+
+fn set_style_color(canvas_native: &mut CanvasNative, color: Color, is_fill: bool) {
+    if is_fill {
+        canvas_native.fill_paint.set_shader(None);
+        canvas_native.fill_paint.set_color(color);
+        canvas_native.fill_style = PaintStyle::Color(color);
+    } else {
+        canvas_native.stroke_paint.set_shader(None);
+        canvas_native.stroke_paint.set_color(color);
+        canvas_native.stroke_style = PaintStyle::Color(color);
+    }
+}
+
+#[inline]
+pub(crate) fn get_fill_style(canvas_native_ptr: c_longlong) -> PaintStyle {
+    if canvas_native_ptr == 0 {
+        return PaintStyle::default();
+    }
+    let canvas_native: Box<CanvasNative> = unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let style = canvas_native.fill_style.clone();
+    Box::into_raw(canvas_native);
+    style
+}
+
+#[inline]
+pub(crate) fn get_stroke_style(canvas_native_ptr: c_longlong) -> PaintStyle {
+    if canvas_native_ptr == 0 {
+        return PaintStyle::default();
+    }
+    let canvas_native: Box<CanvasNative> = unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let style = canvas_native.stroke_style.clone();
+    Box::into_raw(canvas_native);
+    style
+}
+
+#[inline]
+pub(crate) fn get_shadow_color(canvas_native_ptr: c_longlong) -> String {
+    if canvas_native_ptr == 0 {
+        return serialize_color(Color::TRANSPARENT);
+    }
+    let canvas_native: Box<CanvasNative> = unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let color = Color::from(canvas_native.shadow_color);
+    Box::into_raw(canvas_native);
+    serialize_color(color)
+}
+
+// Setters taking a CSS color string. Values that fail to parse are ignored, the same
+// way assigning an invalid color to fillStyle or strokeStyle leaves it unchanged.
+#[inline]
+pub(crate) fn set_fill_color_string(canvas_native_ptr: c_longlong, color: *const c_char) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    if let Ok(color) = parse_color_ptr(color) {
+        set_style_color(&mut canvas_native, color, true);
+    }
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
+
+#[inline]
+pub(crate) fn set_stroke_color_string(canvas_native_ptr: c_longlong, color: *const c_char) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    if let Ok(color) = parse_color_ptr(color) {
+        set_style_color(&mut canvas_native, color, false);
+    }
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
+
+#[inline]
+pub(crate) fn set_shadow_color_string(canvas_native_ptr: c_longlong, color: *const c_char) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    if let Ok(color) = parse_color_ptr(color) {
+        canvas_native.shadow_color = color.into();
+    }
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
+
+/// Sets the CSS filter applied to every draw. Values that fail to parse are ignored.
+#[inline]
+pub(crate) fn set_filter(canvas_native_ptr: c_longlong, filter: *const c_char) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    if filter.is_null() {
+        return canvas_native_ptr;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let value = unsafe { CStr::from_ptr(filter as *mut _).to_str().unwrap_or("") };
+    if let Ok(image_filter) = parse_filter(value) {
+        canvas_native.filter = value.trim().to_string();
+        canvas_native.image_filter = image_filter;
+    }
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
+
+#[inline]
+pub(crate) fn get_filter(canvas_native_ptr: c_longlong) -> String {
+    if canvas_native_ptr == 0 {
+        return "none".to_string();
+    }
+    let canvas_native: Box<CanvasNative> = unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let filter = canvas_native.filter.clone();
+    Box::into_raw(canvas_native);
+    filter
+}
+
+impl CanvasNative {
+    /// The sampling used for images and patterns, from imageSmoothingEnabled and
+    /// imageSmoothingQuality.
+    pub(crate) fn filter_quality(&self) -> FilterQuality {
+        if !self.image_smoothing_enabled {
+            return FilterQuality::None;
+        }
+        match self.image_smoothing_quality.as_str() {
+            "high" => FilterQuality::High,
+            "medium" => FilterQuality::Medium,
+            _ => FilterQuality::Low,
+        }
+    }
+
+    // The spec only draws a shadow when its color isn't fully transparent and there is
+    // a blur or an offset to show it.
+    fn has_shadow(&self) -> bool {
+        Color::from(self.shadow_color).a() != 0
+            && (self.shadow_blur > 0.0 || self.shadow_offset_x != 0.0 || self.shadow_offset_y != 0.0)
+    }
+
+    /// A copy of `paint` for a single draw with globalAlpha, the composite operation,
+    /// image smoothing, the CSS filter and the shadow applied.
+    /// The filter runs first so the shadow is cast by the filtered drawing, and the
+    /// shared fill and stroke paints are never modified.
+    pub(crate) fn paint_for_draw(&mut self, paint: &Paint) -> Paint {
+        let mut paint = paint.clone();
+        paint.set_blend_mode(self.global_composite_operation.get_blend_mode());
+        // Also controls how pattern images are sampled.
+        paint.set_filter_quality(self.filter_quality());
+        // Gradients and patterns carry their own alpha, so only a plain color's alpha is
+        // multiplied with globalAlpha.
+        let alpha = if paint.shader().is_some() { 1.0 } else { paint.alpha_f() };
+        paint.set_alpha_f(alpha * self.global_alpha);
+        // Shadow and filter lengths are in device pixels and ignore the transform, but
+        // Skia maps image filter parameters through it, so undo it first.
+        let matrix = self.surface.canvas().total_matrix();
+        let inverse = matrix.invert().unwrap_or_default();
+        // `filter` only ever holds a value that parsed, so parsing it again succeeds.
+        let mut image_filter = if self.image_filter.is_some() && !matrix.is_translate() {
+            parse_filter_mapped(&self.filter, &inverse).unwrap_or(None)
+        } else {
+            self.image_filter.clone()
+        };
+        if self.has_shadow() {
+            let offset = inverse.map_vector(Vector::new(self.shadow_offset_x, self.shadow_offset_y));
+            let sigma = self.shadow_blur * 0.5;
+            let sigma_x = inverse.map_vector(Vector::new(sigma, 0.0)).length();
+            let sigma_y = inverse.map_vector(Vector::new(0.0, sigma)).length();
+            image_filter = drop_shadow(
+                offset,
+                (sigma_x, sigma_y),
+                Color::from(self.shadow_color),
+                image_filter,
+                None,
+            );
+        }
+        if image_filter.is_some() {
+            paint.set_image_filter(image_filter);
+        }
+        paint
+    }
+
+    pub(crate) fn fill_paint_for_draw(&mut self) -> Paint {
+        let mut paint = self.fill_paint.clone();
+        if let Some(shader) = self.fill_style.current_shader() {
+            paint.set_shader(Some(shader));
+        }
+        self.paint_for_draw(&paint)
+    }
+
+    pub(crate) fn stroke_paint_for_draw(&mut self) -> Paint {
+        let mut paint = self.stroke_paint.clone();
+        if let Some(shader) = self.stroke_style.current_shader() {
+            paint.set_shader(Some(shader));
+        }
+        self.paint_for_draw(&paint)
+    }
+
+    pub(crate) fn image_paint_for_draw(&mut self) -> Paint {
+        let mut paint = Paint::default();
+        paint.set_anti_alias(false);
+        self.paint_for_draw(&paint)
+    }
+}
+
+#[inline]
+pub(crate) fn clear_rect(
+    canvas_native_ptr: c_longlong,
+    x: c_float,
+    y: c_float,
+    width: c_float,
+    height: c_float,
+) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let rect = Rect::new(x, y, width + x, height + y);
+    let mut paint = Paint::default();
+    paint.set_anti_alias(false);
+    paint.set_style(Style::Fill);
+    paint.set_blend_mode(BlendMode::Clear);
+    paint.set_color(0);
+    let surface = &mut canvas_native.surface;
+    let canvas = surface.canvas();
+    canvas.draw_rect(rect, &paint);
+    clear_hit_regions_in_rect(&mut canvas_native, &rect);
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
+
+#[inline]
+pub(crate) fn clear_canvas(canvas_native_ptr: c_longlong) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let surface = &mut canvas_native.surface;
+    let canvas = surface.canvas();
+    canvas.clear(Color::from_argb(255, 255, 255, 255));
+    //canvas.flush();
+    //surface.flush();
+    canvas_native.hit_regions.clear();
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
+
+/// Skia path effects beyond dashing. They apply to fills and strokes alike and are
+/// saved and restored with the rest of the state.
+#[derive(Clone, Default)]
+pub struct CanvasPathEffects {
+    corner_radius: f32,
+    discrete: Option<(f32, f32, u32)>,
+    stamp: Option<(Path, f32, f32, Path1DStyle)>,
+}
+
+impl CanvasPathEffects {
+    fn shape_effect(&self) -> Option<PathEffect> {
+        let mut effect: Option<PathEffect> = None;
+        if self.corner_radius > 0.0 {
+            effect = CornerPathEffect::new(self.corner_radius);
+        }
+        if let Some((segment_length, deviation, seed)) = self.discrete {
+            if let Some(discrete) = DiscretePathEffect::new(segment_length, deviation, Some(seed)) {
+                effect = Some(compose_path_effects(discrete, effect));
+            }
+        }
+        if let Some((path, advance, phase, style)) = &self.stamp {
+            if let Some(stamp) = Path1DPathEffect::new(path, *advance, *phase, *style) {
+                effect = Some(compose_path_effects(stamp, effect));
+            }
+        }
+        effect
+    }
+}
+
+// Applies `inner` first, then `outer`.
+fn compose_path_effects(outer: PathEffect, inner: Option<PathEffect>) -> PathEffect {
+    match inner {
+        Some(inner) => PathEffect::compose(outer, inner),
+        _ => outer,
+    }
+}
+
+// Rebuilds the paint path effects from the stored state so the dash, its offset and
+// the other effects can each change independently. Dashes only apply to strokes and
+// run after the shape effects.
+fn update_path_effects(canvas_native: &mut CanvasNative) {
+    let shape = canvas_native.path_effects.shape_effect();
+    let total: f32 = canvas_native.line_dash.iter().sum();
+    let dash = if total > 0.0 {
+        PathEffect::dash(&canvas_native.line_dash, canvas_native.line_dash_offset)
+    } else {
+        None
+    };
+    let stroke = match dash {
+        Some(dash) => Some(compose_path_effects(dash, shape.clone())),
+        _ => shape.clone(),
+    };
+    canvas_native.stroke_paint.set_path_effect(stroke);
+    canvas_native.fill_paint.set_path_effect(shape);
+}
+
+/// Rounds the corners of drawn paths with the given radius; 0 removes the effect.
+#[inline]
+pub(crate) fn set_corner_path_effect(canvas_native_ptr: c_longlong, radius: c_float) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    if radius.is_finite() {
+        canvas_native.path_effects.corner_radius = radius.max(0.0);
+        update_path_effects(&mut canvas_native);
+    }
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
+/// Breaks paths into `segment_length` pieces and randomly displaces them by up to
+/// `deviation`. A segment length of 0 removes the effect.
 #[inline]
-pub(crate) fn set_stroke_color(canvas_native_ptr: c_longlong, color: u32) -> c_longlong {
+pub(crate) fn set_discrete_path_effect(
+    canvas_native_ptr: c_longlong,
+    segment_length: c_float,
+    deviation: c_float,
+    seed: c_uint,
+) -> c_longlong {
     if canvas_native_ptr == 0 {
         return 0;
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    //&canvas_native.stroke_paint.set_color(Color::from(color));
-    canvas_native.stroke_paint.set_color(color);
+    if segment_length.is_finite() && deviation.is_finite() {
+        canvas_native.path_effects.discrete = if segment_length > 0.0 {
+            Some((segment_length, deviation, seed))
+        } else {
+            None
+        };
+        update_path_effects(&mut canvas_native);
+    }
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
+/// Stamps a copy of `path` every `advance` units along the drawn path. `style` is one
+/// of "translate", "rotate" or "morph". A null path or non-positive advance removes
+/// the effect.
 #[inline]
-pub(crate) fn clear_rect(
+pub(crate) fn set_stamp_path_effect(
     canvas_native_ptr: c_longlong,
-    x: c_float,
-    y: c_float,
-    width: c_float,
-    height: c_float,
+    path: c_longlong,
+    advance: c_float,
+    phase: c_float,
+    style: *const c_char,
 ) -> c_longlong {
     if canvas_native_ptr == 0 {
         return 0;
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    let rect = Rect::new(x, y, width + x, height + y);
-    let mut paint = Paint::default();
-    paint.set_anti_alias(false);
-    paint.set_style(Style::Fill);
-    paint.set_blend_mode(BlendMode::Clear);
-    paint.set_color(0);
-    let surface = &mut canvas_native.surface;
-    let canvas = surface.canvas();
-    canvas.draw_rect(rect, &paint);
+    if advance.is_finite() && phase.is_finite() {
+        canvas_native.path_effects.stamp = if path == 0 || advance <= 0.0 {
+            None
+        } else {
+            let style = if style.is_null() {
+                "translate"
+            } else {
+                unsafe { CStr::from_ptr(style) }.to_str().unwrap_or("translate")
+            };
+            let style = match style {
+                "rotate" => Path1DStyle::Rotate,
+                "morph" => Path1DStyle::Morph,
+                _ => Path1DStyle::Translate,
+            };
+            let stamp: Box<Path> = unsafe { Box::from_raw(path as *mut _) };
+            let copy = stamp.as_ref().clone();
+            Box::into_raw(stamp);
+            Some((copy, advance, phase, style))
+        };
+        update_path_effects(&mut canvas_native);
+    }
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
 #[inline]
-pub(crate) fn clear_canvas(canvas_native_ptr: c_longlong) -> c_longlong {
+pub(crate) fn clear_path_effects(canvas_native_ptr: c_longlong) -> c_longlong {
     if canvas_native_ptr == 0 {
         return 0;
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    let surface = &mut canvas_native.surface;
-    let canvas = surface.canvas();
-    canvas.clear(Color::from_argb(255, 255, 255, 255));
-    //canvas.flush();
-    //surface.flush();
+    canvas_native.path_effects = CanvasPathEffects::default();
+    update_path_effects(&mut canvas_native);
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
@@ -1811,20 +2494,35 @@ pub(crate) fn set_line_dash(
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    let stroke_paint = &mut canvas_native.stroke_paint;
-    if size == 0 {
-        stroke_paint.set_path_effect(None);
+    let segments: &[f32] = if size == 0 || array.is_null() {
+        &[]
     } else {
-        let new_array = unsafe { std::slice::from_raw_parts(array, size) };
-        let dash_path = PathEffect::dash(new_array, canvas_native.line_dash_offset);
-        if dash_path.is_some() {
-            let path = dash_path.unwrap();
-            stroke_paint.set_path_effect(Some(path));
+        unsafe { std::slice::from_raw_parts(array, size) }
+    };
+    // Per spec the whole call is ignored if any value is negative, infinite or NaN,
+    // and an odd number of values is repeated to make it even.
+    if segments.iter().all(|value| value.is_finite() && *value >= 0.0) {
+        let mut line_dash = segments.to_vec();
+        if line_dash.len() % 2 == 1 {
+            line_dash.extend_from_slice(segments);
         }
+        canvas_native.line_dash = line_dash;
+        update_path_effects(&mut canvas_native);
     }
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
+#[inline]
+pub(crate) fn get_line_dash(canvas_native_ptr: c_longlong) -> Vec<f32> {
+    if canvas_native_ptr == 0 {
+        return Vec::new();
+    }
+    let canvas_native: Box<CanvasNative> = unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let line_dash = canvas_native.line_dash.clone();
+    Box::into_raw(canvas_native);
+    line_dash
+}
+
 #[inline]
 pub(crate) fn set_global_composite_operation(
     canvas_native_ptr: c_longlong,
@@ -1836,15 +2534,24 @@ pub(crate) fn set_global_composite_operation(
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
     let operation = unsafe { CStr::from_ptr(composite as *mut _) };
-    let new_operation = operation.to_str().unwrap_or("source-over");
-    let global_composite_operation = CanvasCompositeOperationType::value_from_str(new_operation);
-    let mut fill_paint = &mut canvas_native.fill_paint;
-    let stroke_paint = &mut canvas_native.stroke_paint;
-    fill_paint.set_blend_mode(global_composite_operation.get_blend_mode());
-    stroke_paint.set_blend_mode(global_composite_operation.get_blend_mode());
+    let new_operation = operation.to_str().unwrap_or("");
+    if let Some(global_composite_operation) = CanvasCompositeOperationType::value_from_str(new_operation) {
+        canvas_native.global_composite_operation = global_composite_operation;
+    }
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
+#[inline]
+pub(crate) fn get_global_composite_operation(canvas_native_ptr: c_longlong) -> String {
+    if canvas_native_ptr == 0 {
+        return CanvasCompositeOperationType::SourceOver.as_str().to_string();
+    }
+    let canvas_native: Box<CanvasNative> = unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let operation = canvas_native.global_composite_operation.as_str().to_string();
+    Box::into_raw(canvas_native);
+    operation
+}
+
 #[inline]
 pub(crate) fn set_font(canvas_native_ptr: c_longlong, font: *const c_char) -> c_longlong {
     if canvas_native_ptr == 0 {
@@ -2139,6 +2846,7 @@ pub(crate) fn draw_image(
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let paint = canvas_native.image_paint_for_draw();
     let surface = &mut canvas_native.surface;
     let image_slice: &[u8] = unsafe { std::slice::from_raw_parts(image_array, image_size) };
     let data = Data::new_copy(image_slice);
@@ -2150,26 +2858,6 @@ pub(crate) fn draw_image(
     );
     let image_new = Image::from_raster_data(&info, data, (original_width * 4) as usize);
     let canvas = surface.canvas();
-    let mut paint = Paint::default();
-    paint.set_anti_alias(false);
-    paint.set_blend_mode(canvas_native.fill_paint.blend_mode());
-    if canvas_native.image_smoothing_enabled {
-        match canvas_native.image_smoothing_quality.as_str() {
-            "low" => {
-                paint.set_filter_quality(FilterQuality::Low);
-            }
-            "medium" => {
-                paint.set_filter_quality(FilterQuality::Medium);
-            }
-            "high" => {
-                paint.set_filter_quality(FilterQuality::High);
-            }
-            _ => {}
-        }
-    } else {
-        paint.set_filter_quality(FilterQuality::None);
-    }
-
     if image_new.is_some() {
         canvas.draw_image(&image_new.unwrap(), Point::new(dx, dy), Some(&paint));
     }
@@ -2193,6 +2881,7 @@ pub(crate) fn draw_image_dw(
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let paint = canvas_native.image_paint_for_draw();
     let surface = &mut canvas_native.surface;
     let canvas = surface.canvas();
     let image_slice: &[u8] =
@@ -2206,26 +2895,6 @@ pub(crate) fn draw_image_dw(
     );
     let image_new = Image::from_raster_data(&info, data, (original_width * 4) as usize);
     if image_new.is_some() {
-        let mut paint = Paint::default();
-        paint.set_anti_alias(false);
-        paint.set_blend_mode(canvas_native.fill_paint.blend_mode());
-        if canvas_native.image_smoothing_enabled {
-            match canvas_native.image_smoothing_quality.as_str() {
-                "low" => {
-                    paint.set_filter_quality(FilterQuality::Low);
-                }
-                "medium" => {
-                    paint.set_filter_quality(FilterQuality::Medium);
-                }
-                "high" => {
-                    paint.set_filter_quality(FilterQuality::High);
-                }
-                _ => {}
-            }
-        } else {
-            paint.set_filter_quality(FilterQuality::None);
-        }
-
         canvas.draw_image_rect(
             &image_new.unwrap(),
             None,
@@ -2257,6 +2926,7 @@ pub(crate) fn draw_image_sw(
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let paint = canvas_native.image_paint_for_draw();
     let surface = &mut canvas_native.surface;
     let canvas = surface.canvas();
     let image_slice: &[u8] =
@@ -2273,26 +2943,6 @@ pub(crate) fn draw_image_sw(
 
     if image_new.is_some() {
         let src_rect = Rect::new(sx, sy, s_width + sx, s_height + sy);
-        let mut paint = Paint::default();
-        paint.set_anti_alias(false);
-        paint.set_blend_mode(canvas_native.fill_paint.blend_mode());
-        if canvas_native.image_smoothing_enabled {
-            match canvas_native.image_smoothing_quality.as_str() {
-                "low" => {
-                    paint.set_filter_quality(FilterQuality::Low);
-                }
-                "medium" => {
-                    paint.set_filter_quality(FilterQuality::Medium);
-                }
-                "high" => {
-                    paint.set_filter_quality(FilterQuality::High);
-                }
-                _ => {}
-            }
-        } else {
-            paint.set_filter_quality(FilterQuality::None);
-        }
-
         canvas.draw_image_rect(
             &image_new.unwrap(),
             Some((&src_rect, SrcRectConstraint::Strict)),
@@ -2303,7 +2953,10 @@ pub(crate) fn draw_image_sw(
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
-
+/// Draws encoded image bytes, decoding them through the canvas image cache. The whole
+/// buffer is hashed on every call and a copy of it is kept with the decoded image,
+/// counting toward the cache budget. Large images drawn repeatedly are cheaper through
+/// `draw_image_keyed`, and a budget of 0 turns the caching off.
 #[inline]
 pub(crate) fn draw_image_encoded(
     canvas_native_ptr: c_longlong,
@@ -2319,20 +2972,18 @@ pub(crate) fn draw_image_encoded(
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    let surface = &mut canvas_native.surface;
     let image_slice: &[u8] = unsafe { std::slice::from_raw_parts(image_array, image_size) };
-    let data = Data::new_copy(image_slice);
-    let image_new = Image::from_encoded(data, None);
+    let image_new = cached_encoded_image(&mut canvas_native, image_slice);
+    let paint = canvas_native.image_paint_for_draw();
+    let surface = &mut canvas_native.surface;
     let canvas = surface.canvas();
     if image_new.is_some() {
-        let mut paint = Paint::default();
-        paint.set_anti_alias(false);
-        paint.set_blend_mode(canvas_native.fill_paint.blend_mode());
         canvas.draw_image(&image_new.unwrap(), Point::new(dx, dy), Some(&paint));
     }
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
+/// Like `draw_image_encoded`, scaled into the destination size.
 #[inline]
 pub(crate) fn draw_image_dw_encoded(
     canvas_native_ptr: c_longlong,
@@ -2350,29 +3001,25 @@ pub(crate) fn draw_image_dw_encoded(
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    let surface = &mut canvas_native.surface;
-    let canvas = surface.canvas();
     let image_slice: &[u8] =
         unsafe { std::slice::from_raw_parts(image_array as *mut _, image_size) };
-
-    let data = Data::new_copy(image_slice);
-    let image_new = Image::from_encoded(data, None);
+    let image_new = cached_encoded_image(&mut canvas_native, image_slice);
+    let paint = canvas_native.image_paint_for_draw();
+    let surface = &mut canvas_native.surface;
+    let canvas = surface.canvas();
 
     if image_new.is_some() {
-        let mut paint = Paint::default();
-        paint.set_anti_alias(false);
-        paint.set_blend_mode(canvas_native.fill_paint.blend_mode());
-        let tmp_paint = canvas_native.fill_paint.clone();
         canvas.draw_image_rect(
             &image_new.unwrap(),
             None,
             Rect::new(dx, dy, d_width + dx, d_height + dy),
-            &tmp_paint,
+            &paint,
         );
     }
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
+/// Like `draw_image_encoded`, drawing the source rect into the destination rect.
 #[inline]
 pub(crate) fn draw_image_sw_encoded(
     canvas_native_ptr: c_longlong,
@@ -2394,30 +3041,102 @@ pub(crate) fn draw_image_sw_encoded(
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    let surface = &mut canvas_native.surface;
-    let canvas = surface.canvas();
     let image_slice: &[u8] =
         unsafe { std::slice::from_raw_parts(image_array as *mut _, image_size) };
-
-    let data = Data::new_copy(image_slice);
-    let image_new = Image::from_encoded(data, None);
+    let image_new = cached_encoded_image(&mut canvas_native, image_slice);
+    let paint = canvas_native.image_paint_for_draw();
+    let surface = &mut canvas_native.surface;
+    let canvas = surface.canvas();
 
     if image_new.is_some() {
         let src_rect = Rect::new(sx, sy, s_width + sx, s_height + sy);
-        let mut paint = Paint::default();
-        paint.set_anti_alias(false);
-        paint.set_blend_mode(canvas_native.fill_paint.blend_mode());
-        let tmp_paint = canvas_native.fill_paint.clone();
         canvas.draw_image_rect(
             &image_new.unwrap(),
             Some((&src_rect, SrcRectConstraint::Strict)),
             Rect::new(dx, dy, d_width + dx, d_height + dy),
-            &tmp_paint,
+            &paint,
         );
     }
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
+// The data URL variants draw like the encoded ones, through the same image cache but
+// keyed on the URL, so the base64 is only decoded on a miss. URLs that don't parse
+// draw nothing.
+fn draw_data_url_image(
+    canvas_native_ptr: c_longlong,
+    data_url: *const c_char,
+    draw: impl FnOnce(&mut Canvas, &Image, &Paint),
+) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    if data_url.is_null() {
+        return canvas_native_ptr;
+    }
+    let url = match unsafe { CStr::from_ptr(data_url) }.to_str() {
+        Ok(url) => url,
+        _ => return canvas_native_ptr,
+    };
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    if let Some(image) = cached_data_url_image(&mut canvas_native, url) {
+        let paint = canvas_native.image_paint_for_draw();
+        draw(canvas_native.surface.canvas(), &image, &paint);
+    }
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
+
+#[inline]
+pub(crate) fn draw_image_data_url(
+    canvas_native_ptr: c_longlong,
+    data_url: *const c_char,
+    dx: c_float,
+    dy: c_float,
+) -> c_longlong {
+    draw_data_url_image(canvas_native_ptr, data_url, |canvas, image, paint| {
+        canvas.draw_image(image, Point::new(dx, dy), Some(paint));
+    })
+}
+
+#[inline]
+pub(crate) fn draw_image_dw_data_url(
+    canvas_native_ptr: c_longlong,
+    data_url: *const c_char,
+    dx: c_float,
+    dy: c_float,
+    d_width: c_float,
+    d_height: c_float,
+) -> c_longlong {
+    draw_data_url_image(canvas_native_ptr, data_url, |canvas, image, paint| {
+        canvas.draw_image_rect(image, None, Rect::new(dx, dy, d_width + dx, d_height + dy), paint);
+    })
+}
+
+#[inline]
+pub(crate) fn draw_image_sw_data_url(
+    canvas_native_ptr: c_longlong,
+    data_url: *const c_char,
+    sx: c_float,
+    sy: c_float,
+    s_width: c_float,
+    s_height: c_float,
+    dx: c_float,
+    dy: c_float,
+    d_width: c_float,
+    d_height: c_float,
+) -> c_longlong {
+    draw_data_url_image(canvas_native_ptr, data_url, |canvas, image, paint| {
+        let src_rect = Rect::new(sx, sy, s_width + sx, s_height + sy);
+        canvas.draw_image_rect(
+            image,
+            Some((&src_rect, SrcRectConstraint::Strict)),
+            Rect::new(dx, dy, d_width + dx, d_height + dy),
+            paint,
+        );
+    })
+}
+
 #[inline]
 pub(crate) fn save(canvas_native_ptr: c_longlong) -> c_longlong {
     if canvas_native_ptr == 0 {
@@ -2446,11 +3165,19 @@ pub(crate) fn save(canvas_native_ptr: c_longlong) -> c_longlong {
         device_scale: canvas_native.device_scale,
         text_align: canvas_native.text_align.clone(),
         ios: canvas_native.ios.clone(),
-        global_composite_operation: canvas_native.global_composite_operation.clone(),
+        global_composite_operation: canvas_native.global_composite_operation,
         line_cap: canvas_native.line_cap.clone(),
         line_join: canvas_native.line_join.clone(),
         direction: canvas_native.direction.clone(),
         miter_limit: canvas_native.miter_limit,
+        global_alpha: canvas_native.global_alpha,
+        filter: canvas_native.filter.clone(),
+        image_filter: canvas_native.image_filter.clone(),
+        stroke_style: canvas_native.stroke_style.clone(),
+        fill_style: canvas_native.fill_style.clone(),
+        path_effects: canvas_native.path_effects.clone(),
+        line_dash: canvas_native.line_dash.clone(),
+        clip_paths: canvas_native.clip_paths.clone(),
         surface_kind: canvas_native.surface_kind.clone(),
     };
 
@@ -2511,6 +3238,9 @@ pub(crate) fn clip_path_rule(
     let mut path: Box<Path> = unsafe { Box::from_raw(path as *mut _) };
     path.set_fill_type(fill_type);
     canvas.clip_path(&path, Some(ClipOp::Intersect), Some(true));
+    let mut device_path = path.as_ref().clone();
+    device_path.transform(&canvas.total_matrix());
+    canvas_native.clip_paths.push(device_path);
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
@@ -2535,6 +3265,9 @@ pub(crate) fn clip_rule(canvas_native_ptr: c_longlong, fill_rule: *const c_char)
     };
     canvas_native.path.set_fill_type(fill_type);
     canvas.clip_path(&canvas_native.path, Some(ClipOp::Intersect), Some(true));
+    let mut device_path = canvas_native.path.clone();
+    device_path.transform(&canvas.total_matrix());
+    canvas_native.clip_paths.push(device_path);
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
@@ -2602,17 +3335,30 @@ pub(crate) fn set_line_join(canvas_native_ptr: c_longlong, line_cap: *const c_ch
 }
 
 #[inline]
-pub(crate) fn set_global_alpha(canvas_native_ptr: c_longlong, alpha: u8) -> c_longlong {
+pub(crate) fn set_global_alpha(canvas_native_ptr: c_longlong, alpha: f32) -> c_longlong {
     if canvas_native_ptr == 0 {
         return 0;
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    canvas_native.fill_paint.set_alpha(alpha);
-    canvas_native.stroke_paint.set_alpha(alpha);
+    // Values outside 0..=1 (and NaN) are ignored.
+    if alpha >= 0.0 && alpha <= 1.0 {
+        canvas_native.global_alpha = alpha;
+    }
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
+#[inline]
+pub(crate) fn get_global_alpha(canvas_native_ptr: c_longlong) -> f32 {
+    if canvas_native_ptr == 0 {
+        return 1.0;
+    }
+    let canvas_native: Box<CanvasNative> = unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let alpha = canvas_native.global_alpha;
+    Box::into_raw(canvas_native);
+    alpha
+}
+
 #[inline]
 pub(crate) fn set_miter_limit(canvas_native_ptr: c_longlong, limit: f32) -> c_longlong {
     if canvas_native_ptr == 0 {
@@ -2632,7 +3378,10 @@ pub(crate) fn set_line_dash_offset(canvas_native_ptr: c_longlong, offset: f32) -
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    canvas_native.line_dash_offset = offset;
+    if offset.is_finite() {
+        canvas_native.line_dash_offset = offset;
+        update_path_effects(&mut canvas_native);
+    }
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
@@ -2643,7 +3392,10 @@ pub(crate) fn set_shadow_blur(canvas_native_ptr: c_longlong, limit: f32) -> c_lo
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    canvas_native.shadow_blur = limit;
+    // Negative and non-finite values are ignored.
+    if limit.is_finite() && limit >= 0.0 {
+        canvas_native.shadow_blur = limit;
+    }
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
@@ -2665,7 +3417,9 @@ pub(crate) fn set_shadow_offset_x(canvas_native_ptr: c_longlong, x: f32) -> c_lo
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    canvas_native.shadow_offset_x = x;
+    if x.is_finite() {
+        canvas_native.shadow_offset_x = x;
+    }
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
@@ -2676,7 +3430,9 @@ pub(crate) fn set_shadow_offset_y(canvas_native_ptr: c_longlong, y: f32) -> c_lo
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    canvas_native.shadow_offset_y = y;
+    if y.is_finite() {
+        canvas_native.shadow_offset_y = y;
+    }
     Box::into_raw(canvas_native) as *mut _ as i64
 }
 
@@ -2807,23 +3563,8 @@ fn update_quality(enabled: bool, canvas_native_ptr: c_longlong) -> c_longlong {
     }
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    let mut native_quality = FilterQuality::Low;
-    if enabled {
-        match canvas_native.image_smoothing_quality.borrow() {
-            "high" => {
-                native_quality = FilterQuality::High;
-            }
-            "medium" => {
-                native_quality = FilterQuality::Medium;
-            }
-            _ => {
-                native_quality = FilterQuality::Low;
-            }
-        }
-    } else {
-        native_quality = FilterQuality::None;
-    }
-
+    canvas_native.image_smoothing_enabled = enabled;
+    let native_quality = canvas_native.filter_quality();
     canvas_native
         .stroke_paint
         .set_filter_quality(native_quality);
@@ -2843,12 +3584,11 @@ pub(crate) fn set_image_smoothing_quality(
 
     let mut canvas_native: Box<CanvasNative> =
         unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
-    let mut qual = unsafe { CStr::from_ptr(quality).to_str().unwrap_or("low") };
-    qual = match qual {
-        "high" | "medium" | "low" => qual,
-        _ => "low",
-    };
-    canvas_native.image_smoothing_quality = qual.to_string();
+    let qual = unsafe { CStr::from_ptr(quality).to_str().unwrap_or("") };
+    // Anything else is ignored, as the spec does for unknown enum values.
+    if let "high" | "medium" | "low" = qual {
+        canvas_native.image_smoothing_quality = qual.to_string();
+    }
     let mut enabled = canvas_native.image_smoothing_enabled;
     let ptr = Box::into_raw(canvas_native) as *mut _ as i64;
     update_quality(enabled, ptr)
@@ -2924,3 +3664,443 @@ pub(crate) fn add_path_to_path_with_matrix(
     }
     path_native_ptr
 }
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use super::*;
+
+    const COMPOSITE_OPERATIONS: [(&str, BlendMode); 26] = [
+        ("source-over", BlendMode::SrcOver),
+        ("source-in", BlendMode::SrcIn),
+        ("source-out", BlendMode::SrcOut),
+        ("source-atop", BlendMode::SrcATop),
+        ("destination-over", BlendMode::DstOver),
+        ("destination-in", BlendMode::DstIn),
+        ("destination-out", BlendMode::DstOut),
+        ("destination-atop", BlendMode::DstATop),
+        ("lighter", BlendMode::Plus),
+        ("copy", BlendMode::Src),
+        ("xor", BlendMode::Xor),
+        ("multiply", BlendMode::Multiply),
+        ("screen", BlendMode::Screen),
+        ("overlay", BlendMode::Overlay),
+        ("darken", BlendMode::Darken),
+        ("lighten", BlendMode::Lighten),
+        ("color-dodge", BlendMode::ColorDodge),
+        ("color-burn", BlendMode::ColorBurn),
+        ("hard-light", BlendMode::HardLight),
+        ("soft-light", BlendMode::SoftLight),
+        ("difference", BlendMode::Difference),
+        ("exclusion", BlendMode::Exclusion),
+        ("hue", BlendMode::Hue),
+        ("saturation", BlendMode::Saturation),
+        ("color", BlendMode::Color),
+        ("luminosity", BlendMode::Luminosity),
+    ];
+
+    fn raster_canvas() -> c_longlong {
+        raster_canvas_sized(4, 4)
+    }
+
+    fn raster_canvas_sized(width: i32, height: i32) -> c_longlong {
+        let canvas_native = CanvasNative {
+            surface: Surface::new_raster_n32_premul(ISize::new(width, height)).unwrap(),
+            stroke_paint: Paint::default(),
+            fill_paint: Paint::default(),
+            path: Path::new(),
+            context: None,
+            font: Font::default(),
+            state: Vec::new(),
+            line_dash_offset: 0.0,
+            shadow_blur: 0.0,
+            shadow_color: COLOR_TRANSPARENT as u32,
+            shadow_offset_x: 0.0,
+            shadow_offset_y: 0.0,
+            image_smoothing_enabled: true,
+            image_smoothing_quality: "low".to_string(),
+            device_scale: 1.0,
+            text_align: "start".to_string(),
+            ios: 0,
+            global_composite_operation: CanvasCompositeOperationType::SourceOver,
+            line_cap: "butt".to_string(),
+            line_join: "miter".to_string(),
+            direction: "ltr".to_string(),
+            miter_limit: 10.0,
+            global_alpha: 1.0,
+            filter: "none".to_string(),
+            image_filter: None,
+            stroke_style: Default::default(),
+            fill_style: Default::default(),
+            path_effects: Default::default(),
+            line_dash: vec![],
+            clip_paths: vec![],
+            surface_kind: SurfaceKind::CPU,
+            hit_regions: vec![],
+            image_cache: Default::default(),
+        };
+        Box::into_raw(Box::new(canvas_native)) as *mut _ as i64
+    }
+
+    fn set_operation(canvas_native_ptr: c_longlong, operation: &str) -> c_longlong {
+        let operation = CString::new(operation).unwrap();
+        set_global_composite_operation(canvas_native_ptr, operation.as_ptr())
+    }
+
+    fn free_canvas(canvas_native_ptr: c_longlong) {
+        let _: Box<CanvasNative> = unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    }
+
+    // Premultiplied RGBA, the way the surface stores it.
+    fn pixel(canvas_native_ptr: c_longlong, x: i32, y: i32) -> [u8; 4] {
+        let mut canvas_native: Box<CanvasNative> =
+            unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+        let info = ImageInfo::new(ISize::new(1, 1), ColorType::RGBA8888, AlphaType::Premul, None);
+        let mut pixel = [0u8; 4];
+        assert!(canvas_native.surface.read_pixels(&info, &mut pixel, 4, IPoint::new(x, y)));
+        Box::into_raw(canvas_native);
+        pixel
+    }
+
+    // Unpremultiplied RGBA for the destination and source of the composite tests. The
+    // channels stay clear of 0 and 1 so each blend formula gives a distinct result.
+    const DESTINATION: [u8; 4] = [51, 153, 204, 191];
+    const SOURCE: [u8; 4] = [230, 77, 26, 179];
+
+    fn premultiply(color: [u8; 4]) -> [f32; 4] {
+        let alpha = color[3] as f32 / 255.0;
+        [
+            color[0] as f32 / 255.0 * alpha,
+            color[1] as f32 / 255.0 * alpha,
+            color[2] as f32 / 255.0 * alpha,
+            alpha,
+        ]
+    }
+
+    fn luminosity(c: [f32; 3]) -> f32 {
+        0.3 * c[0] + 0.59 * c[1] + 0.11 * c[2]
+    }
+
+    fn set_luminosity(c: [f32; 3], l: f32) -> [f32; 3] {
+        let d = l - luminosity(c);
+        let c = [c[0] + d, c[1] + d, c[2] + d];
+        let l = luminosity(c);
+        let min = c[0].min(c[1]).min(c[2]);
+        let max = c[0].max(c[1]).max(c[2]);
+        let mut clipped = c;
+        for channel in clipped.iter_mut() {
+            if min < 0.0 {
+                *channel = l + (*channel - l) * l / (l - min);
+            }
+            if max > 1.0 {
+                *channel = l + (*channel - l) * (1.0 - l) / (max - l);
+            }
+        }
+        clipped
+    }
+
+    fn saturation(c: [f32; 3]) -> f32 {
+        c[0].max(c[1]).max(c[2]) - c[0].min(c[1]).min(c[2])
+    }
+
+    fn set_saturation(c: [f32; 3], s: f32) -> [f32; 3] {
+        let min = c[0].min(c[1]).min(c[2]);
+        let max = c[0].max(c[1]).max(c[2]);
+        let mut result = [0.0; 3];
+        if max > min {
+            for (index, channel) in c.iter().enumerate() {
+                result[index] = (channel - min) * s / (max - min);
+            }
+        }
+        result
+    }
+
+    fn blend_channel(operation: &str, cs: f32, cb: f32) -> f32 {
+        let screen = |a: f32, b: f32| a + b - a * b;
+        let hard_light = |cs: f32, cb: f32| {
+            if cs <= 0.5 {
+                cb * 2.0 * cs
+            } else {
+                screen(cb, 2.0 * cs - 1.0)
+            }
+        };
+        match operation {
+            "multiply" => cs * cb,
+            "screen" => screen(cs, cb),
+            "overlay" => hard_light(cb, cs),
+            "darken" => cs.min(cb),
+            "lighten" => cs.max(cb),
+            "color-dodge" => (cb / (1.0 - cs)).min(1.0),
+            "color-burn" => 1.0 - ((1.0 - cb) / cs).min(1.0),
+            "hard-light" => hard_light(cs, cb),
+            "soft-light" => {
+                if cs <= 0.5 {
+                    cb - (1.0 - 2.0 * cs) * cb * (1.0 - cb)
+                } else {
+                    let d = if cb <= 0.25 { ((16.0 * cb - 12.0) * cb + 4.0) * cb } else { cb.sqrt() };
+                    cb + (2.0 * cs - 1.0) * (d - cb)
+                }
+            }
+            "difference" => (cs - cb).abs(),
+            "exclusion" => cs + cb - 2.0 * cs * cb,
+            _ => unreachable!(),
+        }
+    }
+
+    // The expected premultiplied result of compositing `src` over `dst`, following the
+    // Compositing and Blending spec.
+    fn composite(operation: &str, src: [f32; 4], dst: [f32; 4]) -> [f32; 4] {
+        let (sa, da) = (src[3], dst[3]);
+        let porter_duff = |fa: f32, fb: f32| {
+            [
+                fa * src[0] + fb * dst[0],
+                fa * src[1] + fb * dst[1],
+                fa * src[2] + fb * dst[2],
+                fa * sa + fb * da,
+            ]
+        };
+        match operation {
+            "source-over" => return porter_duff(1.0, 1.0 - sa),
+            "source-in" => return porter_duff(da, 0.0),
+            "source-out" => return porter_duff(1.0 - da, 0.0),
+            "source-atop" => return porter_duff(da, 1.0 - sa),
+            "destination-over" => return porter_duff(1.0 - da, 1.0),
+            "destination-in" => return porter_duff(0.0, sa),
+            "destination-out" => return porter_duff(0.0, 1.0 - sa),
+            "destination-atop" => return porter_duff(1.0 - da, sa),
+            "copy" => return porter_duff(1.0, 0.0),
+            "xor" => return porter_duff(1.0 - da, 1.0 - sa),
+            "lighter" => {
+                let sum = porter_duff(1.0, 1.0);
+                return [sum[0].min(1.0), sum[1].min(1.0), sum[2].min(1.0), sum[3].min(1.0)];
+            }
+            _ => {}
+        }
+        let cs = [src[0] / sa, src[1] / sa, src[2] / sa];
+        let cb = [dst[0] / da, dst[1] / da, dst[2] / da];
+        let blended = match operation {
+            "hue" => set_luminosity(set_saturation(cs, saturation(cb)), luminosity(cb)),
+            "saturation" => set_luminosity(set_saturation(cb, saturation(cs)), luminosity(cb)),
+            "color" => set_luminosity(cs, luminosity(cb)),
+            "luminosity" => set_luminosity(cb, luminosity(cs)),
+            _ => [
+                blend_channel(operation, cs[0], cb[0]),
+                blend_channel(operation, cs[1], cb[1]),
+                blend_channel(operation, cs[2], cb[2]),
+            ],
+        };
+        let channel = |index: usize| src[index] * (1.0 - da) + dst[index] * (1.0 - sa) + sa * da * blended[index];
+        [channel(0), channel(1), channel(2), sa + da - sa * da]
+    }
+
+    // Paints DESTINATION over the whole canvas, then the source with `operation`, and
+    // compares a pixel with the spec result.
+    fn assert_composites(operation: &str, draw_source: impl Fn(c_longlong) -> c_longlong) {
+        let mut canvas_native_ptr = raster_canvas();
+        canvas_native_ptr = set_fill_color_rgba(
+            canvas_native_ptr,
+            DESTINATION[0],
+            DESTINATION[1],
+            DESTINATION[2],
+            DESTINATION[3],
+        );
+        canvas_native_ptr = draw_rect(canvas_native_ptr, 0.0, 0.0, 4.0, 4.0, false);
+        let destination = pixel(canvas_native_ptr, 1, 1);
+        let dst = [
+            destination[0] as f32 / 255.0,
+            destination[1] as f32 / 255.0,
+            destination[2] as f32 / 255.0,
+            destination[3] as f32 / 255.0,
+        ];
+        canvas_native_ptr = set_operation(canvas_native_ptr, operation);
+        canvas_native_ptr = draw_source(canvas_native_ptr);
+        let expected = composite(operation, premultiply(SOURCE), dst);
+        let actual = pixel(canvas_native_ptr, 1, 1);
+        for (index, (actual_channel, expected_channel)) in actual.iter().zip(expected.iter()).enumerate() {
+            let expected = (expected_channel * 255.0).round() as i32;
+            assert!(
+                (*actual_channel as i32 - expected).abs() <= 3,
+                "{}: got {:?}, expected channel {} to be {}",
+                operation,
+                actual,
+                index,
+                expected
+            );
+        }
+        free_canvas(canvas_native_ptr);
+    }
+
+    fn hit(canvas_native_ptr: c_longlong, x: f32, y: f32) -> Option<String> {
+        let id = hit_region_at(canvas_native_ptr, x, y);
+        if id.is_null() {
+            return None;
+        }
+        Some(unsafe { CString::from_raw(id as *mut _) }.into_string().unwrap())
+    }
+
+    fn add_rect_region(canvas_native_ptr: c_longlong, id: &str, size: f32) -> c_longlong {
+        let id = CString::new(id).unwrap();
+        let rule = CString::new("nonzero").unwrap();
+        let mut canvas_native_ptr = begin_path(canvas_native_ptr);
+        canvas_native_ptr = rect(canvas_native_ptr, true, 0.0, 0.0, size, size);
+        add_hit_region(canvas_native_ptr, 0, id.as_ptr(), false, rule.as_ptr())
+    }
+
+    fn clip_circle(canvas_native_ptr: c_longlong, x: f32, y: f32, radius: f32) -> c_longlong {
+        let mut canvas_native_ptr = begin_path(canvas_native_ptr);
+        let full_turn = 2.0 * std::f32::consts::PI;
+        canvas_native_ptr = arc(canvas_native_ptr, true, x, y, radius, 0.0, full_turn, false);
+        clip(canvas_native_ptr)
+    }
+
+    fn set_filter_str(canvas_native_ptr: c_longlong, filter: &str) -> c_longlong {
+        let filter = CString::new(filter).unwrap();
+        set_filter(canvas_native_ptr, filter.as_ptr())
+    }
+
+    #[test]
+    fn every_operation_round_trips() {
+        for (name, blend_mode) in COMPOSITE_OPERATIONS.iter() {
+            let operation = CanvasCompositeOperationType::value_from_str(name)
+                .unwrap_or_else(|| panic!("{} was not parsed", name));
+            assert_eq!(operation.as_str(), *name);
+            assert_eq!(operation.get_blend_mode(), *blend_mode, "{}", name);
+        }
+    }
+
+    #[test]
+    fn lighter_adds() {
+        let operation = CanvasCompositeOperationType::value_from_str("lighter").unwrap();
+        assert_eq!(operation, CanvasCompositeOperationType::Lighter);
+        assert_eq!(operation.get_blend_mode(), BlendMode::Plus);
+    }
+
+    #[test]
+    fn unknown_operations_are_rejected() {
+        for name in ["", "plus-lighter", "Source-Over", "MULTIPLY", "source-over ", "darker"].iter() {
+            assert_eq!(CanvasCompositeOperationType::value_from_str(name), None, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn setter_applies_every_operation() {
+        let mut canvas_native_ptr = raster_canvas();
+        for (name, _) in COMPOSITE_OPERATIONS.iter() {
+            canvas_native_ptr = set_operation(canvas_native_ptr, name);
+            assert_eq!(get_global_composite_operation(canvas_native_ptr), *name);
+        }
+        free_canvas(canvas_native_ptr);
+    }
+
+    #[test]
+    fn setter_ignores_unknown_operations() {
+        let mut canvas_native_ptr = raster_canvas();
+        canvas_native_ptr = set_operation(canvas_native_ptr, "multiply");
+        for name in ["", "plus-lighter", "Multiply", "SCREEN", "not-an-operation"].iter() {
+            canvas_native_ptr = set_operation(canvas_native_ptr, name);
+            assert_eq!(get_global_composite_operation(canvas_native_ptr), "multiply");
+        }
+        free_canvas(canvas_native_ptr);
+    }
+
+    #[test]
+    fn drop_shadow_filter_offset_ignores_the_transform() {
+        let mut canvas_native_ptr = raster_canvas_sized(16, 8);
+        canvas_native_ptr = set_filter_str(canvas_native_ptr, "drop-shadow(4px 0px black)");
+        canvas_native_ptr = scale(canvas_native_ptr, 2.0, 2.0);
+        canvas_native_ptr = draw_rect(canvas_native_ptr, 0.0, 0.0, 2.0, 2.0, false);
+        // The rectangle covers x 0..4 and its shadow x 4..8, not the scaled 8..12.
+        assert_eq!(pixel(canvas_native_ptr, 2, 2)[3], 255);
+        assert_eq!(pixel(canvas_native_ptr, 6, 2)[3], 255);
+        assert_eq!(pixel(canvas_native_ptr, 10, 2)[3], 0);
+        free_canvas(canvas_native_ptr);
+    }
+
+    #[test]
+    fn blur_filter_radius_ignores_the_transform() {
+        let mut scaled = raster_canvas_sized(32, 32);
+        scaled = set_filter_str(scaled, "blur(2px)");
+        scaled = scale(scaled, 4.0, 4.0);
+        scaled = draw_rect(scaled, 2.0, 2.0, 4.0, 4.0, false);
+        let mut plain = raster_canvas_sized(32, 32);
+        plain = set_filter_str(plain, "blur(2px)");
+        plain = draw_rect(plain, 8.0, 8.0, 16.0, 16.0, false);
+        for x in 0..32 {
+            let (scaled_alpha, plain_alpha) = (pixel(scaled, x, 16)[3], pixel(plain, x, 16)[3]);
+            assert!(
+                (scaled_alpha as i32 - plain_alpha as i32).abs() <= 2,
+                "x {}: {} vs {}",
+                x,
+                scaled_alpha,
+                plain_alpha
+            );
+        }
+        free_canvas(scaled);
+        free_canvas(plain);
+    }
+    #[test]
+    fn hit_regions_follow_a_circular_clip() {
+        let mut canvas_native_ptr = raster_canvas_sized(20, 20);
+        canvas_native_ptr = clip_circle(canvas_native_ptr, 10.0, 10.0, 8.0);
+        canvas_native_ptr = add_rect_region(canvas_native_ptr, "box", 20.0);
+        assert_eq!(hit(canvas_native_ptr, 10.0, 10.0).as_deref(), Some("box"));
+        assert_eq!(hit(canvas_native_ptr, 10.0, 3.0).as_deref(), Some("box"));
+        // Inside the clip bounds, outside the circle.
+        assert_eq!(hit(canvas_native_ptr, 3.0, 3.0), None);
+        assert_eq!(hit(canvas_native_ptr, 16.5, 16.5), None);
+        free_canvas(canvas_native_ptr);
+    }
+
+    #[test]
+    fn hit_region_clip_is_in_device_space_and_restored() {
+        let mut canvas_native_ptr = raster_canvas_sized(20, 20);
+        canvas_native_ptr = save(canvas_native_ptr);
+        canvas_native_ptr = scale(canvas_native_ptr, 2.0, 2.0);
+        canvas_native_ptr = clip_circle(canvas_native_ptr, 5.0, 5.0, 4.0);
+        canvas_native_ptr = reset_transform(canvas_native_ptr);
+        canvas_native_ptr = add_rect_region(canvas_native_ptr, "clipped", 20.0);
+        canvas_native_ptr = restore(canvas_native_ptr);
+        assert_eq!(hit(canvas_native_ptr, 10.0, 10.0).as_deref(), Some("clipped"));
+        assert_eq!(hit(canvas_native_ptr, 3.0, 3.0), None);
+
+        canvas_native_ptr = add_rect_region(canvas_native_ptr, "unclipped", 20.0);
+        assert_eq!(hit(canvas_native_ptr, 3.0, 3.0).as_deref(), Some("unclipped"));
+        assert_eq!(hit(canvas_native_ptr, 10.0, 10.0).as_deref(), Some("unclipped"));
+        free_canvas(canvas_native_ptr);
+    }
+    #[test]
+    fn fill_composites_with_every_operation() {
+        for (name, _) in COMPOSITE_OPERATIONS.iter() {
+            assert_composites(name, |canvas_native_ptr| {
+                let canvas_native_ptr =
+                    set_fill_color_rgba(canvas_native_ptr, SOURCE[0], SOURCE[1], SOURCE[2], SOURCE[3]);
+                draw_rect(canvas_native_ptr, 0.0, 0.0, 4.0, 4.0, false)
+            });
+        }
+    }
+
+    #[test]
+    fn draw_image_composites_with_every_operation() {
+        let source = premultiply(SOURCE);
+        let texel: Vec<u8> = source.iter().map(|channel| (channel * 255.0).round() as u8).collect();
+        let image: Vec<u8> = texel.iter().cloned().cycle().take(4 * 4 * 4).collect();
+        for (name, _) in COMPOSITE_OPERATIONS.iter() {
+            assert_composites(name, |canvas_native_ptr| {
+                draw_image(canvas_native_ptr, image.as_ptr(), image.len(), 4, 4, 0.0, 0.0)
+            });
+        }
+    }
+
+    #[test]
+    fn lighter_sums_channels() {
+        let mut canvas_native_ptr = raster_canvas();
+        canvas_native_ptr = set_fill_color_rgba(canvas_native_ptr, 100, 50, 0, 255);
+        canvas_native_ptr = draw_rect(canvas_native_ptr, 0.0, 0.0, 4.0, 4.0, false);
+        canvas_native_ptr = set_operation(canvas_native_ptr, "lighter");
+        canvas_native_ptr = set_fill_color_rgba(canvas_native_ptr, 100, 250, 30, 255);
+        canvas_native_ptr = draw_rect(canvas_native_ptr, 0.0, 0.0, 4.0, 4.0, false);
+        assert_eq!(pixel(canvas_native_ptr, 1, 1), [200, 255, 30, 255]);
+        free_canvas(canvas_native_ptr);
+    }
+}
@@ -0,0 +1,199 @@
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+/// The payload of a `data:` URL, or of a bare base64 string, in which case the mime
+/// type is empty.
+pub(crate) struct DataUrl {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+impl DataUrl {
+    /// Fails for data URLs that declare a type other than an image. Types left out
+    /// of the URL default to text/plain, which is let through since browsers sniff
+    /// images regardless.
+    pub fn image_data(self) -> Result<Vec<u8>, String> {
+        if !self.mime_type.is_empty()
+            && self.mime_type != "text/plain"
+            && !self.mime_type.starts_with("image/")
+        {
+            return Err(format!("Unsupported data URL type: {}", self.mime_type));
+        }
+        Ok(self.data)
+    }
+}
+
+pub(crate) fn parse_data_url_ptr(value: *const c_char) -> Result<DataUrl, String> {
+    if value.is_null() {
+        return Err("Empty data URL".to_string());
+    }
+    match unsafe { CStr::from_ptr(value) }.to_str() {
+        Ok(value) => parse_data_url(value),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Parses `data:[<mime type>][;<parameter>]*[;base64],<data>`, or a bare base64 string.
+pub(crate) fn parse_data_url(value: &str) -> Result<DataUrl, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err("Empty data URL".to_string());
+    }
+    let is_data_url = value
+        .get(..5)
+        .map_or(false, |scheme| scheme.eq_ignore_ascii_case("data:"));
+    if !is_data_url {
+        return Ok(DataUrl {
+            mime_type: String::new(),
+            data: decode_base64(value.as_bytes())?,
+        });
+    }
+
+    let rest = &value[5..];
+    let comma = rest
+        .find(',')
+        .ok_or_else(|| "Invalid data URL: missing ','".to_string())?;
+    let (header, body) = (&rest[..comma], &rest[comma + 1..]);
+    let mut parameters = header.split(';').map(|parameter| parameter.trim());
+    let mime_type = parameters.next().unwrap_or("").to_ascii_lowercase();
+    let mime_type = if mime_type.is_empty() {
+        "text/plain".to_string()
+    } else if mime_type.contains('/') {
+        mime_type
+    } else {
+        return Err(format!("Invalid data URL type: {}", mime_type));
+    };
+    let is_base64 = parameters.any(|parameter| parameter.eq_ignore_ascii_case("base64"));
+
+    // Both kinds of body may be percent-encoded; base64 is decoded afterwards.
+    let body = percent_decode(body)?;
+    let data = if is_base64 { decode_base64(&body)? } else { body };
+    Ok(DataUrl { mime_type, data })
+}
+
+fn percent_decode(value: &str) -> Result<Vec<u8>, String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let hex = bytes
+                .get(index + 1..index + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| format!("Invalid percent-encoding at {}", index))?;
+            decoded.push(hex);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    Ok(decoded)
+}
+
+// Accepts the standard and URL-safe alphabets, with or without padding, and ignores
+// whitespace such as the line breaks some encoders insert.
+fn decode_base64(value: &[u8]) -> Result<Vec<u8>, String> {
+    let mut cleaned: Vec<u8> = value
+        .iter()
+        .cloned()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect();
+    while cleaned.last() == Some(&b'=') {
+        cleaned.pop();
+    }
+    if cleaned.is_empty() {
+        return Err("Empty base64 data".to_string());
+    }
+    let config = if cleaned.iter().any(|byte| *byte == b'-' || *byte == b'_') {
+        base64::URL_SAFE_NO_PAD
+    } else {
+        base64::STANDARD_NO_PAD
+    };
+    base64::decode_config(&cleaned, config).map_err(|e| format!("Invalid base64 data: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 1x1 transparent GIF.
+    const GIF_BASE64: &str = "R0lGODlhAQABAAAAACH5BAEKAAEALAAAAAABAAEAAAICTAEAOw==";
+
+    fn gif() -> Vec<u8> {
+        base64::decode(GIF_BASE64).unwrap()
+    }
+
+    #[test]
+    fn parses_base64_image_urls() {
+        let url = parse_data_url(&format!("data:image/gif;base64,{}", GIF_BASE64)).unwrap();
+        assert_eq!(url.mime_type, "image/gif");
+        assert_eq!(url.image_data().unwrap(), gif());
+
+        let url = parse_data_url(&format!(" DATA:Image/GIF;charset=utf-8;BASE64,{}\n", GIF_BASE64)).unwrap();
+        assert_eq!(url.mime_type, "image/gif");
+        assert_eq!(url.data, gif());
+    }
+
+    #[test]
+    fn missing_comma_is_an_error() {
+        assert!(parse_data_url("data:image/png;base64").is_err());
+        assert!(parse_data_url("data:").is_err());
+    }
+
+    #[test]
+    fn decodes_percent_encoded_bodies() {
+        let url = parse_data_url("data:,a%20b%2Cc%e2%9c%93").unwrap();
+        assert_eq!(url.mime_type, "text/plain");
+        assert_eq!(url.data, "a b,c\u{2713}".as_bytes());
+
+        let escaped = GIF_BASE64.replace("=", "%3D").replace("+", "%2B").replace("/", "%2F");
+        let url = parse_data_url(&format!("data:image/gif;base64,{}", escaped)).unwrap();
+        assert_eq!(url.data, gif());
+
+        assert!(parse_data_url("data:,%zz").is_err());
+        assert!(parse_data_url("data:,%4").is_err());
+    }
+
+    #[test]
+    fn accepts_both_base64_alphabets() {
+        let bytes = [0xfb, 0xff, 0xbf, 0x00, 0x3e];
+        assert_eq!(parse_data_url("data:;base64,+/+/AD4=").unwrap().data, bytes);
+        assert_eq!(parse_data_url("data:;base64,-_-_AD4").unwrap().data, bytes);
+        assert_eq!(parse_data_url("data:;base64,+/+/\r\nAD4=").unwrap().data, bytes);
+        assert!(parse_data_url("data:;base64,+/-_AD4=").is_err());
+        assert!(parse_data_url("data:;base64,").is_err());
+    }
+
+    #[test]
+    fn only_images_are_image_data() {
+        let url = parse_data_url("data:application/json;base64,e30=").unwrap();
+        assert_eq!(url.mime_type, "application/json");
+        assert!(url.image_data().is_err());
+        assert!(parse_data_url("data:text/html,<p>").unwrap().image_data().is_err());
+        // Untyped URLs are sniffed like images.
+        assert_eq!(parse_data_url("data:;base64,e30=").unwrap().image_data().unwrap(), b"{}");
+        assert!(parse_data_url("data:png;base64,e30=").is_err());
+    }
+
+    #[test]
+    fn bare_base64_has_no_type() {
+        let url = parse_data_url(GIF_BASE64).unwrap();
+        assert_eq!(url.mime_type, "");
+        assert_eq!(url.image_data().unwrap(), gif());
+        assert!(parse_data_url("").is_err());
+        assert!(parse_data_url("not base64!").is_err());
+    }
+
+    #[test]
+    fn multibyte_prefixes_do_not_panic() {
+        assert!(parse_data_url("dat\u{e9}:,x").is_err());
+        assert!(parse_data_url("\u{2713}\u{2713}").is_err());
+    }
+
+    #[test]
+    fn null_pointers_are_rejected() {
+        assert!(parse_data_url_ptr(std::ptr::null()).is_err());
+    }
+}
@@ -0,0 +1,458 @@
+use std::ffi::CString;
+use std::os::raw::{c_char, c_uint};
+use std::ptr::null;
+
+use image::DynamicImage;
+
+const TAG_MAKE: u16 = 0x010F;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_ORIENTATION: u16 = 0x0112;
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+
+/// The handful of EXIF fields assets expose. `orientation` is the raw tag value,
+/// 1 through 8, or 0 when the image has no orientation tag.
+#[derive(Clone, Debug, Default)]
+pub struct ExifMetadata {
+    pub orientation: u16,
+    pub date_time: Option<String>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub has_gps: bool,
+}
+
+/// C view of `ExifMetadata`. Absent strings are null; release the strings with
+/// `image_asset_free_exif`.
+#[repr(C)]
+pub struct ImageAssetExif {
+    pub orientation: c_uint,
+    pub has_gps: bool,
+    pub date_time: *const c_char,
+    pub camera_make: *const c_char,
+    pub camera_model: *const c_char,
+}
+
+fn to_c_string(value: &Option<String>) -> *const c_char {
+    match value.as_ref().and_then(|value| CString::new(value.as_str()).ok()) {
+        Some(value) => value.into_raw(),
+        None => null(),
+    }
+}
+
+fn free_c_string(value: *const c_char) {
+    if !value.is_null() {
+        let _ = unsafe { CString::from_raw(value as *mut c_char) };
+    }
+}
+
+impl ImageAssetExif {
+    pub(crate) fn new(metadata: Option<&ExifMetadata>) -> Self {
+        match metadata {
+            Some(metadata) => Self {
+                orientation: metadata.orientation as c_uint,
+                has_gps: metadata.has_gps,
+                date_time: to_c_string(&metadata.date_time),
+                camera_make: to_c_string(&metadata.camera_make),
+                camera_model: to_c_string(&metadata.camera_model),
+            },
+            None => Self {
+                orientation: 0,
+                has_gps: false,
+                date_time: null(),
+                camera_make: null(),
+                camera_model: null(),
+            },
+        }
+    }
+
+    pub(crate) fn free(self) {
+        free_c_string(self.date_time);
+        free_c_string(self.camera_make);
+        free_c_string(self.camera_model);
+    }
+}
+
+/// Reads EXIF metadata from a JPEG, PNG, WebP or TIFF file.
+pub(crate) fn read_exif(buf: &[u8]) -> Option<ExifMetadata> {
+    let tiff = find_tiff(buf)?;
+    parse_tiff(tiff)
+}
+
+/// Turns an image stored with the given EXIF orientation upright.
+pub(crate) fn apply_orientation(image: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        // Transpose
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        // Transverse
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+// Returns the TIFF structure holding the EXIF data, wherever the container keeps it.
+fn find_tiff(buf: &[u8]) -> Option<&[u8]> {
+    if buf.starts_with(b"II*\0") || buf.starts_with(b"MM\0*") {
+        return Some(buf);
+    }
+    if buf.starts_with(&[0xFF, 0xD8]) {
+        // JPEG: an APP1 segment starting with "Exif\0\0".
+        let mut offset = 2;
+        while offset + 4 <= buf.len() && buf[offset] == 0xFF {
+            let marker = buf[offset + 1];
+            // Start of scan; metadata segments all come before it.
+            if marker == 0xDA {
+                break;
+            }
+            // The length counts itself but not the marker.
+            let length = u16::from_be_bytes([buf[offset + 2], buf[offset + 3]]) as usize;
+            let segment = slice(buf, offset + 4, length.checked_sub(2)?)?;
+            if marker == 0xE1 && segment.starts_with(b"Exif\0\0") {
+                return Some(&segment[6..]);
+            }
+            offset = offset.checked_add(2)?.checked_add(length)?;
+        }
+        return None;
+    }
+    if buf.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let mut offset = 8;
+        while offset + 8 <= buf.len() {
+            let length = read_u32(buf, offset, false)? as usize;
+            let kind = &buf[offset + 4..offset + 8];
+            if kind == b"eXIf" {
+                return slice(buf, offset + 8, length);
+            }
+            if kind == b"IDAT" {
+                break;
+            }
+            offset = offset.checked_add(12)?.checked_add(length)?;
+        }
+        return None;
+    }
+    if buf.len() >= 12 && &buf[0..4] == b"RIFF" && &buf[8..12] == b"WEBP" {
+        let mut offset = 12;
+        while offset + 8 <= buf.len() {
+            let length = read_u32(buf, offset + 4, true)? as usize;
+            if &buf[offset..offset + 4] == b"EXIF" {
+                let chunk = slice(buf, offset + 8, length)?;
+                // Some writers keep the JPEG "Exif\0\0" prefix.
+                return Some(if chunk.starts_with(b"Exif\0\0") { &chunk[6..] } else { chunk });
+            }
+            // Chunks are padded to an even size.
+            offset = offset.checked_add(8)?.checked_add(length)?.checked_add(length & 1)?;
+        }
+    }
+    None
+}
+
+// Offsets and lengths come from the file, so the end of the range is checked rather
+// than left to wrap around.
+fn slice(buf: &[u8], offset: usize, length: usize) -> Option<&[u8]> {
+    buf.get(offset..offset.checked_add(length)?)
+}
+
+fn read_u16(buf: &[u8], offset: usize, little_endian: bool) -> Option<u16> {
+    let bytes = slice(buf, offset, 2)?;
+    let bytes = [bytes[0], bytes[1]];
+    Some(if little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+}
+
+fn read_u32(buf: &[u8], offset: usize, little_endian: bool) -> Option<u32> {
+    let bytes = slice(buf, offset, 4)?;
+    let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    Some(if little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+}
+
+struct IfdEntry {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    // Offset of the 4-byte value field within the TIFF data.
+    value_offset: usize,
+}
+
+fn read_ifd(tiff: &[u8], offset: usize, little_endian: bool) -> Option<Vec<IfdEntry>> {
+    let count = read_u16(tiff, offset, little_endian)? as usize;
+    let first = offset.checked_add(2)?;
+    let mut entries = Vec::with_capacity(count);
+    for index in 0..count {
+        let entry = first.checked_add(index * 12)?;
+        entries.push(IfdEntry {
+            tag: read_u16(tiff, entry, little_endian)?,
+            kind: read_u16(tiff, entry + 2, little_endian)?,
+            count: read_u32(tiff, entry + 4, little_endian)?,
+            value_offset: entry + 8,
+        });
+    }
+    Some(entries)
+}
+
+fn read_ascii(tiff: &[u8], entry: &IfdEntry, little_endian: bool) -> Option<String> {
+    // Type 2 is ASCII; values over 4 bytes are stored at an offset.
+    if entry.kind != 2 {
+        return None;
+    }
+    let length = entry.count as usize;
+    let start = if length <= 4 {
+        entry.value_offset
+    } else {
+        read_u32(tiff, entry.value_offset, little_endian)? as usize
+    };
+    let bytes = slice(tiff, start, length)?;
+    let value = String::from_utf8_lossy(bytes);
+    let value = value.trim_end_matches('\0').trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+fn read_offset(tiff: &[u8], entry: &IfdEntry, little_endian: bool) -> Option<usize> {
+    read_u32(tiff, entry.value_offset, little_endian).map(|offset| offset as usize)
+}
+
+fn parse_tiff(tiff: &[u8]) -> Option<ExifMetadata> {
+    let little_endian = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    if read_u16(tiff, 2, little_endian)? != 42 {
+        return None;
+    }
+    let ifd0 = read_u32(tiff, 4, little_endian)? as usize;
+    let mut metadata = ExifMetadata::default();
+    let mut date_time = None;
+    for entry in read_ifd(tiff, ifd0, little_endian)? {
+        match entry.tag {
+            // SHORT values sit in the first two bytes of the value field.
+            TAG_ORIENTATION if entry.kind == 3 => {
+                let orientation = read_u16(tiff, entry.value_offset, little_endian).unwrap_or(0);
+                if orientation >= 1 && orientation <= 8 {
+                    metadata.orientation = orientation;
+                }
+            }
+            TAG_MAKE => metadata.camera_make = read_ascii(tiff, &entry, little_endian),
+            TAG_MODEL => metadata.camera_model = read_ascii(tiff, &entry, little_endian),
+            TAG_DATE_TIME => date_time = read_ascii(tiff, &entry, little_endian),
+            TAG_EXIF_IFD => {
+                let exif_ifd = read_offset(tiff, &entry, little_endian)
+                    .and_then(|offset| read_ifd(tiff, offset, little_endian));
+                if let Some(exif_ifd) = exif_ifd {
+                    metadata.date_time = exif_ifd
+                        .iter()
+                        .find(|entry| entry.tag == TAG_DATE_TIME_ORIGINAL)
+                        .and_then(|entry| read_ascii(tiff, entry, little_endian));
+                }
+            }
+            TAG_GPS_IFD => {
+                metadata.has_gps = read_offset(tiff, &entry, little_endian)
+                    .and_then(|offset| read_ifd(tiff, offset, little_endian))
+                    .map_or(false, |gps_ifd| !gps_ifd.is_empty());
+            }
+            _ => {}
+        }
+    }
+    // The capture time is DateTimeOriginal; DateTime is when the file was last changed,
+    // which is the best there is when the camera didn't write the former.
+    if metadata.date_time.is_none() {
+        metadata.date_time = date_time;
+    }
+    Some(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GenericImageView, Rgba, RgbaImage};
+
+    use super::*;
+
+    fn put_u16(buf: &mut Vec<u8>, value: u16, little_endian: bool) {
+        buf.extend_from_slice(&if little_endian { value.to_le_bytes() } else { value.to_be_bytes() });
+    }
+
+    fn put_u32(buf: &mut Vec<u8>, value: u32, little_endian: bool) {
+        buf.extend_from_slice(&if little_endian { value.to_le_bytes() } else { value.to_be_bytes() });
+    }
+
+    // A TIFF header and IFD0 holding an orientation and a Make string long enough to
+    // be stored at an offset.
+    fn tiff(little_endian: bool, orientation: u16) -> Vec<u8> {
+        let mut tiff = Vec::new();
+        tiff.extend_from_slice(if little_endian { b"II" } else { b"MM" });
+        put_u16(&mut tiff, 42, little_endian);
+        put_u32(&mut tiff, 8, little_endian);
+        put_u16(&mut tiff, 2, little_endian);
+        put_u16(&mut tiff, TAG_ORIENTATION, little_endian);
+        put_u16(&mut tiff, 3, little_endian);
+        put_u32(&mut tiff, 1, little_endian);
+        put_u16(&mut tiff, orientation, little_endian);
+        put_u16(&mut tiff, 0, little_endian);
+        put_u16(&mut tiff, TAG_MAKE, little_endian);
+        put_u16(&mut tiff, 2, little_endian);
+        put_u32(&mut tiff, 6, little_endian);
+        put_u32(&mut tiff, 38, little_endian);
+        put_u32(&mut tiff, 0, little_endian);
+        tiff.extend_from_slice(b"Canon\0");
+        tiff
+    }
+
+    fn jpeg(tiff: &[u8]) -> Vec<u8> {
+        let mut jpeg = vec![0xFF, 0xD8];
+        // An APP0 segment before the EXIF one, to be skipped.
+        jpeg.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x07]);
+        jpeg.extend_from_slice(b"JFIF\0");
+        jpeg.extend_from_slice(&[0xFF, 0xE1]);
+        jpeg.extend_from_slice(&(2 + 6 + tiff.len() as u16).to_be_bytes());
+        jpeg.extend_from_slice(b"Exif\0\0");
+        jpeg.extend_from_slice(tiff);
+        jpeg.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x02]);
+        jpeg
+    }
+
+    fn png_chunk(png: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        png.extend_from_slice(kind);
+        png.extend_from_slice(data);
+        // The CRC isn't checked.
+        png.extend_from_slice(&[0; 4]);
+    }
+
+    fn png(tiff: &[u8]) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png_chunk(&mut png, b"IHDR", &[0; 13]);
+        png_chunk(&mut png, b"eXIf", tiff);
+        png_chunk(&mut png, b"IDAT", &[]);
+        png
+    }
+
+    fn webp_chunk(webp: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+        webp.extend_from_slice(kind);
+        webp.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        webp.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            webp.push(0);
+        }
+    }
+
+    fn webp(exif: &[u8]) -> Vec<u8> {
+        let mut chunks = Vec::new();
+        webp_chunk(&mut chunks, b"VP8X", &[0x08, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        // Odd-sized, so the padding byte has to be skipped.
+        webp_chunk(&mut chunks, b"ICCP", &[1, 2, 3]);
+        webp_chunk(&mut chunks, b"EXIF", exif);
+        let mut webp = b"RIFF".to_vec();
+        webp.extend_from_slice(&(4 + chunks.len() as u32).to_le_bytes());
+        webp.extend_from_slice(b"WEBP");
+        webp.extend_from_slice(&chunks);
+        webp
+    }
+
+    fn assert_metadata(buf: &[u8], orientation: u16) {
+        let metadata = read_exif(buf).expect("no EXIF found");
+        assert_eq!(metadata.orientation, orientation);
+        assert_eq!(metadata.camera_make.as_deref(), Some("Canon"));
+        assert_eq!(metadata.camera_model, None);
+        assert!(!metadata.has_gps);
+    }
+
+    #[test]
+    fn reads_tiff_in_both_byte_orders() {
+        assert_metadata(&tiff(true, 6), 6);
+        assert_metadata(&tiff(false, 6), 6);
+    }
+
+    #[test]
+    fn reads_jpeg_app1() {
+        assert_metadata(&jpeg(&tiff(true, 3)), 3);
+        assert_metadata(&jpeg(&tiff(false, 8)), 8);
+    }
+
+    #[test]
+    fn reads_png_exif_chunk() {
+        assert_metadata(&png(&tiff(true, 5)), 5);
+        assert_metadata(&png(&tiff(false, 2)), 2);
+    }
+
+    #[test]
+    fn reads_webp_exif_chunk() {
+        assert_metadata(&webp(&tiff(true, 7)), 7);
+        assert_metadata(&webp(&tiff(false, 4)), 4);
+        let mut prefixed = b"Exif\0\0".to_vec();
+        prefixed.extend_from_slice(&tiff(false, 6));
+        assert_metadata(&webp(&prefixed), 6);
+    }
+
+    #[test]
+    fn reads_every_orientation() {
+        for orientation in 1..=8 {
+            assert_metadata(&jpeg(&tiff(orientation % 2 == 0, orientation)), orientation);
+        }
+        // Out of range values are ignored.
+        assert_metadata(&tiff(true, 9), 0);
+    }
+
+    #[test]
+    fn rejects_lengths_past_the_end() {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png.extend_from_slice(&u32::MAX.to_be_bytes());
+        png.extend_from_slice(b"tEXt");
+        png.extend_from_slice(&[0; 16]);
+        assert!(read_exif(&png).is_none());
+
+        let mut webp = b"RIFF\0\0\0\0WEBP".to_vec();
+        webp.extend_from_slice(b"ICCP");
+        webp.extend_from_slice(&u32::MAX.to_le_bytes());
+        webp.extend_from_slice(&[0; 16]);
+        assert!(read_exif(&webp).is_none());
+
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE1, 0xFF, 0xFF];
+        jpeg.extend_from_slice(b"Exif\0\0");
+        assert!(read_exif(&jpeg).is_none());
+        assert!(read_exif(&[0xFF, 0xD8, 0xFF, 0xE1, 0x00, 0x00]).is_none());
+
+        let mut truncated = tiff(true, 6);
+        truncated.truncate(40);
+        let metadata = read_exif(&truncated).unwrap();
+        assert_eq!(metadata.orientation, 6);
+        assert_eq!(metadata.camera_make, None);
+        assert!(read_exif(&tiff(true, 6)[..12]).is_none());
+    }
+
+    // Where the stored pixel (x, y) of a `width` by `height` image ends up once
+    // upright, following the definitions of the eight orientations.
+    fn displayed(orientation: u16, x: u32, y: u32, width: u32, height: u32) -> (u32, u32) {
+        match orientation {
+            2 => (width - 1 - x, y),
+            3 => (width - 1 - x, height - 1 - y),
+            4 => (x, height - 1 - y),
+            5 => (y, x),
+            6 => (height - 1 - y, x),
+            7 => (height - 1 - y, width - 1 - x),
+            8 => (y, width - 1 - x),
+            _ => (x, y),
+        }
+    }
+
+    #[test]
+    fn applies_every_orientation() {
+        let (width, height) = (3, 2);
+        let stored = RgbaImage::from_fn(width, height, |x, y| Rgba([x as u8, y as u8, 0, 255]));
+        for orientation in 1..=8 {
+            let upright = apply_orientation(DynamicImage::ImageRgba8(stored.clone()), orientation);
+            let expected = if orientation >= 5 { (height, width) } else { (width, height) };
+            assert_eq!(upright.dimensions(), expected, "orientation {}", orientation);
+            for (x, y, pixel) in stored.enumerate_pixels() {
+                let (dx, dy) = displayed(orientation, x, y, width, height);
+                assert_eq!(upright.get_pixel(dx, dy), *pixel, "orientation {} at {},{}", orientation, x, y);
+            }
+        }
+    }
+}
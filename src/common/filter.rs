@@ -0,0 +1,386 @@
+use skia_safe::{Color, ColorFilter, ImageFilter, Matrix, Vector, color_filters, image_filters};
+
+use crate::common::parse_color;
+
+/// Parses a CSS `filter` value into an image filter chain, with each function taking
+/// the output of the one before it as input. `none` parses to `Ok(None)`.
+pub(crate) fn parse_filter(value: &str) -> Result<Option<ImageFilter>, String> {
+    parse_filter_mapped(value, &Matrix::default())
+}
+
+/// Like `parse_filter`, with the blur and drop-shadow lengths mapped through `inverse`.
+/// The lengths are in device pixels, but Skia maps image filter parameters through the
+/// transform, so drawing under a transform passes its inverse to undo that.
+pub(crate) fn parse_filter_mapped(value: &str, inverse: &Matrix) -> Result<Option<ImageFilter>, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err("Empty filter".to_string());
+    }
+    if value.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+    let functions = split_functions(value).ok_or_else(|| format!("Invalid filter: {}", value))?;
+    let mut filter: Option<ImageFilter> = None;
+    for (name, args) in functions {
+        filter = apply_function(&name, args.trim(), filter, inverse)
+            .ok_or_else(|| format!("Invalid filter: {}", value))?;
+    }
+    Ok(filter)
+}
+
+// Splits `blur(2px) drop-shadow(1px 1px rgb(0 0 0))` into (name, arguments) pairs,
+// keeping nested parentheses inside the arguments.
+fn split_functions(value: &str) -> Option<Vec<(String, &str)>> {
+    let mut functions = Vec::new();
+    let mut rest = value.trim_start();
+    while !rest.is_empty() {
+        let open = rest.find('(')?;
+        let name = rest[..open].trim();
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return None;
+        }
+        let mut depth = 0;
+        let mut close = None;
+        for (index, c) in rest[open..].char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(open + index);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let close = close?;
+        functions.push((name.to_ascii_lowercase(), &rest[open + 1..close]));
+        rest = rest[close + 1..].trim_start();
+    }
+    Some(functions)
+}
+
+// Splits arguments on whitespace outside of parentheses.
+fn split_arguments(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = None;
+    for (index, c) in args.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        if c.is_whitespace() && depth == 0 {
+            if let Some(begin) = start.take() {
+                parts.push(&args[begin..index]);
+            }
+        } else if start.is_none() {
+            start = Some(index);
+        }
+    }
+    if let Some(begin) = start {
+        parts.push(&args[begin..]);
+    }
+    parts
+}
+
+fn parse_number(value: &str) -> Option<f32> {
+    match value.parse::<f32>() {
+        Ok(number) if number.is_finite() => Some(number),
+        _ => None,
+    }
+}
+
+fn parse_length(value: &str) -> Option<f32> {
+    let value = value.to_ascii_lowercase();
+    if value.ends_with("px") {
+        parse_number(&value[..value.len() - 2])
+    } else {
+        // Unitless lengths are only allowed for zero.
+        parse_number(&value).filter(|length| *length == 0.0)
+    }
+}
+
+fn parse_amount(value: &str) -> Option<f32> {
+    if value.ends_with('%') {
+        parse_number(&value[..value.len() - 1]).map(|amount| amount / 100.0)
+    } else {
+        parse_number(value)
+    }
+}
+
+fn parse_angle(value: &str) -> Option<f32> {
+    let value = value.to_ascii_lowercase();
+    let units = [("deg", 1.0), ("grad", 0.9), ("rad", 180.0 / std::f32::consts::PI), ("turn", 360.0)];
+    for (unit, factor) in units.iter() {
+        if value.ends_with(unit) {
+            return parse_number(&value[..value.len() - unit.len()]).map(|angle| angle * factor);
+        }
+    }
+    parse_number(&value).filter(|angle| *angle == 0.0)
+}
+
+fn single_argument<T>(args: &str, default: T, parse: impl Fn(&str) -> Option<T>) -> Option<T> {
+    match split_arguments(args).as_slice() {
+        [] => Some(default),
+        [value] => parse(value),
+        _ => None,
+    }
+}
+
+// The x and y sigmas that blur by `sigma` device pixels once Skia applies the transform.
+fn map_sigma(inverse: &Matrix, sigma: f32) -> (f32, f32) {
+    (
+        inverse.map_vector(Vector::new(sigma, 0.0)).length(),
+        inverse.map_vector(Vector::new(0.0, sigma)).length(),
+    )
+}
+
+fn apply_function(name: &str, args: &str, input: Option<ImageFilter>, inverse: &Matrix) -> Option<Option<ImageFilter>> {
+    match name {
+        "blur" => {
+            let radius = single_argument(args, 0.0, parse_length).filter(|radius| *radius >= 0.0)?;
+            if radius == 0.0 {
+                return Some(input);
+            }
+            Some(image_filters::blur(map_sigma(inverse, radius), None, input, None))
+        }
+        "drop-shadow" => {
+            // The color can come before or after the lengths, but not between them.
+            let mut args = split_arguments(args);
+            let mut color = None;
+            if args.first().map_or(false, |arg| parse_length(arg).is_none()) {
+                color = Some(parse_color(args.remove(0)).ok()?);
+            } else if args.last().map_or(false, |arg| parse_length(arg).is_none()) {
+                color = Some(parse_color(args.pop()?).ok()?);
+            }
+            let lengths = args
+                .iter()
+                .map(|arg| parse_length(arg))
+                .collect::<Option<Vec<f32>>>()?;
+            if lengths.len() < 2 || lengths.len() > 3 {
+                return None;
+            }
+            let blur = lengths.get(2).cloned().unwrap_or(0.0);
+            if blur < 0.0 {
+                return None;
+            }
+            // The blur length is a radius, so halve it for the Gaussian sigma the same way
+            // shadowBlur does.
+            Some(image_filters::drop_shadow(
+                inverse.map_vector(Vector::new(lengths[0], lengths[1])),
+                map_sigma(inverse, blur * 0.5),
+                color.unwrap_or(Color::BLACK),
+                input,
+                None,
+            ))
+        }
+        "hue-rotate" => {
+            let angle = single_argument(args, 0.0, parse_angle)?.to_radians();
+            Some(color_matrix(hue_rotate_matrix(angle), input))
+        }
+        _ => {
+            let amount = single_argument(args, 1.0, parse_amount).filter(|amount| *amount >= 0.0)?;
+            let matrix = match name {
+                "brightness" => scale_matrix(amount, 0.0, false),
+                "contrast" => scale_matrix(amount, (1.0 - amount) * 0.5, false),
+                "invert" => {
+                    let amount = amount.min(1.0);
+                    scale_matrix(1.0 - 2.0 * amount, amount, false)
+                }
+                "opacity" => scale_matrix(amount.min(1.0), 0.0, true),
+                "grayscale" => grayscale_matrix(1.0 - amount.min(1.0)),
+                "sepia" => sepia_matrix(1.0 - amount.min(1.0)),
+                "saturate" => saturate_matrix(amount),
+                _ => return None,
+            };
+            Some(color_matrix(matrix, input))
+        }
+    }
+}
+
+fn color_matrix(matrix: [f32; 20], input: Option<ImageFilter>) -> Option<ImageFilter> {
+    let color_filter: ColorFilter = color_filters::matrix_row_major(&matrix);
+    image_filters::color_filter(color_filter, input, None)
+}
+
+// Scales either the color channels or, for `alpha`, only the alpha channel, adding
+// `offset` (in 0..=1 units) to each scaled channel.
+fn scale_matrix(scale: f32, offset: f32, alpha: bool) -> [f32; 20] {
+    if alpha {
+        [
+            1.0, 0.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 0.0, scale, offset,
+        ]
+    } else {
+        [
+            scale, 0.0, 0.0, 0.0, offset,
+            0.0, scale, 0.0, 0.0, offset,
+            0.0, 0.0, scale, 0.0, offset,
+            0.0, 0.0, 0.0, 1.0, 0.0,
+        ]
+    }
+}
+
+fn rgb_matrix(m: [f32; 9]) -> [f32; 20] {
+    [
+        m[0], m[1], m[2], 0.0, 0.0,
+        m[3], m[4], m[5], 0.0, 0.0,
+        m[6], m[7], m[8], 0.0, 0.0,
+        0.0, 0.0, 0.0, 1.0, 0.0,
+    ]
+}
+
+// The matrices below are the ones given in the Filter Effects specification.
+
+fn grayscale_matrix(s: f32) -> [f32; 20] {
+    rgb_matrix([
+        0.2126 + 0.7874 * s, 0.7152 - 0.7152 * s, 0.0722 - 0.0722 * s,
+        0.2126 - 0.2126 * s, 0.7152 + 0.2848 * s, 0.0722 - 0.0722 * s,
+        0.2126 - 0.2126 * s, 0.7152 - 0.7152 * s, 0.0722 + 0.9278 * s,
+    ])
+}
+
+fn sepia_matrix(s: f32) -> [f32; 20] {
+    rgb_matrix([
+        0.393 + 0.607 * s, 0.769 - 0.769 * s, 0.189 - 0.189 * s,
+        0.349 - 0.349 * s, 0.686 + 0.314 * s, 0.168 - 0.168 * s,
+        0.272 - 0.272 * s, 0.534 - 0.534 * s, 0.131 + 0.869 * s,
+    ])
+}
+
+fn saturate_matrix(s: f32) -> [f32; 20] {
+    rgb_matrix([
+        0.213 + 0.787 * s, 0.715 - 0.715 * s, 0.072 - 0.072 * s,
+        0.213 - 0.213 * s, 0.715 + 0.285 * s, 0.072 - 0.072 * s,
+        0.213 - 0.213 * s, 0.715 - 0.715 * s, 0.072 + 0.928 * s,
+    ])
+}
+
+fn hue_rotate_matrix(angle: f32) -> [f32; 20] {
+    let (sin, cos) = angle.sin_cos();
+    rgb_matrix([
+        0.213 + cos * 0.787 - sin * 0.213,
+        0.715 - cos * 0.715 - sin * 0.715,
+        0.072 - cos * 0.072 + sin * 0.928,
+        0.213 - cos * 0.213 + sin * 0.143,
+        0.715 + cos * 0.285 + sin * 0.140,
+        0.072 - cos * 0.072 - sin * 0.283,
+        0.213 - cos * 0.213 - sin * 0.787,
+        0.715 - cos * 0.715 + sin * 0.715,
+        0.072 + cos * 0.928 + sin * 0.072,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parses(value: &str) -> bool {
+        match parse_filter(value) {
+            Ok(Some(_)) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn none_parses_to_no_filter() {
+        for value in ["none", "  NONE ", "None"].iter() {
+            assert!(matches!(parse_filter(value), Ok(None)), "{}", value);
+        }
+    }
+
+    #[test]
+    fn parses_each_function() {
+        let values = [
+            "blur(2px)",
+            "drop-shadow(1px 2px)",
+            "drop-shadow(1px 2px 3px)",
+            "drop-shadow(1px 2px 3px red)",
+            "drop-shadow(rgb(0 0 0 / 50%) 1px 2px)",
+            "brightness(50%)",
+            "contrast(2)",
+            "grayscale(1)",
+            "hue-rotate(90deg)",
+            "hue-rotate(0.25turn)",
+            "invert()",
+            "opacity(0.5)",
+            "saturate(30%)",
+            "sepia(100%)",
+            "blur(1px) drop-shadow(0 0 2px black) sepia(0.5)",
+        ];
+        for value in values.iter() {
+            assert!(parses(value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn zero_blur_passes_the_input_through() {
+        assert!(matches!(parse_filter("blur(0)"), Ok(None)));
+        assert!(matches!(parse_filter("blur()"), Ok(None)));
+        assert!(parses("sepia(1) blur(0px)"));
+    }
+
+    #[test]
+    fn rejects_invalid_forms() {
+        let values = [
+            "",
+            "   ",
+            "blur(2)",
+            "blur(-1px)",
+            "blur(1px 2px)",
+            "blur(1em)",
+            "drop-shadow(1px)",
+            "drop-shadow(1px 1px 1px 1px)",
+            "drop-shadow(1px 2px -3px)",
+            "drop-shadow(1px red 2px)",
+            "drop-shadow(1px 2px notacolor)",
+            "hue-rotate(90)",
+            "opacity(-1)",
+            "sepia(1, 2)",
+            "blur(1px) garbage",
+            "blur(1px))",
+            "blur(1px",
+            "unknown(1)",
+            "blur (1px)",
+        ];
+        for value in values.iter() {
+            assert!(parse_filter(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn parses_units() {
+        assert_eq!(parse_length("2PX"), Some(2.0));
+        assert_eq!(parse_length("0"), Some(0.0));
+        assert_eq!(parse_length("2"), None);
+        assert_eq!(parse_amount("50%"), Some(0.5));
+        assert_eq!(parse_amount("1.5"), Some(1.5));
+        assert_eq!(parse_angle("90deg"), Some(90.0));
+        assert_eq!(parse_angle("100grad"), Some(90.0));
+        assert_eq!(parse_angle("0.5turn"), Some(180.0));
+        assert!((parse_angle("3.14159265rad").unwrap() - 180.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn splits_functions_keeping_nested_parentheses() {
+        let functions = split_functions("Blur(1px)  drop-shadow(1px 1px rgb(0 0 0))").unwrap();
+        assert_eq!(
+            functions,
+            vec![("blur".to_string(), "1px"), ("drop-shadow".to_string(), "1px 1px rgb(0 0 0)")]
+        );
+        assert_eq!(split_arguments(" 1px  rgb(0 0 0) 2px "), vec!["1px", "rgb(0 0 0)", "2px"]);
+    }
+
+    #[test]
+    fn sigma_undoes_the_transform() {
+        let mut inverse = Matrix::default();
+        inverse.set_scale((0.5, 0.25), None);
+        assert_eq!(map_sigma(&inverse, 4.0), (2.0, 1.0));
+        assert_eq!(map_sigma(&Matrix::default(), 4.0), (4.0, 4.0));
+    }
+}
@@ -0,0 +1,260 @@
+use std::os::raw::c_char;
+use std::sync::{Arc, Mutex};
+
+use libc::{c_float, c_longlong};
+use skia_safe::{Color, Matrix, Point, Shader, TileMode, gradient_shader::GradientShaderColors};
+
+use crate::common::{CanvasNative, PaintStyle, parse_color_ptr};
+
+#[derive(Clone)]
+pub enum CanvasGradientKind {
+    Linear {
+        x0: f32,
+        y0: f32,
+        x1: f32,
+        y1: f32,
+    },
+    Radial {
+        x0: f32,
+        y0: f32,
+        r0: f32,
+        x1: f32,
+        y1: f32,
+        r1: f32,
+    },
+    /// Sweeps clockwise around (x, y) starting `start_angle` radians from the positive
+    /// x axis.
+    Conic {
+        start_angle: f32,
+        x: f32,
+        y: f32,
+    },
+}
+
+/// A gradient built up with `add_color_stop`. Styles turn it into a shader when
+/// drawing, so stops added after it was assigned are used, as on the web, and one
+/// gradient can be reused on any context.
+#[derive(Clone)]
+pub struct CanvasGradient {
+    kind: CanvasGradientKind,
+    stops: Vec<(f32, Color)>,
+    // Built on first use and dropped when a stop is added.
+    shader: Option<Shader>,
+}
+
+/// Gradient handles point at one of these. Each handle holds its own reference, so
+/// the gradient lives until every handle is freed and no style uses it.
+pub type SharedGradient = Arc<Mutex<CanvasGradient>>;
+
+#[repr(C)]
+pub enum CanvasGradientStopResult {
+    Ok = 0,
+    /// The offset was outside 0..=1 (an IndexSizeError in the spec).
+    InvalidOffset = 1,
+    /// The color could not be parsed (a SyntaxError in the spec).
+    InvalidColor = 2,
+    /// The gradient handle was 0.
+    InvalidHandle = 3,
+}
+
+impl CanvasGradient {
+    pub fn new(kind: CanvasGradientKind) -> Self {
+        Self {
+            kind,
+            stops: Vec::new(),
+            shader: None,
+        }
+    }
+
+    /// Takes the stops as given, for the legacy setters that pass colors and offsets
+    /// as two arrays.
+    pub(crate) fn from_stops(kind: CanvasGradientKind, colors: &[Color], positions: &[f32]) -> Self {
+        Self {
+            kind,
+            stops: positions.iter().cloned().zip(colors.iter().cloned()).collect(),
+            shader: None,
+        }
+    }
+
+    pub fn add_color_stop(&mut self, offset: f32, color: *const c_char) -> CanvasGradientStopResult {
+        if !offset.is_finite() || offset < 0.0 || offset > 1.0 {
+            return CanvasGradientStopResult::InvalidOffset;
+        }
+        let color = match parse_color_ptr(color) {
+            Ok(color) => color,
+            _ => return CanvasGradientStopResult::InvalidColor,
+        };
+        // Stops with the same offset stay in the order they were added, which gives a
+        // hard transition between them.
+        let position = self
+            .stops
+            .iter()
+            .position(|(stop, _)| *stop > offset)
+            .unwrap_or(self.stops.len());
+        self.stops.insert(position, (offset, color));
+        self.shader = None;
+        CanvasGradientStopResult::Ok
+    }
+
+    fn is_degenerate(&self) -> bool {
+        match self.kind {
+            CanvasGradientKind::Linear { x0, y0, x1, y1 } => x0 == x1 && y0 == y1,
+            CanvasGradientKind::Radial { x0, y0, r0, x1, y1, r1 } => {
+                x0 == x1 && y0 == y1 && r0 == r1
+            }
+            CanvasGradientKind::Conic { .. } => false,
+        }
+    }
+
+    /// The shader for the current stops, reused until another stop is added.
+    pub fn shader(&mut self) -> Option<Shader> {
+        if self.shader.is_none() {
+            self.shader = self.to_shader();
+        }
+        self.shader.clone()
+    }
+
+    pub fn to_shader(&self) -> Option<Shader> {
+        // Without stops, or with nothing to interpolate across, the spec paints
+        // transparent black. A single stop paints its color everywhere.
+        let (colors, positions): (Vec<Color>, Vec<f32>) = if self.stops.is_empty() || self.is_degenerate() {
+            (vec![Color::TRANSPARENT, Color::TRANSPARENT], vec![0.0, 1.0])
+        } else if self.stops.len() == 1 {
+            (vec![self.stops[0].1, self.stops[0].1], vec![0.0, 1.0])
+        } else {
+            self.stops.iter().map(|(offset, color)| (*color, *offset)).unzip()
+        };
+        let colors = GradientShaderColors::Colors(colors.as_slice());
+        match self.kind {
+            CanvasGradientKind::Linear { x0, y0, x1, y1 } => Shader::linear_gradient(
+                (Point::new(x0, y0), Point::new(x1, y1)),
+                colors,
+                Some(positions.as_slice()),
+                TileMode::Clamp,
+                None,
+                None,
+            ),
+            CanvasGradientKind::Radial { x0, y0, r0, x1, y1, r1 } => Shader::two_point_conical_gradient(
+                Point::new(x0, y0),
+                r0,
+                Point::new(x1, y1),
+                r1,
+                colors,
+                Some(positions.as_slice()),
+                TileMode::Clamp,
+                None,
+                None,
+            ),
+            CanvasGradientKind::Conic { start_angle, x, y } => {
+                let rotation = Matrix::rotate_deg_pivot(start_angle.to_degrees(), Point::new(x, y));
+                Shader::sweep_gradient(
+                    Point::new(x, y),
+                    colors,
+                    Some(positions.as_slice()),
+                    TileMode::Clamp,
+                    None,
+                    None,
+                    Some(&rotation),
+                )
+            }
+        }
+    }
+}
+
+#[inline]
+pub(crate) fn create_linear_gradient(x0: c_float, y0: c_float, x1: c_float, y1: c_float) -> c_longlong {
+    if ![x0, y0, x1, y1].iter().all(|value| value.is_finite()) {
+        return 0;
+    }
+    let gradient = CanvasGradient::new(CanvasGradientKind::Linear { x0, y0, x1, y1 });
+    gradient_handle(&Arc::new(Mutex::new(gradient)))
+}
+
+/// Returns 0 when a value isn't finite or a radius is negative.
+#[inline]
+pub(crate) fn create_radial_gradient(
+    x0: c_float,
+    y0: c_float,
+    r0: c_float,
+    x1: c_float,
+    y1: c_float,
+    r1: c_float,
+) -> c_longlong {
+    if ![x0, y0, r0, x1, y1, r1].iter().all(|value| value.is_finite()) || r0 < 0.0 || r1 < 0.0 {
+        return 0;
+    }
+    let gradient = CanvasGradient::new(CanvasGradientKind::Radial { x0, y0, r0, x1, y1, r1 });
+    gradient_handle(&Arc::new(Mutex::new(gradient)))
+}
+
+#[inline]
+pub(crate) fn create_conic_gradient(start_angle: c_float, x: c_float, y: c_float) -> c_longlong {
+    if ![start_angle, x, y].iter().all(|value| value.is_finite()) {
+        return 0;
+    }
+    let gradient = CanvasGradient::new(CanvasGradientKind::Conic { start_angle, x, y });
+    gradient_handle(&Arc::new(Mutex::new(gradient)))
+}
+
+/// A new handle to `gradient`, released with `free_gradient`.
+pub(crate) fn gradient_handle(gradient: &SharedGradient) -> c_longlong {
+    Box::into_raw(Box::new(gradient.clone())) as *mut _ as i64
+}
+
+#[inline]
+pub(crate) fn gradient_add_color_stop(
+    gradient: c_longlong,
+    offset: c_float,
+    color: *const c_char,
+) -> CanvasGradientStopResult {
+    if gradient == 0 {
+        return CanvasGradientStopResult::InvalidHandle;
+    }
+    let canvas_gradient: Box<SharedGradient> = unsafe { Box::from_raw(gradient as *mut _) };
+    let result = canvas_gradient.lock().unwrap().add_color_stop(offset, color);
+    Box::into_raw(canvas_gradient);
+    result
+}
+
+#[inline]
+pub(crate) fn free_gradient(gradient: c_longlong) {
+    if gradient == 0 {
+        return;
+    }
+    let _: Box<SharedGradient> = unsafe { Box::from_raw(gradient as *mut _) };
+}
+
+#[inline]
+pub(crate) fn set_fill_gradient(canvas_native_ptr: c_longlong, gradient: c_longlong) -> c_longlong {
+    set_gradient(canvas_native_ptr, gradient, true)
+}
+
+#[inline]
+pub(crate) fn set_stroke_gradient(canvas_native_ptr: c_longlong, gradient: c_longlong) -> c_longlong {
+    set_gradient(canvas_native_ptr, gradient, false)
+}
+
+fn set_gradient(canvas_native_ptr: c_longlong, gradient: c_longlong, is_fill: bool) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    if gradient == 0 {
+        return canvas_native_ptr;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let canvas_gradient: Box<SharedGradient> = unsafe { Box::from_raw(gradient as *mut _) };
+    let shared_gradient = (*canvas_gradient).clone();
+    Box::into_raw(canvas_gradient);
+    let shader = shared_gradient.lock().unwrap().shader();
+    if shader.is_some() {
+        if is_fill {
+            canvas_native.fill_paint.set_shader(shader);
+            canvas_native.fill_style = PaintStyle::Gradient(shared_gradient);
+        } else {
+            canvas_native.stroke_paint.set_shader(shader);
+            canvas_native.stroke_style = PaintStyle::Gradient(shared_gradient);
+        }
+    }
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
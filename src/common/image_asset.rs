@@ -1,18 +1,53 @@
 use std::ffi::{CStr, CString};
+use std::io::Cursor;
 use std::mem;
 use std::os::raw::{c_char, c_longlong, c_uint};
 use std::ptr::{null, null_mut};
 
-use image::{GenericImageView, ImageFormat};
+use image::{AnimationDecoder, DynamicImage, GenericImageView, ImageFormat, ImageResult, RgbImage, RgbaImage};
+use image::{ColorType, Delay, Frame, ImageError};
+use image::buffer::ConvertBuffer;
+use image::error::DecodingError;
+use image::codecs::bmp::BmpEncoder;
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::ico::IcoEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType as PngFilterType, PngDecoder, PngEncoder};
+use image::codecs::tiff::TiffEncoder;
 use image::imageops::FilterType;
+use image_webp::{LoopCount, WebPDecoder};
 use libc::size_t;
+use skia_safe::Image;
 
-use crate::common::NativeByteArray;
+use crate::common::{ExifMetadata, ImageAssetExif, NativeByteArray, apply_orientation, encode_webp_lossless, parse_data_url_ptr, rasterize_svg, read_exif};
 
 #[repr(C)]
 pub struct NativeImageAsset {
     pub(crate) image: Option<image::DynamicImage>,
     pub(crate) error: String,
+    /// Every frame of an animated GIF, APNG or WebP, composited to the full image size.
+    /// Empty for still images, where `image` is the only frame.
+    pub(crate) frames: Vec<ImageAssetFrame>,
+    /// How many times the animation plays, 0 meaning forever.
+    pub(crate) loop_count: c_uint,
+    pub(crate) exif: Option<ExifMetadata>,
+    /// Rotates and flips still images upright according to their EXIF orientation
+    /// when they're loaded.
+    pub(crate) auto_orient: bool,
+    /// `image` as a Skia image, made the first time it's needed so drawing the asset
+    /// and making patterns or bitmaps from it share one copy of the pixels.
+    pub(crate) raster: Option<RasterImage>,
+}
+
+// Skia images are immutable and reference counted atomically, so the cached one can
+// move between threads with its asset.
+pub(crate) struct RasterImage(pub(crate) Image);
+
+unsafe impl Send for RasterImage {}
+
+pub(crate) struct ImageAssetFrame {
+    pub(crate) image: RgbaImage,
+    pub(crate) delay: c_uint,
 }
 
 #[repr(C)]
@@ -22,12 +57,16 @@ pub enum PixelType {
 }
 
 #[repr(C)]
+#[derive(Copy, Clone)]
 pub enum OutputFormat {
     JPG = 0,
     PNG = 1,
     ICO = 2,
     BMP = 3,
     TIFF = 4,
+    GIF = 5,
+    /// Always lossless.
+    WEBP = 6,
 }
 
 impl From<u32> for OutputFormat {
@@ -37,11 +76,156 @@ impl From<u32> for OutputFormat {
             2 => OutputFormat::ICO,
             3 => OutputFormat::BMP,
             4 => OutputFormat::TIFF,
+            5 => OutputFormat::GIF,
+            6 => OutputFormat::WEBP,
             _ => OutputFormat::JPG,
         }
     }
 }
 
+/// Settings for `encode` and `save_path_with_options`; formats ignore the settings
+/// that don't apply to them. The PNG fields take the values of `PngCompression` and
+/// `PngFilter`.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct ImageAssetEncodeOptions {
+    /// 1 through 100.
+    pub jpeg_quality: c_uint,
+    pub png_compression: c_uint,
+    pub png_filter: c_uint,
+}
+
+impl ImageAssetEncodeOptions {
+    // What `save_path` wrote before it took options, the image crate's own encoder
+    // defaults, so existing callers get the same files.
+    fn save_path_defaults() -> Self {
+        Self {
+            jpeg_quality: 75,
+            png_compression: PngCompression::Fast as c_uint,
+            png_filter: PngFilter::Sub as c_uint,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub enum PngCompression {
+    Default = 0,
+    Fast = 1,
+    Best = 2,
+    Huffman = 3,
+    Rle = 4,
+}
+
+impl From<u32> for PngCompression {
+    fn from(compression: u32) -> Self {
+        match compression {
+            1 => PngCompression::Fast,
+            2 => PngCompression::Best,
+            3 => PngCompression::Huffman,
+            4 => PngCompression::Rle,
+            _ => PngCompression::Default,
+        }
+    }
+}
+
+impl From<PngCompression> for CompressionType {
+    fn from(compression: PngCompression) -> Self {
+        match compression {
+            PngCompression::Default => CompressionType::Default,
+            PngCompression::Fast => CompressionType::Fast,
+            PngCompression::Best => CompressionType::Best,
+            PngCompression::Huffman => CompressionType::Huffman,
+            PngCompression::Rle => CompressionType::Rle,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub enum PngFilter {
+    NoFilter = 0,
+    Sub = 1,
+    Up = 2,
+    Avg = 3,
+    Paeth = 4,
+}
+
+impl From<u32> for PngFilter {
+    fn from(filter: u32) -> Self {
+        match filter {
+            0 => PngFilter::NoFilter,
+            1 => PngFilter::Sub,
+            2 => PngFilter::Up,
+            3 => PngFilter::Avg,
+            _ => PngFilter::Paeth,
+        }
+    }
+}
+
+impl From<PngFilter> for PngFilterType {
+    fn from(filter: PngFilter) -> Self {
+        match filter {
+            PngFilter::NoFilter => PngFilterType::NoFilter,
+            PngFilter::Sub => PngFilterType::Sub,
+            PngFilter::Up => PngFilterType::Up,
+            PngFilter::Avg => PngFilterType::Avg,
+            PngFilter::Paeth => PngFilterType::Paeth,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub enum ResizeFilter {
+    Nearest = 0,
+    Triangle = 1,
+    CatmullRom = 2,
+    Lanczos3 = 3,
+}
+
+impl From<u32> for ResizeFilter {
+    fn from(filter: u32) -> Self {
+        match filter {
+            0 => ResizeFilter::Nearest,
+            2 => ResizeFilter::CatmullRom,
+            3 => ResizeFilter::Lanczos3,
+            _ => ResizeFilter::Triangle,
+        }
+    }
+}
+
+impl From<ResizeFilter> for FilterType {
+    fn from(filter: ResizeFilter) -> Self {
+        match filter {
+            ResizeFilter::Nearest => FilterType::Nearest,
+            ResizeFilter::Triangle => FilterType::Triangle,
+            ResizeFilter::CatmullRom => FilterType::CatmullRom,
+            ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// How `resize` treats the aspect ratio: `Stretch` ignores it, `Fit` scales to fit
+/// inside the box and `Fill` scales to cover the box, cropping the overflow.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub enum ResizeMode {
+    Stretch = 0,
+    Fit = 1,
+    Fill = 2,
+}
+
+impl From<u32> for ResizeMode {
+    fn from(mode: u32) -> Self {
+        match mode {
+            1 => ResizeMode::Fit,
+            2 => ResizeMode::Fill,
+            _ => ResizeMode::Stretch,
+        }
+    }
+}
+
 pub(crate) fn to_byte_slice(buf: &mut [i8]) -> &mut [u8] {
     unsafe { &mut *(buf as *mut [i8] as *mut [u8]) }
 }
@@ -51,9 +235,20 @@ impl NativeImageAsset {
         Self {
             image: None,
             error: String::new(),
+            frames: Vec::new(),
+            loop_count: 1,
+            exif: None,
+            auto_orient: false,
+            raster: None,
         }
     }
 
+    // Every change to the pixels goes through here or clears `raster` itself.
+    fn set_image(&mut self, image: Option<DynamicImage>) {
+        self.image = image;
+        self.raster = None;
+    }
+
     pub fn error(&self) -> *const c_char {
         if self.error.is_empty() { return null(); }
         CString::new(self.error.as_str()).unwrap().into_raw()
@@ -77,14 +272,11 @@ impl NativeImageAsset {
         if !self.error.is_empty() {
             self.error.clear()
         }
-        self.image = None;
+        self.set_image(None);
+        self.frames.clear();
         let real_path = unsafe { CStr::from_ptr(path) }.to_str().unwrap_or("");
-        let result = image::open(real_path);
-        match result {
-            Ok(result) => {
-                self.image = Some(result);
-                1
-            }
+        match std::fs::read(real_path) {
+            Ok(buf) => self.decode(&buf),
             Err(e) => {
                 let error = e.to_string();
                 self.error.push_str(error.as_str());
@@ -97,36 +289,52 @@ impl NativeImageAsset {
         if !self.error.is_empty() {
             self.error.clear()
         }
-        self.image = None;
+        self.set_image(None);
         let buf = unsafe { std::slice::from_raw_parts(buffer, size) };
-        let result = image::load_from_memory(buf);
-        match result {
-            Ok(result) => {
-                self.image = Some(result);
-                1
-            }
+        self.decode(buf)
+    }
+
+    pub fn load_from_bytes(&mut self, buf: &[u8]) -> c_uint {
+        if !self.error.is_empty() {
+            self.error.clear()
+        }
+        self.set_image(None);
+        self.decode(buf)
+    }
+
+    /// Loads a `data:` URL or a bare base64 string.
+    pub fn load_from_data_url(&mut self, url: *const c_char) -> c_uint {
+        if !self.error.is_empty() {
+            self.error.clear()
+        }
+        self.set_image(None);
+        match parse_data_url_ptr(url).and_then(|url| url.image_data()) {
+            Ok(buf) => self.decode(&buf),
             Err(e) => {
-                let error = e.to_string();
-                self.error.push_str(error.as_str());
+                self.frames.clear();
+                self.error.push_str(e.as_str());
                 0
             }
         }
     }
 
-    pub fn load_from_bytes(&mut self, buf: &[u8]) -> c_uint {
+    /// Renders an SVG document at `width` by `height`; see `rasterize_svg` for how a
+    /// size of 0 is handled.
+    pub fn load_svg(&mut self, svg: &[u8], width: c_uint, height: c_uint) -> c_uint {
         if !self.error.is_empty() {
             self.error.clear()
         }
-        self.image = None;
-        let result = image::load_from_memory(buf);
-        match result {
-            Ok(result) => {
-                self.image = Some(result);
+        self.set_image(None);
+        self.frames.clear();
+        self.loop_count = 1;
+        self.exif = None;
+        match rasterize_svg(svg, width, height) {
+            Ok(image) => {
+                self.set_image(Some(DynamicImage::ImageRgba8(image)));
                 1
             }
             Err(e) => {
-                let error = e.to_string();
-                self.error.push_str(error.as_str());
+                self.error.push_str(e.as_str());
                 0
             }
         }
@@ -136,15 +344,55 @@ impl NativeImageAsset {
         if !self.error.is_empty() {
             self.error.clear()
         }
-        self.image = None;
+        self.set_image(None);
         let buf = unsafe { &*(buf as *mut [i8] as *mut [u8]) };
-        let result = image::load_from_memory(buf);
-        match result {
-            Ok(result) => {
-                self.image = Some(result);
-                1
+        self.decode(buf)
+    }
+
+    // GIFs, APNGs and animated WebPs keep all of their frames; everything else loads
+    // as a single image.
+    fn decode(&mut self, buf: &[u8]) -> c_uint {
+        self.frames.clear();
+        self.loop_count = 1;
+        self.exif = None;
+        let result = match image::guess_format(buf) {
+            Ok(ImageFormat::Gif) => GifDecoder::new(Cursor::new(buf))
+                .and_then(|decoder| decode_frames(decoder))
+                .map(|frames| (frames, gif_loop_count(buf))),
+            Ok(ImageFormat::Png) => PngDecoder::new(Cursor::new(buf)).and_then(|decoder| {
+                if decoder.is_apng() {
+                    decode_frames(decoder.apng()).map(|frames| (frames, apng_loop_count(buf)))
+                } else {
+                    Ok((Vec::new(), 1))
+                }
+            }),
+            Ok(ImageFormat::WebP) if is_animated_webp(buf) => decode_webp_frames(buf),
+            _ => Ok((Vec::new(), 1)),
+        };
+        let result = result.and_then(|(mut frames, loop_count)| {
+            if frames.len() > 1 {
+                self.set_image(Some(DynamicImage::ImageRgba8(frames[0].image.clone())));
+                self.frames = frames;
+                self.loop_count = loop_count;
+            } else if let Some(frame) = frames.pop() {
+                self.set_image(Some(DynamicImage::ImageRgba8(frame.image)));
+            } else {
+                let image = image::load_from_memory(buf)?;
+                self.exif = read_exif(buf);
+                let orientation = self.exif.as_ref().map_or(0, |exif| exif.orientation);
+                self.set_image(Some(if self.auto_orient {
+                    apply_orientation(image, orientation)
+                } else {
+                    image
+                }));
             }
+            Ok(())
+        });
+        match result {
+            Ok(_) => 1,
             Err(e) => {
+                self.set_image(None);
+                self.frames.clear();
                 let error = e.to_string();
                 self.error.push_str(error.as_str());
                 0
@@ -152,32 +400,212 @@ impl NativeImageAsset {
         }
     }
 
-    pub fn scale(&mut self, x: c_uint, y: c_uint) {
+    /// The raw EXIF orientation of the loaded image, or 0 when it has none.
+    pub fn orientation(&self) -> c_uint {
+        self.exif.as_ref().map_or(0, |exif| exif.orientation as c_uint)
+    }
+
+    pub fn frame_count(&self) -> c_uint {
+        match &self.image {
+            Some(_) => self.frames.len().max(1) as c_uint,
+            _ => 0,
+        }
+    }
+
+    /// Delay of the frame at `index` in milliseconds, 0 for still images.
+    pub fn frame_delay(&self, index: c_uint) -> c_uint {
+        self.frames
+            .get(index as usize)
+            .map_or(0, |frame| frame.delay)
+    }
+
+    fn frame_image(&self, index: c_uint) -> Option<DynamicImage> {
+        if self.frames.is_empty() {
+            return if index == 0 { self.image.clone() } else { None };
+        }
+        self.frames
+            .get(index as usize)
+            .map(|frame| DynamicImage::ImageRgba8(frame.image.clone()))
+    }
+
+    /// Makes the frame at `index` the current image, so drawing the asset or reading
+    /// its bytes uses that frame.
+    pub fn select_frame(&mut self, index: c_uint) -> c_uint {
         if !self.error.is_empty() {
             self.error.clear()
         }
-        match &self.image {
+        match self.frame_image(index) {
             Some(image) => {
-                image.resize(x, y, FilterType::Triangle);
+                self.set_image(Some(image));
+                1
             }
             _ => {
-                self.error.push_str("No Image loaded");
+                self.error.push_str("Frame index out of range");
+                0
             }
         }
     }
 
-    pub fn flip_x(&mut self) {
+    /// RGBA pixels of the frame at `index`.
+    pub fn frame_bytes(&mut self, index: c_uint) -> NativeByteArray {
         if !self.error.is_empty() {
             self.error.clear()
         }
+        match self.frame_image(index) {
+            Some(image) => {
+                let mut pixels = image.to_rgba8().into_raw().into_boxed_slice();
+                let raw_pixels = pixels.as_mut_ptr();
+                let size = pixels.len();
+                mem::forget(pixels);
+                NativeByteArray {
+                    array: raw_pixels,
+                    length: size,
+                }
+            }
+            _ => {
+                self.error.push_str("Frame index out of range");
+                NativeByteArray {
+                    array: null_mut(),
+                    length: 0,
+                }
+            }
+        }
+    }
+
+    // Applies `op` to the current image and, for animations, to every frame so they
+    // keep matching.
+    fn transform(&mut self, op: impl Fn(&DynamicImage) -> DynamicImage) -> c_uint {
         match &self.image {
-            Some(image) => { image.fliph(); }
+            Some(image) => {
+                let image = op(image);
+                for frame in self.frames.iter_mut() {
+                    let buffer = mem::replace(&mut frame.image, RgbaImage::new(0, 0));
+                    frame.image = op(&DynamicImage::ImageRgba8(buffer)).to_rgba8();
+                }
+                self.set_image(Some(image));
+                1
+            }
             _ => {
                 self.error.push_str("No Image loaded");
+                0
             }
         }
     }
 
+    /// Resizes to exactly `x` by `y`.
+    pub fn scale(&mut self, x: c_uint, y: c_uint) -> c_uint {
+        self.resize(x, y, ResizeFilter::Triangle, ResizeMode::Stretch)
+    }
+
+    pub fn resize(&mut self, width: c_uint, height: c_uint, filter: ResizeFilter, mode: ResizeMode) -> c_uint {
+        if !self.error.is_empty() {
+            self.error.clear()
+        }
+        if width == 0 || height == 0 {
+            self.error.push_str("Invalid size");
+            return 0;
+        }
+        let filter = FilterType::from(filter);
+        self.transform(|image| match mode {
+            ResizeMode::Stretch => image.resize_exact(width, height, filter),
+            ResizeMode::Fit => image.resize(width, height, filter),
+            ResizeMode::Fill => image.resize_to_fill(width, height, filter),
+        })
+    }
+
+    pub fn crop(&mut self, x: c_uint, y: c_uint, width: c_uint, height: c_uint) -> c_uint {
+        if !self.error.is_empty() {
+            self.error.clear()
+        }
+        let fits = x.checked_add(width).map_or(false, |right| right <= self.width())
+            && y.checked_add(height).map_or(false, |bottom| bottom <= self.height());
+        if self.image.is_some() && (width == 0 || height == 0 || !fits) {
+            self.error.push_str("Crop rectangle outside of image");
+            return 0;
+        }
+        self.transform(|image| image.crop_imm(x, y, width, height))
+    }
+
+    /// Rotates clockwise by `degrees`. Multiples of 90 are exact; other angles grow the
+    /// image to fit the rotated corners and leave the uncovered area transparent.
+    pub fn rotate(&mut self, degrees: f32) -> c_uint {
+        if !self.error.is_empty() {
+            self.error.clear()
+        }
+        if !degrees.is_finite() {
+            self.error.push_str("Invalid angle");
+            return 0;
+        }
+        let degrees = degrees.rem_euclid(360.0);
+        self.transform(|image| {
+            if degrees == 0.0 {
+                image.clone()
+            } else if degrees == 90.0 {
+                image.rotate90()
+            } else if degrees == 180.0 {
+                image.rotate180()
+            } else if degrees == 270.0 {
+                image.rotate270()
+            } else {
+                DynamicImage::ImageRgba8(rotate_rgba(&image.to_rgba8(), degrees.to_radians()))
+            }
+        })
+    }
+
+    pub fn flip_x(&mut self) -> c_uint {
+        if !self.error.is_empty() {
+            self.error.clear()
+        }
+        self.transform(|image| image.fliph())
+    }
+
+    pub fn grayscale(&mut self) -> c_uint {
+        if !self.error.is_empty() {
+            self.error.clear()
+        }
+        self.transform(|image| image.grayscale())
+    }
+
+    /// Gaussian blur with the given standard deviation.
+    pub fn blur(&mut self, sigma: f32) -> c_uint {
+        if !self.error.is_empty() {
+            self.error.clear()
+        }
+        if !sigma.is_finite() || sigma < 0.0 {
+            self.error.push_str("Invalid blur sigma");
+            return 0;
+        }
+        self.transform(|image| image.blur(sigma))
+    }
+
+    /// Adds `value` to every color channel; negative values darken.
+    pub fn brighten(&mut self, value: i32) -> c_uint {
+        if !self.error.is_empty() {
+            self.error.clear()
+        }
+        self.transform(|image| image.brighten(value))
+    }
+
+    /// Adjusts contrast by `value` percent; negative values reduce it.
+    pub fn contrast(&mut self, value: f32) -> c_uint {
+        if !self.error.is_empty() {
+            self.error.clear()
+        }
+        if !value.is_finite() {
+            self.error.push_str("Invalid contrast");
+            return 0;
+        }
+        self.transform(|image| image.adjust_contrast(value))
+    }
+
+    /// Rotates the hue of every pixel by `degrees`.
+    pub fn hue_rotate(&mut self, degrees: i32) -> c_uint {
+        if !self.error.is_empty() {
+            self.error.clear()
+        }
+        self.transform(|image| image.huerotate(degrees))
+    }
+
     pub fn flip_x_in_place(&mut self) -> c_longlong {
         if !self.error.is_empty() {
             self.error.clear()
@@ -185,6 +613,7 @@ impl NativeImageAsset {
         match &mut self.image {
             Some(image) => {
                 image::imageops::flip_horizontal_in_place(image);
+                self.raster = None;
                 1
             }
             _ => {
@@ -194,16 +623,11 @@ impl NativeImageAsset {
         }
     }
 
-    pub fn flip_y(&mut self) {
+    pub fn flip_y(&mut self) -> c_uint {
         if !self.error.is_empty() {
             self.error.clear()
         }
-        match &self.image {
-            Some(image) => { image.flipv(); }
-            _ => {
-                self.error.push_str("No Image loaded");
-            }
-        }
+        self.transform(|image| image.flipv())
     }
 
     pub fn flip_y_in_place(&mut self) -> c_longlong {
@@ -213,6 +637,7 @@ impl NativeImageAsset {
         match &mut self.image {
             Some(image) => {
                 image::imageops::flip_vertical_in_place(image);
+                self.raster = None;
                 1
             }
             _ => {
@@ -228,7 +653,7 @@ impl NativeImageAsset {
         }
         match &self.image {
             Some(image) => {
-                let image_ref = image.to_rgba();
+                let image_ref = image.to_rgba8();
                 let mut raw = image_ref.into_raw();
                 let mut pixels = raw.into_boxed_slice();
                 let raw_pixels = pixels.as_mut_ptr();
@@ -250,32 +675,119 @@ impl NativeImageAsset {
     }
 
     pub fn save_path(&mut self, path: *const c_char, format: OutputFormat) -> c_uint {
+        self.save_path_with_options(path, format, ImageAssetEncodeOptions::save_path_defaults())
+    }
+
+    pub fn save_path_with_options(
+        &mut self,
+        path: *const c_char,
+        format: OutputFormat,
+        options: ImageAssetEncodeOptions,
+    ) -> c_uint {
         if !self.error.is_empty() {
             self.error.clear()
         }
-        match &self.image {
-            Some(image) => {
-                let format = match format {
-                    OutputFormat::PNG => ImageFormat::Png,
-                    OutputFormat::ICO => ImageFormat::Ico,
-                    OutputFormat::BMP => ImageFormat::Bmp,
-                    OutputFormat::TIFF => ImageFormat::Tiff,
-                    _ => ImageFormat::Jpeg,
-                };
-                let real_path = unsafe { CStr::from_ptr(path) }.to_str().unwrap_or("");
-                let done = match image.save_with_format(real_path, format) {
-                    Ok(_) => 1,
-                    _ => 0,
-                };
-                done
-            }
-            _ => {
-                self.error.push_str("No Image loaded");
+        let real_path = unsafe { CStr::from_ptr(path) }.to_str().unwrap_or("");
+        let result = self
+            .encode_to_vec(format, options)
+            .and_then(|encoded| std::fs::write(real_path, encoded).map_err(|e| e.to_string()));
+        match result {
+            Ok(_) => 1,
+            Err(e) => {
+                self.error.push_str(e.as_str());
                 0
             }
         }
     }
 
+    /// Encodes the current image into memory. Animated assets keep all their frames
+    /// when encoded as GIF; every other format gets the current frame.
+    pub fn encode(&mut self, format: OutputFormat, options: ImageAssetEncodeOptions) -> NativeByteArray {
+        if !self.error.is_empty() {
+            self.error.clear()
+        }
+        match self.encode_to_vec(format, options) {
+            Ok(encoded) => {
+                let mut encoded = encoded.into_boxed_slice();
+                let array = encoded.as_mut_ptr();
+                let length = encoded.len();
+                mem::forget(encoded);
+                NativeByteArray { array, length }
+            }
+            Err(e) => {
+                self.error.push_str(e.as_str());
+                NativeByteArray {
+                    array: null_mut(),
+                    length: 0,
+                }
+            }
+        }
+    }
+
+    fn encode_to_vec(&self, format: OutputFormat, options: ImageAssetEncodeOptions) -> Result<Vec<u8>, String> {
+        let image = match &self.image {
+            Some(image) => image,
+            _ => return Err("No Image loaded".to_string()),
+        };
+        let mut encoded = Vec::new();
+        let result = match format {
+            OutputFormat::JPG => {
+                if options.jpeg_quality < 1 || options.jpeg_quality > 100 {
+                    return Err("JPEG quality must be between 1 and 100".to_string());
+                }
+                // JPEG has no alpha channel.
+                let rgb = image.to_rgb8();
+                JpegEncoder::new_with_quality(&mut encoded, options.jpeg_quality as u8)
+                    .encode(&rgb, rgb.width(), rgb.height(), ColorType::Rgb8)
+            }
+            OutputFormat::PNG => {
+                let rgba = image.to_rgba8();
+                PngEncoder::new_with_quality(
+                    &mut encoded,
+                    PngCompression::from(options.png_compression).into(),
+                    PngFilter::from(options.png_filter).into(),
+                )
+                .encode(&rgba, rgba.width(), rgba.height(), ColorType::Rgba8)
+            }
+            OutputFormat::ICO => {
+                let rgba = image.to_rgba8();
+                IcoEncoder::new(&mut encoded).encode(&rgba, rgba.width(), rgba.height(), ColorType::Rgba8)
+            }
+            OutputFormat::BMP => {
+                let rgba = image.to_rgba8();
+                BmpEncoder::new(&mut encoded).encode(&rgba, rgba.width(), rgba.height(), ColorType::Rgba8)
+            }
+            OutputFormat::TIFF => {
+                let rgba = image.to_rgba8();
+                TiffEncoder::new(Cursor::new(&mut encoded)).encode(&rgba, rgba.width(), rgba.height(), ColorType::Rgba8)
+            }
+            OutputFormat::GIF => self.encode_gif(&mut encoded),
+            OutputFormat::WEBP => return encode_webp_lossless(&image.to_rgba8()),
+        };
+        result.map(|_| encoded).map_err(|e| e.to_string())
+    }
+
+    fn encode_gif(&self, encoded: &mut Vec<u8>) -> ImageResult<()> {
+        let mut encoder = GifEncoder::new(encoded);
+        if self.frames.is_empty() {
+            let rgba = match &self.image {
+                Some(image) => image.to_rgba8(),
+                _ => return Ok(()),
+            };
+            return encoder.encode(&rgba, rgba.width(), rgba.height(), ColorType::Rgba8);
+        }
+        // GIF counts repeats after the first play, and leaving the extension out is the
+        // only way to play once.
+        match self.loop_count {
+            0 => encoder.set_repeat(Repeat::Infinite)?,
+            1 => {}
+            plays => encoder.set_repeat(Repeat::Finite((plays - 1).min(u16::MAX as c_uint) as u16))?,
+        }
+        encoder.encode_frames(self.frames.iter().map(|frame| {
+            Frame::from_parts(frame.image.clone(), 0, 0, Delay::from_numer_denom_ms(frame.delay, 1))
+        }))
+    }
+
     pub fn free_image_data(data: NativeByteArray) {
         unsafe {
             Box::from_raw(std::slice::from_raw_parts_mut(data.array, data.length));
@@ -283,6 +795,153 @@ impl NativeImageAsset {
     }
 }
 
+// Rotates clockwise around the center into a canvas big enough for the rotated
+// corners, sampling the source bilinearly in premultiplied space so the transparent
+// border doesn't darken the edges.
+fn rotate_rgba(image: &RgbaImage, radians: f32) -> RgbaImage {
+    let (sin, cos) = radians.sin_cos();
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let out_width = (width * cos.abs() + height * sin.abs()).round().max(1.0) as u32;
+    let out_height = (width * sin.abs() + height * cos.abs()).round().max(1.0) as u32;
+    let mut output = RgbaImage::new(out_width, out_height);
+    let (center_x, center_y) = (width / 2.0, height / 2.0);
+    let (out_center_x, out_center_y) = (out_width as f32 / 2.0, out_height as f32 / 2.0);
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        let dx = x as f32 + 0.5 - out_center_x;
+        let dy = y as f32 + 0.5 - out_center_y;
+        let source_x = dx * cos + dy * sin + center_x - 0.5;
+        let source_y = -dx * sin + dy * cos + center_y - 0.5;
+        let (x0, y0) = (source_x.floor(), source_y.floor());
+        let (fx, fy) = (source_x - x0, source_y - y0);
+        let mut sum = [0f32; 4];
+        for (offset_x, offset_y, weight) in [
+            (0.0, 0.0, (1.0 - fx) * (1.0 - fy)),
+            (1.0, 0.0, fx * (1.0 - fy)),
+            (0.0, 1.0, (1.0 - fx) * fy),
+            (1.0, 1.0, fx * fy),
+        ]
+        .iter()
+        {
+            let (sample_x, sample_y) = (x0 + offset_x, y0 + offset_y);
+            if sample_x < 0.0 || sample_y < 0.0 || sample_x >= width || sample_y >= height {
+                continue;
+            }
+            let source = image.get_pixel(sample_x as u32, sample_y as u32);
+            let alpha = source[3] as f32 * weight;
+            sum[0] += source[0] as f32 * alpha;
+            sum[1] += source[1] as f32 * alpha;
+            sum[2] += source[2] as f32 * alpha;
+            sum[3] += alpha;
+        }
+        if sum[3] > 0.0 {
+            pixel[0] = (sum[0] / sum[3]).round().min(255.0) as u8;
+            pixel[1] = (sum[1] / sum[3]).round().min(255.0) as u8;
+            pixel[2] = (sum[2] / sum[3]).round().min(255.0) as u8;
+            pixel[3] = sum[3].round().min(255.0) as u8;
+        }
+    }
+    output
+}
+
+fn decode_frames<'a>(decoder: impl AnimationDecoder<'a>) -> ImageResult<Vec<ImageAssetFrame>> {
+    decoder
+        .into_frames()
+        .map(|frame| {
+            frame.map(|frame| {
+                let (numerator, denominator) = frame.delay().numer_denom_ms();
+                let delay = if denominator == 0 {
+                    0
+                } else {
+                    (numerator as f64 / denominator as f64).round() as c_uint
+                };
+                ImageAssetFrame {
+                    image: frame.into_buffer(),
+                    delay,
+                }
+            })
+        })
+        .collect()
+}
+
+// The loop count lives in the NETSCAPE2.0 application extension, which the decoder
+// skips. Its value is the number of repeats after the first play, 0 meaning forever;
+// without the extension the animation plays once.
+fn gif_loop_count(buf: &[u8]) -> c_uint {
+    let marker = b"NETSCAPE2.0";
+    let position = buf
+        .windows(marker.len())
+        .position(|window| window == marker);
+    match position.and_then(|position| buf.get(position + marker.len()..position + marker.len() + 4)) {
+        Some([3, 1, low, high]) => {
+            let repeats = u16::from_le_bytes([*low, *high]) as c_uint;
+            if repeats == 0 { 0 } else { repeats + 1 }
+        }
+        _ => 1,
+    }
+}
+
+// The animation flag of the VP8X header, which always comes first in the files that
+// have one.
+fn is_animated_webp(buf: &[u8]) -> bool {
+    buf.len() > 20 && &buf[12..16] == b"VP8X" && buf[20] & 0x02 != 0
+}
+
+// image's own WebP decoder only handles still lossy images, so animations go through
+// image-webp, which composites each frame onto the canvas and reads the loop count
+// from the ANIM chunk (0 meaning forever, as here).
+fn decode_webp_frames(buf: &[u8]) -> ImageResult<(Vec<ImageAssetFrame>, c_uint)> {
+    let webp_error = |e: image_webp::DecodingError| {
+        ImageError::Decoding(DecodingError::new(ImageFormat::WebP.into(), e))
+    };
+    let mut decoder = WebPDecoder::new(Cursor::new(buf)).map_err(webp_error)?;
+    let (width, height) = decoder.dimensions();
+    let size = decoder
+        .output_buffer_size()
+        .ok_or_else(|| webp_error(image_webp::DecodingError::ImageTooLarge))?;
+    let loop_count = match decoder.loop_count() {
+        LoopCount::Forever => 0,
+        LoopCount::Times(times) => times.get() as c_uint,
+    };
+    let mut frames = Vec::with_capacity(decoder.num_frames() as usize);
+    for _ in 0..decoder.num_frames() {
+        let mut buffer = vec![0; size];
+        let delay = decoder.read_frame(&mut buffer).map_err(webp_error)?;
+        let image = if decoder.has_alpha() {
+            RgbaImage::from_raw(width, height, buffer)
+        } else {
+            RgbImage::from_raw(width, height, buffer).map(|image| image.convert())
+        };
+        match image {
+            Some(image) => frames.push(ImageAssetFrame { image, delay }),
+            None => return Err(webp_error(image_webp::DecodingError::ImageTooLarge)),
+        }
+    }
+    Ok((frames, loop_count))
+}
+
+// num_plays from the acTL chunk, which already counts the first play.
+fn apng_loop_count(buf: &[u8]) -> c_uint {
+    let mut offset = 8;
+    while offset + 8 <= buf.len() {
+        let length = u32::from_be_bytes([buf[offset], buf[offset + 1], buf[offset + 2], buf[offset + 3]]) as usize;
+        let kind = &buf[offset + 4..offset + 8];
+        if kind == b"acTL" {
+            return match buf.get(offset + 12..offset + 16) {
+                Some(plays) => u32::from_be_bytes([plays[0], plays[1], plays[2], plays[3]]),
+                None => 1,
+            };
+        }
+        if kind == b"IDAT" {
+            break;
+        }
+        offset = match offset.checked_add(12).and_then(|offset| offset.checked_add(length)) {
+            Some(offset) => offset,
+            None => break,
+        };
+    }
+    1
+}
+
 pub(crate) fn create_image_asset() -> c_longlong {
     Box::into_raw(Box::new(NativeImageAsset::new())) as *mut _ as i64
 }
@@ -305,6 +964,33 @@ pub(crate) fn image_asset_load_from_raw(
     result
 }
 
+pub(crate) fn image_asset_load_from_data_url(asset: c_longlong, url: *const c_char) -> c_uint {
+    if asset == 0 {
+        return 0;
+    }
+    let mut native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.load_from_data_url(url);
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_load_svg(
+    asset: c_longlong,
+    svg: *const u8,
+    size: size_t,
+    width: c_uint,
+    height: c_uint,
+) -> c_uint {
+    if asset == 0 || svg.is_null() {
+        return 0;
+    }
+    let mut native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let svg = unsafe { std::slice::from_raw_parts(svg, size) };
+    let result = native_asset.load_svg(svg, width, height);
+    Box::into_raw(native_asset);
+    result
+}
+
 pub(crate) fn image_asset_load_from_slice_i8(asset: c_longlong, array: &mut [i8]) -> c_uint {
     let mut native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
     let result = native_asset.load_from_bytes_int(array);
@@ -412,4 +1098,431 @@ pub(crate) fn image_asset_save_path(
 
 pub(crate) fn image_asset_release(asset: c_longlong) {
     let _: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
-}
\ No newline at end of file
+}
+
+pub(crate) fn image_asset_frame_count(asset: c_longlong) -> c_uint {
+    if asset == 0 {
+        return 0;
+    }
+    let native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.frame_count();
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_loop_count(asset: c_longlong) -> c_uint {
+    if asset == 0 {
+        return 0;
+    }
+    let native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.loop_count;
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_frame_delay(asset: c_longlong, index: c_uint) -> c_uint {
+    if asset == 0 {
+        return 0;
+    }
+    let native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.frame_delay(index);
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_get_frame_bytes(asset: c_longlong, index: c_uint) -> NativeByteArray {
+    if asset == 0 {
+        return NativeByteArray {
+            array: null_mut(),
+            length: 0,
+        };
+    }
+    let mut native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.frame_bytes(index);
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_select_frame(asset: c_longlong, index: c_uint) -> c_uint {
+    if asset == 0 {
+        return 0;
+    }
+    let mut native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.select_frame(index);
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_set_auto_orient(asset: c_longlong, auto_orient: bool) -> c_longlong {
+    if asset == 0 {
+        return 0;
+    }
+    let mut native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    native_asset.auto_orient = auto_orient;
+    Box::into_raw(native_asset) as *mut _ as i64
+}
+
+pub(crate) fn image_asset_get_orientation(asset: c_longlong) -> c_uint {
+    if asset == 0 {
+        return 0;
+    }
+    let native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.orientation();
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_get_exif_metadata(asset: c_longlong) -> Option<ExifMetadata> {
+    if asset == 0 {
+        return None;
+    }
+    let native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.exif.clone();
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_get_exif(asset: c_longlong) -> ImageAssetExif {
+    ImageAssetExif::new(image_asset_get_exif_metadata(asset).as_ref())
+}
+
+pub(crate) fn image_asset_free_exif(exif: ImageAssetExif) {
+    exif.free();
+}
+
+pub(crate) fn image_asset_resize(
+    asset: c_longlong,
+    width: c_uint,
+    height: c_uint,
+    filter: c_uint,
+    mode: c_uint,
+) -> c_uint {
+    if asset == 0 {
+        return 0;
+    }
+    let mut native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.resize(width, height, ResizeFilter::from(filter), ResizeMode::from(mode));
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_crop(
+    asset: c_longlong,
+    x: c_uint,
+    y: c_uint,
+    width: c_uint,
+    height: c_uint,
+) -> c_uint {
+    if asset == 0 {
+        return 0;
+    }
+    let mut native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.crop(x, y, width, height);
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_rotate(asset: c_longlong, degrees: f32) -> c_uint {
+    if asset == 0 {
+        return 0;
+    }
+    let mut native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.rotate(degrees);
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_grayscale(asset: c_longlong) -> c_uint {
+    if asset == 0 {
+        return 0;
+    }
+    let mut native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.grayscale();
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_blur(asset: c_longlong, sigma: f32) -> c_uint {
+    if asset == 0 {
+        return 0;
+    }
+    let mut native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.blur(sigma);
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_brighten(asset: c_longlong, value: i32) -> c_uint {
+    if asset == 0 {
+        return 0;
+    }
+    let mut native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.brighten(value);
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_contrast(asset: c_longlong, value: f32) -> c_uint {
+    if asset == 0 {
+        return 0;
+    }
+    let mut native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.contrast(value);
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_hue_rotate(asset: c_longlong, degrees: i32) -> c_uint {
+    if asset == 0 {
+        return 0;
+    }
+    let mut native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.hue_rotate(degrees);
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_save_path_with_options(
+    asset: c_longlong,
+    path: *const c_char,
+    format: c_uint,
+    options: ImageAssetEncodeOptions,
+) -> c_uint {
+    if asset == 0 {
+        return 0;
+    }
+    let mut native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.save_path_with_options(path, OutputFormat::from(format), options);
+    Box::into_raw(native_asset);
+    result
+}
+
+pub(crate) fn image_asset_encode(
+    asset: c_longlong,
+    format: c_uint,
+    options: ImageAssetEncodeOptions,
+) -> NativeByteArray {
+    if asset == 0 {
+        return NativeByteArray {
+            array: null_mut(),
+            length: 0,
+        };
+    }
+    let mut native_asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let result = native_asset.encode(OutputFormat::from(format), options);
+    Box::into_raw(native_asset);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    fn chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = kind.to_vec();
+        chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn u24(value: u32) -> [u8; 3] {
+        let bytes = value.to_le_bytes();
+        [bytes[0], bytes[1], bytes[2]]
+    }
+
+    // Each frame is a lossless still from the encoder with its VP8L chunk moved into
+    // an ANMF chunk covering the whole canvas.
+    fn animated_webp(frames: &[(RgbaImage, u32)], loop_count: u16) -> Vec<u8> {
+        let (width, height) = frames[0].0.dimensions();
+        let mut header = vec![0x12, 0, 0, 0];
+        header.extend_from_slice(&u24(width - 1));
+        header.extend_from_slice(&u24(height - 1));
+        let mut animation = vec![0; 4];
+        animation.extend_from_slice(&loop_count.to_le_bytes());
+        let mut body = b"WEBP".to_vec();
+        body.extend(chunk(b"VP8X", &header));
+        body.extend(chunk(b"ANIM", &animation));
+        for (image, delay) in frames {
+            let still = encode_webp_lossless(image).unwrap();
+            let mut frame = vec![0; 6];
+            frame.extend_from_slice(&u24(width - 1));
+            frame.extend_from_slice(&u24(height - 1));
+            frame.extend_from_slice(&u24(*delay));
+            frame.push(0x02);
+            frame.extend_from_slice(&still[12..]);
+            body.extend(chunk(b"ANMF", &frame));
+        }
+        let mut webp = b"RIFF".to_vec();
+        webp.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        webp.extend(body);
+        webp
+    }
+
+    #[test]
+    fn decodes_animated_webp_frames() {
+        let red = RgbaImage::from_pixel(3, 2, Rgba([255, 0, 0, 255]));
+        let mut blue = RgbaImage::from_pixel(3, 2, Rgba([0, 0, 255, 128]));
+        blue.put_pixel(1, 1, Rgba([0, 255, 0, 255]));
+        let webp = animated_webp(&[(red.clone(), 100), (blue.clone(), 40)], 3);
+        let mut asset = NativeImageAsset::new();
+        assert_eq!(asset.load_from_bytes(&webp), 1);
+        assert_eq!(asset.loop_count, 3);
+        assert_eq!(asset.frames.len(), 2);
+        assert_eq!(asset.frames[0].delay, 100);
+        assert_eq!(asset.frames[1].delay, 40);
+        assert_eq!(asset.frames[0].image, red);
+        assert_eq!(asset.frames[1].image, blue);
+        assert_eq!(asset.image.as_ref().and_then(|image| image.as_rgba8()), Some(&red));
+    }
+
+    #[test]
+    fn animated_webp_loops_forever_with_a_zero_count() {
+        let frame = RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 255]));
+        let webp = animated_webp(&[(frame.clone(), 10), (frame, 10)], 0);
+        let mut asset = NativeImageAsset::new();
+        assert_eq!(asset.load_from_bytes(&webp), 1);
+        assert_eq!(asset.loop_count, 0);
+    }
+    // Each pixel records its own coordinates so moves can be traced.
+    fn marked(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| Rgba([x as u8 * 10, y as u8 * 10, 0, 255]))
+    }
+
+    fn asset_with(image: RgbaImage, frames: usize) -> NativeImageAsset {
+        let mut asset = NativeImageAsset::new();
+        for _ in 0..frames {
+            asset.frames.push(ImageAssetFrame { image: image.clone(), delay: 10 });
+        }
+        asset.set_image(Some(DynamicImage::ImageRgba8(image)));
+        asset
+    }
+
+    fn pixel(asset: &NativeImageAsset, x: u32, y: u32) -> [u8; 4] {
+        asset.image.as_ref().unwrap().to_rgba8().get_pixel(x, y).0
+    }
+
+    fn frame_sizes(asset: &NativeImageAsset) -> Vec<(u32, u32)> {
+        asset.frames.iter().map(|frame| frame.image.dimensions()).collect()
+    }
+
+    #[test]
+    fn crop_rejects_rectangles_outside_the_image() {
+        let mut asset = asset_with(marked(4, 3), 0);
+        let outside = [(0, 0, 5, 1), (3, 0, 2, 1), (0, 2, 1, 2), (0, 0, 0, 1), (0, 0, 1, 0), (u32::MAX, 0, 2, 1)];
+        for (x, y, width, height) in outside.iter() {
+            assert_eq!(asset.crop(*x, *y, *width, *height), 0);
+            assert_eq!(asset.error, "Crop rectangle outside of image");
+            assert_eq!((asset.width(), asset.height()), (4, 3));
+        }
+        assert_eq!(asset.crop(0, 0, 4, 3), 1);
+        assert!(asset.error.is_empty());
+
+        let mut empty = NativeImageAsset::new();
+        assert_eq!(empty.crop(0, 0, 1, 1), 0);
+        assert_eq!(empty.error, "No Image loaded");
+    }
+
+    #[test]
+    fn crop_keeps_the_rectangle() {
+        let mut asset = asset_with(marked(4, 3), 0);
+        assert_eq!(asset.crop(1, 1, 3, 2), 1);
+        assert_eq!((asset.width(), asset.height()), (3, 2));
+        assert_eq!(pixel(&asset, 0, 0), [10, 10, 0, 255]);
+        assert_eq!(pixel(&asset, 2, 1), [30, 20, 0, 255]);
+    }
+
+    #[test]
+    fn rotates_right_angles_exactly() {
+        let mut asset = asset_with(marked(4, 2), 0);
+        assert_eq!(asset.rotate(90.0), 1);
+        assert_eq!((asset.width(), asset.height()), (2, 4));
+        // Clockwise, so the bottom left corner comes to the top left.
+        assert_eq!(pixel(&asset, 0, 0), [0, 10, 0, 255]);
+        assert_eq!(pixel(&asset, 1, 3), [30, 0, 0, 255]);
+
+        let mut asset = asset_with(marked(4, 2), 0);
+        assert_eq!(asset.rotate(180.0), 1);
+        assert_eq!((asset.width(), asset.height()), (4, 2));
+        assert_eq!(pixel(&asset, 0, 0), [30, 10, 0, 255]);
+
+        let mut asset = asset_with(marked(4, 2), 0);
+        assert_eq!(asset.rotate(270.0), 1);
+        assert_eq!((asset.width(), asset.height()), (2, 4));
+        assert_eq!(pixel(&asset, 0, 0), [30, 0, 0, 255]);
+
+        let mut asset = asset_with(marked(4, 2), 0);
+        assert_eq!(asset.rotate(-90.0), 1);
+        assert_eq!(pixel(&asset, 0, 0), [30, 0, 0, 255]);
+
+        let mut asset = asset_with(marked(4, 2), 0);
+        assert_eq!(asset.rotate(720.0), 1);
+        assert_eq!(asset.image.as_ref().unwrap().to_rgba8(), marked(4, 2));
+
+        assert_eq!(asset.rotate(std::f32::NAN), 0);
+        assert_eq!(asset.error, "Invalid angle");
+    }
+
+    #[test]
+    fn rotates_other_angles_into_a_larger_canvas() {
+        let mut asset = asset_with(RgbaImage::from_pixel(10, 4, Rgba([255, 0, 0, 255])), 0);
+        assert_eq!(asset.rotate(30.0), 1);
+        // 10cos30 + 4sin30 by 10sin30 + 4cos30, rounded.
+        assert_eq!((asset.width(), asset.height()), (11, 8));
+        for (x, y) in [(0, 0), (10, 0), (0, 7), (10, 7)].iter() {
+            assert_eq!(pixel(&asset, *x, *y)[3], 0, "corner {}, {}", x, y);
+        }
+        assert_eq!(pixel(&asset, 5, 4), [255, 0, 0, 255]);
+
+        let rotated = rotate_rgba(&RgbaImage::from_pixel(4, 4, Rgba([0, 0, 255, 255])), 45f32.to_radians());
+        assert_eq!(rotated.dimensions(), (6, 6));
+        assert_eq!(rotated.get_pixel(0, 0)[3], 0);
+        // Edge pixels keep their color rather than blending towards black.
+        assert!(rotated
+            .pixels()
+            .filter(|pixel| pixel[3] > 0)
+            .all(|pixel| pixel[2] == 255 && pixel[0] == 0));
+    }
+
+    #[test]
+    fn resize_modes_set_the_dimensions() {
+        let mut asset = asset_with(marked(8, 4), 0);
+        assert_eq!(asset.resize(4, 4, ResizeFilter::Nearest, ResizeMode::Fit), 1);
+        assert_eq!((asset.width(), asset.height()), (4, 2));
+
+        let mut asset = asset_with(marked(8, 4), 0);
+        assert_eq!(asset.resize(4, 4, ResizeFilter::Nearest, ResizeMode::Fill), 1);
+        assert_eq!((asset.width(), asset.height()), (4, 4));
+
+        let mut asset = asset_with(marked(8, 4), 0);
+        assert_eq!(asset.resize(3, 5, ResizeFilter::Triangle, ResizeMode::Stretch), 1);
+        assert_eq!((asset.width(), asset.height()), (3, 5));
+
+        assert_eq!(asset.resize(0, 5, ResizeFilter::Triangle, ResizeMode::Stretch), 0);
+        assert_eq!(asset.error, "Invalid size");
+        assert_eq!((asset.width(), asset.height()), (3, 5));
+    }
+
+    #[test]
+    fn transforms_apply_to_every_frame() {
+        let mut asset = asset_with(marked(4, 2), 2);
+        assert_eq!(asset.rotate(90.0), 1);
+        assert_eq!(frame_sizes(&asset), vec![(2, 4), (2, 4)]);
+        assert_eq!(asset.frames[1].image.get_pixel(0, 0).0, [0, 10, 0, 255]);
+
+        assert_eq!(asset.crop(0, 1, 2, 2), 1);
+        assert_eq!(frame_sizes(&asset), vec![(2, 2), (2, 2)]);
+
+        assert_eq!(asset.resize(8, 8, ResizeFilter::Nearest, ResizeMode::Stretch), 1);
+        assert_eq!(frame_sizes(&asset), vec![(8, 8), (8, 8)]);
+
+        assert_eq!(asset.rotate(30.0), 1);
+        let size = (asset.width(), asset.height());
+        assert_eq!(frame_sizes(&asset), vec![size, size]);
+        assert_eq!(asset.frames.iter().map(|frame| frame.delay).collect::<Vec<_>>(), vec![10, 10]);
+    }
+}
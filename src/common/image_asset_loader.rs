@@ -0,0 +1,395 @@
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_longlong, c_void};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use lazy_static::lazy_static;
+use libc::size_t;
+
+use crate::common::NativeImageAsset;
+
+const DECODE_THREADS: usize = 2;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Runs with the load handle and final status once a load stops being pending. It's
+/// called on a decoder thread, or on the thread that started the load when no decoder
+/// thread could be started.
+pub(crate) type ImageAssetLoadCompletion = Box<dyn FnOnce(c_longlong, ImageAssetLoadStatus) + Send>;
+
+pub type ImageAssetLoadCallback =
+    extern "C" fn(load: c_longlong, status: ImageAssetLoadStatus, user_data: *mut c_void);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ImageAssetLoadStatus {
+    Pending = 0,
+    Loaded = 1,
+    Error = 2,
+    Cancelled = 3,
+}
+
+/// Decoder threads fed from one queue. When none could be spawned, or they have all
+/// exited, jobs run on the thread that queues them instead of being dropped.
+struct DecodePool {
+    sender: Mutex<Sender<Job>>,
+    threads: usize,
+}
+
+impl DecodePool {
+    fn new(threads: usize) -> Self {
+        let (sender, receiver) = channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let mut spawned = 0;
+        for index in 0..threads {
+            let receiver: Arc<Mutex<Receiver<Job>>> = receiver.clone();
+            let thread = thread::Builder::new()
+                .name(format!("canvas-image-decode-{}", index))
+                .spawn(move || loop {
+                    let job = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        _ => break,
+                    };
+                    match job {
+                        Ok(job) => job(),
+                        _ => break,
+                    }
+                });
+            if thread.is_ok() {
+                spawned += 1;
+            }
+        }
+        Self {
+            sender: Mutex::new(sender),
+            threads: spawned,
+        }
+    }
+
+    fn execute(&self, job: Job) {
+        if self.threads == 0 {
+            job();
+            return;
+        }
+        // Sending fails once every thread has exited and dropped the receiver, and
+        // hands the job back.
+        let job = match self.sender.lock() {
+            Ok(sender) => match sender.send(job) {
+                Ok(()) => return,
+                Err(error) => error.0,
+            },
+            Err(_) => job,
+        };
+        job()
+    }
+}
+
+lazy_static! {
+    // Started by the first async load and kept for the life of the process.
+    static ref DECODE_POOL: DecodePool = DecodePool::new(DECODE_THREADS);
+}
+
+pub(crate) enum ImageAssetSource {
+    Path(CString),
+    Bytes(Vec<u8>),
+}
+
+struct ImageAssetLoadState {
+    status: ImageAssetLoadStatus,
+    asset: Option<Box<NativeImageAsset>>,
+}
+
+/// A decode running on the shared decoder threads. The finished asset is moved out
+/// with `take`, after which it belongs to the caller like any other asset handle.
+pub struct ImageAssetLoad {
+    state: Mutex<ImageAssetLoadState>,
+    cancelled: AtomicBool,
+}
+
+impl ImageAssetLoad {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(ImageAssetLoadState {
+                status: ImageAssetLoadStatus::Pending,
+                asset: None,
+            }),
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    fn run(&self, source: ImageAssetSource, auto_orient: bool) -> ImageAssetLoadStatus {
+        // Loads cancelled while queued never start decoding.
+        if self.cancelled.load(Ordering::SeqCst) {
+            return self.finish(ImageAssetLoadStatus::Cancelled, None);
+        }
+        let mut asset = NativeImageAsset::new();
+        asset.auto_orient = auto_orient;
+        let read;
+        let bytes: &[u8] = match &source {
+            ImageAssetSource::Path(path) => {
+                read = std::fs::read(path.to_str().unwrap_or(""));
+                match &read {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        asset.error.push_str(e.to_string().as_str());
+                        return self.finish(ImageAssetLoadStatus::Error, Some(Box::new(asset)));
+                    }
+                }
+            }
+            ImageAssetSource::Bytes(bytes) => bytes,
+        };
+        // Files are read before decoding starts, so a cancel that lands while reading
+        // skips the decode too.
+        if self.cancelled.load(Ordering::SeqCst) {
+            return self.finish(ImageAssetLoadStatus::Cancelled, None);
+        }
+        let decoded = catch_unwind(AssertUnwindSafe(|| asset.load_from_bytes(bytes)));
+        let status = match decoded {
+            Ok(1) => ImageAssetLoadStatus::Loaded,
+            Ok(_) => ImageAssetLoadStatus::Error,
+            Err(_) => {
+                asset.image = None;
+                asset.error.push_str("Failed to decode image");
+                ImageAssetLoadStatus::Error
+            }
+        };
+        self.finish(status, Some(Box::new(asset)))
+    }
+
+    fn finish(&self, status: ImageAssetLoadStatus, asset: Option<Box<NativeImageAsset>>) -> ImageAssetLoadStatus {
+        let mut state = self.state.lock().unwrap();
+        // Checked under the lock so a cancel either lands before this and wins, or
+        // finds the load already finished.
+        if self.cancelled.load(Ordering::SeqCst) {
+            state.status = ImageAssetLoadStatus::Cancelled;
+            state.asset = None;
+        } else {
+            state.status = status;
+            state.asset = asset;
+        }
+        state.status
+    }
+
+    pub fn status(&self) -> ImageAssetLoadStatus {
+        self.state.lock().unwrap().status
+    }
+
+    /// Returns false when the load had already finished. A load that hasn't started
+    /// decoding never does; a decode already under way isn't interrupted, it runs to
+    /// the end and its result is dropped.
+    pub fn cancel(&self) -> bool {
+        self.cancelled.store(true, Ordering::SeqCst);
+        let mut state = self.state.lock().unwrap();
+        if state.status != ImageAssetLoadStatus::Pending {
+            return false;
+        }
+        state.status = ImageAssetLoadStatus::Cancelled;
+        true
+    }
+
+    /// Moves the asset out once the load is done. Failed loads hand back an asset too,
+    /// with its error set.
+    pub fn take(&self) -> Option<Box<NativeImageAsset>> {
+        let mut state = self.state.lock().unwrap();
+        match state.status {
+            ImageAssetLoadStatus::Loaded | ImageAssetLoadStatus::Error => state.asset.take(),
+            _ => None,
+        }
+    }
+}
+
+/// Queues a decode and returns its handle. The handle stays valid until
+/// `image_asset_load_release`; when `on_complete` is given, it also stays valid for
+/// the duration of that call even if released in the meantime.
+pub(crate) fn image_asset_load_async(
+    source: ImageAssetSource,
+    auto_orient: bool,
+    on_complete: Option<ImageAssetLoadCompletion>,
+) -> c_longlong {
+    let load = Arc::new(ImageAssetLoad::new());
+    let worker_load = load.clone();
+    let handle = Arc::into_raw(load) as c_longlong;
+    DECODE_POOL.execute(Box::new(move || {
+        let status = worker_load.run(source, auto_orient);
+        if let Some(on_complete) = on_complete {
+            on_complete(handle, status);
+        }
+    }));
+    handle
+}
+
+// Raw pointers aren't Send; the caller owns whatever user_data points to.
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+
+fn callback_completion(
+    callback: Option<ImageAssetLoadCallback>,
+    user_data: *mut c_void,
+) -> Option<ImageAssetLoadCompletion> {
+    let callback = callback?;
+    let user_data = UserData(user_data);
+    Some(Box::new(move |load, status| {
+        let user_data = user_data;
+        callback(load, status, user_data.0)
+    }))
+}
+
+pub(crate) fn image_asset_load_from_path_async(
+    path: *const c_char,
+    auto_orient: bool,
+    callback: Option<ImageAssetLoadCallback>,
+    user_data: *mut c_void,
+) -> c_longlong {
+    if path.is_null() {
+        return 0;
+    }
+    let path = unsafe { CStr::from_ptr(path) }.to_owned();
+    image_asset_load_async(
+        ImageAssetSource::Path(path),
+        auto_orient,
+        callback_completion(callback, user_data),
+    )
+}
+
+/// Copies the encoded bytes, so the buffer can be freed as soon as this returns.
+pub(crate) fn image_asset_load_from_raw_async(
+    array: *const u8,
+    size: size_t,
+    auto_orient: bool,
+    callback: Option<ImageAssetLoadCallback>,
+    user_data: *mut c_void,
+) -> c_longlong {
+    if array.is_null() {
+        return 0;
+    }
+    let bytes = unsafe { std::slice::from_raw_parts(array, size) }.to_vec();
+    image_asset_load_async(
+        ImageAssetSource::Bytes(bytes),
+        auto_orient,
+        callback_completion(callback, user_data),
+    )
+}
+
+fn with_load<T>(load: c_longlong, default: T, f: impl FnOnce(&ImageAssetLoad) -> T) -> T {
+    if load == 0 {
+        return default;
+    }
+    let load: &ImageAssetLoad = unsafe { &*(load as *const ImageAssetLoad) };
+    f(load)
+}
+
+pub(crate) fn image_asset_load_status(load: c_longlong) -> ImageAssetLoadStatus {
+    with_load(load, ImageAssetLoadStatus::Error, |load| load.status())
+}
+
+pub(crate) fn image_asset_load_cancel(load: c_longlong) -> bool {
+    with_load(load, false, |load| load.cancel())
+}
+
+/// Returns the decoded asset handle, or 0 while pending, after cancelling or once it
+/// has been taken.
+pub(crate) fn image_asset_load_take(load: c_longlong) -> c_longlong {
+    with_load(load, 0, |load| match load.take() {
+        Some(asset) => Box::into_raw(asset) as *mut _ as i64,
+        None => 0,
+    })
+}
+
+pub(crate) fn image_asset_load_release(load: c_longlong) {
+    if load == 0 {
+        return;
+    }
+    let _: Arc<ImageAssetLoad> = unsafe { Arc::from_raw(load as *const ImageAssetLoad) };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::sync_channel;
+    use std::time::Duration;
+
+    use image::ColorType;
+    use image::png::PngEncoder;
+
+    use super::*;
+
+    fn png() -> Vec<u8> {
+        let mut encoded = Vec::new();
+        PngEncoder::new(&mut encoded)
+            .encode(&[255u8; 2 * 2 * 4], 2, 2, ColorType::Rgba8)
+            .unwrap();
+        encoded
+    }
+
+    #[test]
+    fn cancel_before_run_skips_the_decode() {
+        let load = ImageAssetLoad::new();
+        assert!(load.cancel());
+        assert_eq!(load.status(), ImageAssetLoadStatus::Cancelled);
+        assert_eq!(load.run(ImageAssetSource::Bytes(png()), false), ImageAssetLoadStatus::Cancelled);
+        assert_eq!(load.status(), ImageAssetLoadStatus::Cancelled);
+        assert!(load.take().is_none());
+    }
+
+    #[test]
+    fn cancel_after_finish_keeps_the_result() {
+        let load = ImageAssetLoad::new();
+        assert_eq!(load.run(ImageAssetSource::Bytes(png()), false), ImageAssetLoadStatus::Loaded);
+        assert!(!load.cancel());
+        assert_eq!(load.status(), ImageAssetLoadStatus::Loaded);
+        let asset = load.take().unwrap();
+        assert_eq!((asset.width(), asset.height()), (2, 2));
+    }
+
+    #[test]
+    fn take_moves_the_asset_out_once() {
+        let load = ImageAssetLoad::new();
+        assert!(load.take().is_none());
+        load.run(ImageAssetSource::Bytes(png()), false);
+        assert!(load.take().is_some());
+        assert!(load.take().is_none());
+        assert_eq!(load.status(), ImageAssetLoadStatus::Loaded);
+    }
+
+    #[test]
+    fn failed_loads_hand_back_an_asset_with_the_error() {
+        let load = ImageAssetLoad::new();
+        assert_eq!(load.run(ImageAssetSource::Bytes(vec![1, 2, 3]), false), ImageAssetLoadStatus::Error);
+        assert!(!load.take().unwrap().error.is_empty());
+
+        let load = ImageAssetLoad::new();
+        let path = CString::new("/nonexistent/canvas-image.png").unwrap();
+        assert_eq!(load.run(ImageAssetSource::Path(path), false), ImageAssetLoadStatus::Error);
+        assert!(!load.take().unwrap().error.is_empty());
+    }
+
+    #[test]
+    fn completion_receives_the_handle_and_final_status() {
+        for (bytes, expected) in vec![(png(), ImageAssetLoadStatus::Loaded), (vec![0], ImageAssetLoadStatus::Error)] {
+            let (sender, receiver) = sync_channel(1);
+            let handle = image_asset_load_async(
+                ImageAssetSource::Bytes(bytes),
+                false,
+                Some(Box::new(move |load, status| sender.send((load, status)).unwrap())),
+            );
+            let (load, status) = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+            assert_eq!((load, status), (handle, expected));
+            assert_eq!(image_asset_load_status(handle), expected);
+            let asset = image_asset_load_take(handle);
+            assert_ne!(asset, 0);
+            let _: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+            image_asset_load_release(handle);
+        }
+    }
+
+    #[test]
+    fn pool_without_threads_runs_jobs_inline() {
+        let pool = DecodePool::new(0);
+        let ran = Arc::new(AtomicBool::new(false));
+        let job_ran = ran.clone();
+        pool.execute(Box::new(move || job_ran.store(true, Ordering::SeqCst)));
+        assert!(ran.load(Ordering::SeqCst));
+    }
+}
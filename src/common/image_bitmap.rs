@@ -0,0 +1,599 @@
+use std::mem;
+use std::ptr::null_mut;
+
+use image::GenericImageView;
+use libc::{c_float, c_int, c_longlong, size_t};
+use skia_safe::{AlphaType, Color, ColorSpace, ColorType, Data, FilterQuality, IPoint, ISize, Image, ImageInfo, Paint, Point, Rect, Surface, BlendMode, canvas::SrcRectConstraint, image::CachingHint};
+
+use crate::common::{CanvasNative, NativeByteArray, NativeImageAsset, RasterImage, SurfaceKind};
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum ImageBitmapPremultiplyAlpha {
+    Default,
+    Premultiply,
+    None,
+}
+
+impl From<c_int> for ImageBitmapPremultiplyAlpha {
+    fn from(value: c_int) -> Self {
+        match value {
+            1 => ImageBitmapPremultiplyAlpha::Premultiply,
+            2 => ImageBitmapPremultiplyAlpha::None,
+            _ => ImageBitmapPremultiplyAlpha::Default,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum ImageBitmapColorSpaceConversion {
+    Default,
+    None,
+}
+
+impl From<c_int> for ImageBitmapColorSpaceConversion {
+    fn from(value: c_int) -> Self {
+        match value {
+            1 => ImageBitmapColorSpaceConversion::None,
+            _ => ImageBitmapColorSpaceConversion::Default,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum ImageBitmapResizeQuality {
+    Low,
+    Medium,
+    High,
+    Pixelated,
+}
+
+impl From<c_int> for ImageBitmapResizeQuality {
+    fn from(value: c_int) -> Self {
+        match value {
+            1 => ImageBitmapResizeQuality::Medium,
+            2 => ImageBitmapResizeQuality::High,
+            3 => ImageBitmapResizeQuality::Pixelated,
+            _ => ImageBitmapResizeQuality::Low,
+        }
+    }
+}
+
+/// The createImageBitmap arguments. The crop rectangle (sx, sy, sw, sh) is only used
+/// when `crop` is set, and a resize dimension of 0 or less means it wasn't given.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct ImageBitmapOptions {
+    pub crop: bool,
+    pub sx: c_float,
+    pub sy: c_float,
+    pub sw: c_float,
+    pub sh: c_float,
+    pub flip_y: bool,
+    pub premultiply_alpha: ImageBitmapPremultiplyAlpha,
+    pub color_space_conversion: ImageBitmapColorSpaceConversion,
+    pub resize_width: c_float,
+    pub resize_height: c_float,
+    pub resize_quality: ImageBitmapResizeQuality,
+}
+
+impl Default for ImageBitmapOptions {
+    fn default() -> Self {
+        Self {
+            crop: false,
+            sx: 0.0,
+            sy: 0.0,
+            sw: 0.0,
+            sh: 0.0,
+            flip_y: false,
+            premultiply_alpha: ImageBitmapPremultiplyAlpha::Default,
+            color_space_conversion: ImageBitmapColorSpaceConversion::Default,
+            resize_width: 0.0,
+            resize_height: 0.0,
+            resize_quality: ImageBitmapResizeQuality::Low,
+        }
+    }
+}
+
+/// A decoded raster image that stays resident so it can be drawn, used as a pattern or
+/// uploaded to WebGL without decoding or copying the source again.
+pub struct ImageBitmap {
+    pub(crate) image: Image,
+}
+
+impl ImageBitmap {
+    pub fn width(&self) -> c_int {
+        self.image.width()
+    }
+
+    pub fn height(&self) -> c_int {
+        self.image.height()
+    }
+
+    /// Applies the crop, resize, orientation, alpha and color space options to `source`.
+    /// Returns `None` for an empty crop or output size.
+    pub fn new(source: &Image, options: &ImageBitmapOptions) -> Option<Self> {
+        let mut src = if options.crop {
+            // A negative width or height crops towards the origin.
+            Rect::new(options.sx, options.sy, options.sx + options.sw, options.sy + options.sh).sorted()
+        } else {
+            Rect::new(0.0, 0.0, source.width() as f32, source.height() as f32)
+        };
+        if options.crop {
+            src = Rect::new(src.left.floor(), src.top.floor(), src.right.ceil(), src.bottom.ceil());
+        }
+        if src.width() <= 0.0 || src.height() <= 0.0 {
+            return None;
+        }
+
+        let (width, height) = match (options.resize_width > 0.0, options.resize_height > 0.0) {
+            (true, true) => (options.resize_width, options.resize_height),
+            (true, false) => (options.resize_width, (src.height() * options.resize_width / src.width()).ceil()),
+            (false, true) => ((src.width() * options.resize_height / src.height()).ceil(), options.resize_height),
+            (false, false) => (src.width(), src.height()),
+        };
+        let width = width as i32;
+        let height = height as i32;
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+
+        let alpha_type = if options.premultiply_alpha == ImageBitmapPremultiplyAlpha::None {
+            AlphaType::Unpremul
+        } else {
+            AlphaType::Premul
+        };
+        // Without color space conversion the pixels are read in the source's own color
+        // space, so they come out as they were stored.
+        let color_space = match options.color_space_conversion {
+            ImageBitmapColorSpaceConversion::None => source.image_info().color_space(),
+            ImageBitmapColorSpaceConversion::Default => Some(ColorSpace::new_srgb()),
+        };
+        let read_info = ImageInfo::new(ISize::new(width, height), ColorType::RGBA8888, alpha_type, color_space.clone());
+        let row_bytes = (width * 4) as usize;
+        let mut pixels = vec![0u8; row_bytes * height as usize];
+        if width == src.width() as i32 && height == src.height() as i32 {
+            // Cropping and flipping only move pixels, so they're copied as they are. The
+            // parts of the crop outside the source stay transparent.
+            let bounds = Rect::new(0.0, 0.0, source.width() as f32, source.height() as f32);
+            if src.intersects(&bounds)
+                && !source.read_pixels(
+                    &read_info,
+                    pixels.as_mut_slice(),
+                    row_bytes,
+                    IPoint::new(src.left as i32, src.top as i32),
+                    CachingHint::Allow,
+                )
+            {
+                return None;
+            }
+            if options.flip_y {
+                pixels = pixels.chunks(row_bytes).rev().collect::<Vec<&[u8]>>().concat();
+            }
+        } else {
+            // Resampling draws into a premultiplied surface. Unpremultiplied output needs
+            // half floats there, or translucent pixels lose their color on the way back.
+            let color_type = if alpha_type == AlphaType::Unpremul {
+                ColorType::RGBAF16
+            } else {
+                ColorType::RGBA8888
+            };
+            let surface_info = ImageInfo::new(ISize::new(width, height), color_type, AlphaType::Premul, color_space);
+            let mut surface = Surface::new_raster(&surface_info, None, None)?;
+            let canvas = surface.canvas();
+            canvas.clear(Color::TRANSPARENT);
+            if options.flip_y {
+                canvas.translate(Point::new(0.0, height as f32));
+                canvas.scale((1.0, -1.0));
+            }
+            let mut paint = Paint::default();
+            paint.set_blend_mode(BlendMode::Src);
+            paint.set_filter_quality(match options.resize_quality {
+                ImageBitmapResizeQuality::Pixelated => FilterQuality::None,
+                ImageBitmapResizeQuality::Low => FilterQuality::Low,
+                ImageBitmapResizeQuality::Medium => FilterQuality::Medium,
+                ImageBitmapResizeQuality::High => FilterQuality::High,
+            });
+            canvas.draw_image_rect(
+                source,
+                Some((&src, SrcRectConstraint::Strict)),
+                Rect::new(0.0, 0.0, width as f32, height as f32),
+                &paint,
+            );
+            let snapshot = surface.image_snapshot();
+            if !snapshot.read_pixels(&read_info, pixels.as_mut_slice(), row_bytes, IPoint::new(0, 0), CachingHint::Allow) {
+                return None;
+            }
+        }
+        // The pixels are now either sRGB or deliberately left unconverted; both are
+        // tagged as sRGB from here on.
+        let info = ImageInfo::new(ISize::new(width, height), ColorType::RGBA8888, alpha_type, None);
+        let image = Image::from_raster_data(&info, Data::new_copy(&pixels), row_bytes)?;
+        Some(Self { image })
+    }
+
+    /// The RGBA8888 pixels, premultiplied or not, optionally flipped vertically as
+    /// WebGL's UNPACK_FLIP_Y_WEBGL expects.
+    pub fn bytes(&self, flip_y: bool, premultiply: bool) -> Vec<u8> {
+        let alpha_type = if premultiply { AlphaType::Premul } else { AlphaType::Unpremul };
+        let info = ImageInfo::new(
+            ISize::new(self.width(), self.height()),
+            ColorType::RGBA8888,
+            alpha_type,
+            None,
+        );
+        let row_bytes = (self.width() * 4) as usize;
+        let mut pixels = vec![0u8; row_bytes * self.height() as usize];
+        let _ = self.image.read_pixels(&info, pixels.as_mut_slice(), row_bytes, IPoint::new(0, 0), CachingHint::Allow);
+        if flip_y {
+            let rows: Vec<&[u8]> = pixels.chunks(row_bytes).rev().collect();
+            return rows.concat();
+        }
+        pixels
+    }
+}
+
+/// The asset's current image for Skia. Its pixels, which are unpremultiplied RGBA,
+/// are copied the first time and shared with every later caller until they change.
+pub(crate) fn image_from_asset(asset: &mut NativeImageAsset) -> Option<Image> {
+    if asset.raster.is_none() {
+        asset.raster = asset.image.as_ref().and_then(|image| {
+            let width = image.width() as i32;
+            let height = image.height() as i32;
+            let info = ImageInfo::new(
+                ISize::new(width, height),
+                ColorType::RGBA8888,
+                AlphaType::Unpremul,
+                None,
+            );
+            let data = match image.as_rgba8() {
+                Some(rgba) => Data::new_copy(rgba.as_raw()),
+                _ => Data::new_copy(&image.to_rgba8().into_raw()),
+            };
+            Image::from_raster_data(&info, data, (width * 4) as usize).map(RasterImage)
+        });
+    }
+    asset.raster.as_ref().map(|raster| raster.0.clone())
+}
+
+fn new_image_bitmap(source: Option<Image>, options: &ImageBitmapOptions) -> c_longlong {
+    match source.and_then(|source| ImageBitmap::new(&source, options)) {
+        Some(bitmap) => Box::into_raw(Box::new(bitmap)) as *mut _ as i64,
+        _ => 0,
+    }
+}
+
+#[inline]
+pub(crate) fn create_image_bitmap_encoded(
+    image_data: *const u8,
+    image_size: size_t,
+    options: ImageBitmapOptions,
+) -> c_longlong {
+    if image_data.is_null() || image_size == 0 {
+        return 0;
+    }
+    let image_slice: &[u8] = unsafe { std::slice::from_raw_parts(image_data, image_size) };
+    let image = Image::from_encoded(Data::new_copy(image_slice), None);
+    new_image_bitmap(image, &options)
+}
+
+#[inline]
+pub(crate) fn create_image_bitmap_from_asset(asset: c_longlong, options: ImageBitmapOptions) -> c_longlong {
+    if asset == 0 {
+        return 0;
+    }
+    let mut asset: Box<NativeImageAsset> = unsafe { Box::from_raw(asset as *mut _) };
+    let image = image_from_asset(&mut asset);
+    Box::into_raw(asset);
+    new_image_bitmap(image, &options)
+}
+
+/// ImageData holds unpremultiplied RGBA.
+#[inline]
+pub(crate) fn create_image_bitmap_from_image_data(
+    data: *const u8,
+    data_size: size_t,
+    width: c_int,
+    height: c_int,
+    options: ImageBitmapOptions,
+) -> c_longlong {
+    if data.is_null() || width <= 0 || height <= 0 {
+        return 0;
+    }
+    let size = match (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(4))
+    {
+        Some(size) if size <= data_size => size,
+        _ => return 0,
+    };
+    let array = unsafe { std::slice::from_raw_parts(data, size) };
+    let info = ImageInfo::new(
+        ISize::new(width, height),
+        ColorType::RGBA8888,
+        AlphaType::Unpremul,
+        None,
+    );
+    let image = Image::from_raster_data(&info, Data::new_copy(array), width as usize * 4);
+    new_image_bitmap(image, &options)
+}
+
+#[inline]
+pub(crate) fn create_image_bitmap_from_canvas(
+    canvas_native_ptr: c_longlong,
+    options: ImageBitmapOptions,
+) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let snapshot = canvas_native.surface.image_snapshot();
+    let image = match canvas_native.surface_kind {
+        SurfaceKind::GPU => snapshot.new_raster_image(),
+        SurfaceKind::CPU => Some(snapshot),
+    };
+    Box::into_raw(canvas_native);
+    new_image_bitmap(image, &options)
+}
+
+#[inline]
+pub(crate) fn create_image_bitmap_from_bitmap(bitmap: c_longlong, options: ImageBitmapOptions) -> c_longlong {
+    if bitmap == 0 {
+        return 0;
+    }
+    let image_bitmap: Box<ImageBitmap> = unsafe { Box::from_raw(bitmap as *mut _) };
+    let image = image_bitmap.image.clone();
+    Box::into_raw(image_bitmap);
+    new_image_bitmap(Some(image), &options)
+}
+
+#[inline]
+pub(crate) fn image_bitmap_width(bitmap: c_longlong) -> c_int {
+    if bitmap == 0 {
+        return 0;
+    }
+    let image_bitmap: Box<ImageBitmap> = unsafe { Box::from_raw(bitmap as *mut _) };
+    let width = image_bitmap.width();
+    Box::into_raw(image_bitmap);
+    width
+}
+
+#[inline]
+pub(crate) fn image_bitmap_height(bitmap: c_longlong) -> c_int {
+    if bitmap == 0 {
+        return 0;
+    }
+    let image_bitmap: Box<ImageBitmap> = unsafe { Box::from_raw(bitmap as *mut _) };
+    let height = image_bitmap.height();
+    Box::into_raw(image_bitmap);
+    height
+}
+
+#[inline]
+pub(crate) fn image_bitmap_get_bytes(bitmap: c_longlong, flip_y: bool, premultiply: bool) -> NativeByteArray {
+    if bitmap == 0 {
+        return NativeByteArray { array: null_mut(), length: 0 };
+    }
+    let image_bitmap: Box<ImageBitmap> = unsafe { Box::from_raw(bitmap as *mut _) };
+    let mut pixels = image_bitmap.bytes(flip_y, premultiply).into_boxed_slice();
+    Box::into_raw(image_bitmap);
+    let array = NativeByteArray {
+        array: pixels.as_mut_ptr(),
+        length: pixels.len(),
+    };
+    mem::forget(pixels);
+    array
+}
+
+/// Releases the bitmap, like ImageBitmap.close().
+#[inline]
+pub(crate) fn image_bitmap_close(bitmap: c_longlong) {
+    if bitmap == 0 {
+        return;
+    }
+    let _: Box<ImageBitmap> = unsafe { Box::from_raw(bitmap as *mut _) };
+}
+
+fn draw_image_bitmap_rect(
+    canvas_native_ptr: c_longlong,
+    bitmap: c_longlong,
+    src: Option<Rect>,
+    dst: Option<Rect>,
+    dx: c_float,
+    dy: c_float,
+) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    if bitmap == 0 {
+        return canvas_native_ptr;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let image_bitmap: &ImageBitmap = unsafe { &*(bitmap as *const ImageBitmap) };
+    let paint = canvas_native.image_paint_for_draw();
+    let canvas = canvas_native.surface.canvas();
+    match dst {
+        Some(dst) => {
+            canvas.draw_image_rect(
+                &image_bitmap.image,
+                src.as_ref().map(|src| (src, SrcRectConstraint::Strict)),
+                dst,
+                &paint,
+            );
+        }
+        _ => {
+            canvas.draw_image(&image_bitmap.image, Point::new(dx, dy), Some(&paint));
+        }
+    }
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
+
+#[inline]
+pub(crate) fn draw_image_bitmap(
+    canvas_native_ptr: c_longlong,
+    bitmap: c_longlong,
+    dx: c_float,
+    dy: c_float,
+) -> c_longlong {
+    draw_image_bitmap_rect(canvas_native_ptr, bitmap, None, None, dx, dy)
+}
+
+#[inline]
+pub(crate) fn draw_image_bitmap_dw(
+    canvas_native_ptr: c_longlong,
+    bitmap: c_longlong,
+    dx: c_float,
+    dy: c_float,
+    d_width: c_float,
+    d_height: c_float,
+) -> c_longlong {
+    let dst = Rect::new(dx, dy, d_width + dx, d_height + dy);
+    draw_image_bitmap_rect(canvas_native_ptr, bitmap, None, Some(dst), dx, dy)
+}
+
+#[inline]
+pub(crate) fn draw_image_bitmap_sw(
+    canvas_native_ptr: c_longlong,
+    bitmap: c_longlong,
+    sx: c_float,
+    sy: c_float,
+    s_width: c_float,
+    s_height: c_float,
+    dx: c_float,
+    dy: c_float,
+    d_width: c_float,
+    d_height: c_float,
+) -> c_longlong {
+    let src = Rect::new(sx, sy, s_width + sx, s_height + sy);
+    let dst = Rect::new(dx, dy, d_width + dx, d_height + dy);
+    draw_image_bitmap_rect(canvas_native_ptr, bitmap, Some(src), Some(dst), dx, dy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    // An unpremultiplied RGBA image from its rows of pixels.
+    fn source(rows: &[&[[u8; 4]]]) -> Image {
+        let width = rows[0].len() as i32;
+        let height = rows.len() as i32;
+        let pixels: Vec<u8> = rows.iter().flat_map(|row| row.iter().flatten().cloned()).collect();
+        let info = ImageInfo::new(ISize::new(width, height), ColorType::RGBA8888, AlphaType::Unpremul, None);
+        Image::from_raster_data(&info, Data::new_copy(&pixels), width as usize * 4).unwrap()
+    }
+
+    fn pixels(bitmap: &ImageBitmap) -> Vec<[u8; 4]> {
+        bitmap
+            .bytes(false, false)
+            .chunks(4)
+            .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]])
+            .collect()
+    }
+
+    fn cropped(sx: f32, sy: f32, sw: f32, sh: f32) -> ImageBitmapOptions {
+        ImageBitmapOptions { crop: true, sx, sy, sw, sh, ..Default::default() }
+    }
+
+    #[test]
+    fn crop_keeps_the_parts_outside_the_source_transparent() {
+        let image = source(&[&[RED, BLUE], &[BLUE, RED]]);
+        let bitmap = ImageBitmap::new(&image, &cropped(1.0, 1.0, 2.0, 2.0)).unwrap();
+        assert_eq!((bitmap.width(), bitmap.height()), (2, 2));
+        assert_eq!(pixels(&bitmap), vec![RED, CLEAR, CLEAR, CLEAR]);
+
+        let bitmap = ImageBitmap::new(&image, &cropped(-1.0, 0.0, 2.0, 1.0)).unwrap();
+        assert_eq!(pixels(&bitmap), vec![CLEAR, RED]);
+
+        let bitmap = ImageBitmap::new(&image, &cropped(5.0, 5.0, 3.0, 1.0)).unwrap();
+        assert_eq!(pixels(&bitmap), vec![CLEAR; 3]);
+    }
+
+    #[test]
+    fn crop_with_a_negative_size_crops_towards_the_origin() {
+        let image = source(&[&[RED, BLUE], &[BLUE, RED]]);
+        let bitmap = ImageBitmap::new(&image, &cropped(2.0, 1.0, -1.0, -1.0)).unwrap();
+        assert_eq!(pixels(&bitmap), vec![BLUE]);
+        assert!(ImageBitmap::new(&image, &cropped(0.0, 0.0, 0.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn resize_with_one_dimension_keeps_the_aspect_ratio() {
+        let image = source(&[&[RED; 4], &[RED; 4]]);
+        let options = ImageBitmapOptions { resize_width: 2.0, ..Default::default() };
+        let bitmap = ImageBitmap::new(&image, &options).unwrap();
+        assert_eq!((bitmap.width(), bitmap.height()), (2, 1));
+
+        let options = ImageBitmapOptions { resize_height: 4.0, ..Default::default() };
+        let bitmap = ImageBitmap::new(&image, &options).unwrap();
+        assert_eq!((bitmap.width(), bitmap.height()), (8, 4));
+        assert!(pixels(&bitmap).iter().all(|pixel| *pixel == RED));
+
+        let options = ImageBitmapOptions { resize_width: 3.0, resize_height: 5.0, ..Default::default() };
+        let bitmap = ImageBitmap::new(&image, &options).unwrap();
+        assert_eq!((bitmap.width(), bitmap.height()), (3, 5));
+    }
+
+    #[test]
+    fn flip_y_reverses_the_rows() {
+        let image = source(&[&[RED], &[BLUE]]);
+        let options = ImageBitmapOptions { flip_y: true, ..Default::default() };
+        let bitmap = ImageBitmap::new(&image, &options).unwrap();
+        assert_eq!(pixels(&bitmap), vec![BLUE, RED]);
+
+        let options = ImageBitmapOptions {
+            flip_y: true,
+            resize_width: 2.0,
+            resize_height: 4.0,
+            resize_quality: ImageBitmapResizeQuality::Pixelated,
+            ..Default::default()
+        };
+        let bitmap = ImageBitmap::new(&image, &options).unwrap();
+        assert_eq!(pixels(&bitmap), vec![BLUE, BLUE, BLUE, BLUE, RED, RED, RED, RED]);
+    }
+
+    #[test]
+    fn premultiply_alpha_none_keeps_translucent_colors() {
+        let translucent = [200, 100, 50, 3];
+        let image = source(&[&[translucent; 2], &[translucent; 2]]);
+        let options = ImageBitmapOptions {
+            premultiply_alpha: ImageBitmapPremultiplyAlpha::None,
+            ..Default::default()
+        };
+        let bitmap = ImageBitmap::new(&image, &options).unwrap();
+        assert_eq!(pixels(&bitmap), vec![translucent; 4]);
+
+        let options = ImageBitmapOptions {
+            premultiply_alpha: ImageBitmapPremultiplyAlpha::None,
+            resize_width: 4.0,
+            resize_height: 4.0,
+            ..Default::default()
+        };
+        let bitmap = ImageBitmap::new(&image, &options).unwrap();
+        for pixel in pixels(&bitmap) {
+            for (channel, expected) in pixel.iter().zip(translucent.iter()) {
+                assert!((*channel as i32 - *expected as i32).abs() <= 2, "{:?}", pixel);
+            }
+        }
+    }
+
+    #[test]
+    fn premultiply_alpha_stores_premultiplied_pixels() {
+        let image = source(&[&[[255, 0, 0, 128]]]);
+        let options = ImageBitmapOptions {
+            premultiply_alpha: ImageBitmapPremultiplyAlpha::Premultiply,
+            ..Default::default()
+        };
+        let bitmap = ImageBitmap::new(&image, &options).unwrap();
+        assert_eq!(bitmap.image.alpha_type(), AlphaType::Premul);
+        assert_eq!(bitmap.bytes(false, true), vec![128, 0, 0, 128]);
+        assert_eq!(bitmap.bytes(false, false), vec![255, 0, 0, 128]);
+    }
+}
@@ -0,0 +1,506 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::ffi::CStr;
+use std::hash::{Hash, Hasher};
+use std::os::raw::c_char;
+
+use libc::{c_float, c_longlong, size_t};
+use skia_safe::{Data, Image, Point, Rect, canvas::SrcRectConstraint, gpu::MipMapped};
+
+use crate::common::{CanvasNative, SurfaceKind, parse_data_url};
+
+/// Budget a canvas starts with for decoded images, in bytes.
+pub const IMAGE_CACHE_DEFAULT_BUDGET: size_t = 32 * 1024 * 1024;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ImageCacheStats {
+    pub entries: size_t,
+    pub bytes: size_t,
+    pub budget: size_t,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+/// What an image is cached under: its encoded bytes, a key the caller picked or the
+/// data URL it was decoded from.
+#[derive(Copy, Clone)]
+pub enum ImageCacheSource<'a> {
+    Bytes(&'a [u8]),
+    Key(&'a str),
+    DataUrl(&'a str),
+}
+
+impl<'a> ImageCacheSource<'a> {
+    // Each kind of source is hashed with its own prefix so a caller key can't collide
+    // with the hash of some encoded image or data URL.
+    fn hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        match self {
+            ImageCacheSource::Bytes(bytes) => {
+                0u8.hash(&mut hasher);
+                bytes.hash(&mut hasher);
+            }
+            ImageCacheSource::Key(key) => {
+                1u8.hash(&mut hasher);
+                key.hash(&mut hasher);
+            }
+            ImageCacheSource::DataUrl(url) => {
+                2u8.hash(&mut hasher);
+                url.hash(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+}
+
+// Owned copy of the source an entry was stored under. Lookups compare it in full, so
+// two sources whose hashes collide never return each other's image.
+enum ImageCacheKey {
+    Bytes(Vec<u8>),
+    Key(String),
+    DataUrl(String),
+}
+
+impl ImageCacheKey {
+    fn new(source: ImageCacheSource) -> Self {
+        match source {
+            ImageCacheSource::Bytes(bytes) => ImageCacheKey::Bytes(bytes.to_vec()),
+            ImageCacheSource::Key(key) => ImageCacheKey::Key(key.to_string()),
+            ImageCacheSource::DataUrl(url) => ImageCacheKey::DataUrl(url.to_string()),
+        }
+    }
+
+    fn matches(&self, source: ImageCacheSource) -> bool {
+        match (self, source) {
+            (ImageCacheKey::Bytes(stored), ImageCacheSource::Bytes(bytes)) => stored.as_slice() == bytes,
+            (ImageCacheKey::Key(stored), ImageCacheSource::Key(key)) => stored == key,
+            (ImageCacheKey::DataUrl(stored), ImageCacheSource::DataUrl(url)) => stored == url,
+            _ => false,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            ImageCacheKey::Bytes(bytes) => bytes.len(),
+            ImageCacheKey::Key(key) | ImageCacheKey::DataUrl(key) => key.len(),
+        }
+    }
+}
+
+struct ImageCacheEntry {
+    key: ImageCacheKey,
+    image: Image,
+    bytes: usize,
+    last_used: u64,
+}
+
+/// Decoded images keyed by their encoded bytes or by a key the caller picked, so
+/// drawing the same source again skips decoding. On GPU surfaces the entries are
+/// texture-backed, which also skips the upload. Entries keep a copy of their source,
+/// which counts toward the budget, and least recently used entries are evicted once
+/// the budget is exceeded.
+pub struct ImageCache {
+    entries: HashMap<u64, ImageCacheEntry>,
+    budget: usize,
+    used: usize,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl Default for ImageCache {
+    fn default() -> Self {
+        Self::new(IMAGE_CACHE_DEFAULT_BUDGET)
+    }
+}
+
+impl ImageCache {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            budget,
+            used: 0,
+            tick: 0,
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    pub fn contains(&self, source: ImageCacheSource) -> bool {
+        self.entries
+            .get(&source.hash())
+            .map_or(false, |entry| entry.key.matches(source))
+    }
+
+    pub fn get(&mut self, source: ImageCacheSource) -> Option<Image> {
+        self.tick += 1;
+        match self.entries.get_mut(&source.hash()) {
+            Some(entry) if entry.key.matches(source) => {
+                entry.last_used = self.tick;
+                self.hits += 1;
+                Some(entry.image.clone())
+            }
+            _ => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Stores `image` under `source`, replacing whatever had the same hash. Images
+    /// larger than the whole budget aren't kept.
+    pub fn insert(&mut self, source: ImageCacheSource, image: Image) {
+        let hash = source.hash();
+        let key = ImageCacheKey::new(source);
+        let bytes = (image.width() as usize) * (image.height() as usize) * 4 + key.len();
+        self.remove_hash(hash);
+        if bytes > self.budget {
+            return;
+        }
+        self.evict_to(self.budget - bytes);
+        self.tick += 1;
+        self.used += bytes;
+        self.entries.insert(
+            hash,
+            ImageCacheEntry {
+                key,
+                image,
+                bytes,
+                last_used: self.tick,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, source: ImageCacheSource) -> bool {
+        if !self.contains(source) {
+            return false;
+        }
+        self.remove_hash(source.hash())
+    }
+
+    fn remove_hash(&mut self, hash: u64) -> bool {
+        match self.entries.remove(&hash) {
+            Some(entry) => {
+                self.used -= entry.bytes;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict_to(budget);
+    }
+
+    /// Drops every entry. The counters are kept so stats still cover the whole
+    /// lifetime of the canvas.
+    pub fn purge(&mut self) {
+        self.entries.clear();
+        self.used = 0;
+    }
+
+    pub fn stats(&self) -> ImageCacheStats {
+        ImageCacheStats {
+            entries: self.entries.len(),
+            bytes: self.used,
+            budget: self.budget,
+            hits: self.hits,
+            misses: self.misses,
+            evictions: self.evictions,
+        }
+    }
+
+    fn evict_to(&mut self, limit: usize) {
+        while self.used > limit {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key);
+            match oldest {
+                Some(hash) => {
+                    self.remove_hash(hash);
+                    self.evictions += 1;
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+// Looks `source` up in the canvas cache, decoding `bytes` on a miss. Decoding
+// happens here rather than lazily at draw time so the cached image is ready to draw:
+// a texture on GPU surfaces and raster pixels otherwise.
+fn cached_image(canvas_native: &mut CanvasNative, source: ImageCacheSource, bytes: Option<&[u8]>) -> Option<Image> {
+    if let Some(image) = canvas_native.image_cache.get(source) {
+        return Some(image);
+    }
+    decode_into_cache(canvas_native, source, bytes?)
+}
+
+fn decode_into_cache(canvas_native: &mut CanvasNative, source: ImageCacheSource, bytes: &[u8]) -> Option<Image> {
+    let encoded = Image::from_encoded(Data::new_copy(bytes), None)?;
+    let decoded = match (canvas_native.surface_kind, canvas_native.context.as_mut()) {
+        (SurfaceKind::GPU, Some(context)) => encoded.new_texture_image(context, MipMapped::No),
+        _ => encoded.new_raster_image(),
+    }
+    .unwrap_or(encoded);
+    canvas_native.image_cache.insert(source, decoded.clone());
+    Some(decoded)
+}
+
+/// Returns the decoded image for encoded bytes, going through the canvas cache. The
+/// bytes are hashed in full and, on a miss, copied into the entry.
+pub(crate) fn cached_encoded_image(canvas_native: &mut CanvasNative, bytes: &[u8]) -> Option<Image> {
+    cached_image(canvas_native, ImageCacheSource::Bytes(bytes), Some(bytes))
+}
+
+/// Returns the decoded image a data URL holds. It's cached under the URL, so drawing
+/// the same URL again skips the base64 decode as well as the image decode.
+pub(crate) fn cached_data_url_image(canvas_native: &mut CanvasNative, url: &str) -> Option<Image> {
+    let source = ImageCacheSource::DataUrl(url);
+    if let Some(image) = canvas_native.image_cache.get(source) {
+        return Some(image);
+    }
+    let bytes = parse_data_url(url).and_then(|url| url.image_data()).ok()?;
+    decode_into_cache(canvas_native, source, &bytes)
+}
+
+fn key_from_ptr<'a>(key: *const c_char) -> Option<&'a str> {
+    if key.is_null() {
+        return None;
+    }
+    match unsafe { CStr::from_ptr(key) }.to_str() {
+        Ok(key) if !key.is_empty() => Some(key),
+        _ => None,
+    }
+}
+
+/// Draws the image cached under a caller-supplied `key`. The encoded bytes are only
+/// read on a miss, so they can be null once `image_cache_contains` reports the key.
+/// With no key the bytes themselves are hashed.
+#[inline]
+pub(crate) fn draw_image_keyed(
+    canvas_native_ptr: c_longlong,
+    key: *const c_char,
+    image_array: *const u8,
+    image_size: size_t,
+    sx: c_float,
+    sy: c_float,
+    s_width: c_float,
+    s_height: c_float,
+    dx: c_float,
+    dy: c_float,
+    d_width: c_float,
+    d_height: c_float,
+) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let bytes = if image_array.is_null() || image_size == 0 {
+        None
+    } else {
+        Some(unsafe { std::slice::from_raw_parts(image_array, image_size) })
+    };
+    let source = match key_from_ptr(key) {
+        Some(key) => Some(ImageCacheSource::Key(key)),
+        None => bytes.map(ImageCacheSource::Bytes),
+    };
+    let image = source.and_then(|source| cached_image(&mut canvas_native, source, bytes));
+    if let Some(image) = image {
+        let paint = canvas_native.image_paint_for_draw();
+        let canvas = canvas_native.surface.canvas();
+        // A negative source width means the whole image, as with drawImage(image, dx, dy, dw, dh).
+        let src_rect = if s_width < 0.0 || s_height < 0.0 {
+            Rect::new(0.0, 0.0, image.width() as f32, image.height() as f32)
+        } else {
+            Rect::new(sx, sy, s_width + sx, s_height + sy)
+        };
+        let d_width = if d_width < 0.0 { src_rect.width() } else { d_width };
+        let d_height = if d_height < 0.0 { src_rect.height() } else { d_height };
+        canvas.draw_image_rect(
+            &image,
+            Some((&src_rect, SrcRectConstraint::Strict)),
+            Rect::from_point_and_size(Point::new(dx, dy), (d_width, d_height)),
+            &paint,
+        );
+    }
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
+
+#[inline]
+pub(crate) fn image_cache_contains(canvas_native_ptr: c_longlong, key: *const c_char) -> bool {
+    if canvas_native_ptr == 0 {
+        return false;
+    }
+    let canvas_native: Box<CanvasNative> = unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let contains = match key_from_ptr(key) {
+        Some(key) => canvas_native.image_cache.contains(ImageCacheSource::Key(key)),
+        None => false,
+    };
+    Box::into_raw(canvas_native);
+    contains
+}
+
+#[inline]
+pub(crate) fn image_cache_remove(canvas_native_ptr: c_longlong, key: *const c_char) -> bool {
+    if canvas_native_ptr == 0 {
+        return false;
+    }
+    let mut canvas_native: Box<CanvasNative> = unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let removed = match key_from_ptr(key) {
+        Some(key) => canvas_native.image_cache.remove(ImageCacheSource::Key(key)),
+        None => false,
+    };
+    Box::into_raw(canvas_native);
+    removed
+}
+
+#[inline]
+pub(crate) fn image_cache_set_budget(canvas_native_ptr: c_longlong, budget: size_t) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    canvas_native.image_cache.set_budget(budget);
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
+
+#[inline]
+pub(crate) fn image_cache_purge(canvas_native_ptr: c_longlong) -> c_longlong {
+    if canvas_native_ptr == 0 {
+        return 0;
+    }
+    let mut canvas_native: Box<CanvasNative> =
+        unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    canvas_native.image_cache.purge();
+    Box::into_raw(canvas_native) as *mut _ as i64
+}
+
+#[inline]
+pub(crate) fn image_cache_stats(canvas_native_ptr: c_longlong) -> ImageCacheStats {
+    if canvas_native_ptr == 0 {
+        return ImageCacheStats::default();
+    }
+    let canvas_native: Box<CanvasNative> = unsafe { Box::from_raw(canvas_native_ptr as *mut _) };
+    let stats = canvas_native.image_cache.stats();
+    Box::into_raw(canvas_native);
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use skia_safe::{ISize, ImageInfo};
+
+    use super::*;
+
+    // A 4x4 image, which takes 64 bytes of the budget plus its key.
+    fn image() -> Image {
+        let info = ImageInfo::new_n32_premul(ISize::new(4, 4), None);
+        Image::from_raster_data(&info, Data::new_copy(&[0u8; 64]), 16).unwrap()
+    }
+
+    fn key(key: &str) -> ImageCacheSource {
+        ImageCacheSource::Key(key)
+    }
+
+    #[test]
+    fn insert_counts_pixels_and_source() {
+        let mut cache = ImageCache::new(1024);
+        cache.insert(key("a"), image());
+        cache.insert(ImageCacheSource::Bytes(&[1, 2, 3]), image());
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.bytes, 65 + 67);
+        assert_eq!(stats.budget, 1024);
+    }
+
+    #[test]
+    fn insert_replaces_the_same_source() {
+        let mut cache = ImageCache::new(1024);
+        cache.insert(key("a"), image());
+        cache.insert(key("a"), image());
+        assert_eq!(cache.stats().entries, 1);
+        assert_eq!(cache.stats().bytes, 65);
+        assert_eq!(cache.stats().evictions, 0);
+    }
+
+    #[test]
+    fn insert_skips_images_larger_than_the_budget() {
+        let mut cache = ImageCache::new(64);
+        cache.insert(key("a"), image());
+        assert!(!cache.contains(key("a")));
+        assert_eq!(cache.stats().bytes, 0);
+    }
+
+    #[test]
+    fn insert_evicts_the_least_recently_used() {
+        let mut cache = ImageCache::new(65 * 2);
+        cache.insert(key("a"), image());
+        cache.insert(key("b"), image());
+        assert!(cache.get(key("a")).is_some());
+        cache.insert(key("c"), image());
+        assert!(cache.contains(key("a")));
+        assert!(!cache.contains(key("b")));
+        assert!(cache.contains(key("c")));
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.bytes, stats.evictions), (2, 130, 1));
+    }
+
+    #[test]
+    fn set_budget_evicts_down_to_the_new_budget() {
+        let mut cache = ImageCache::new(1024);
+        for name in ["a", "b", "c"].iter() {
+            cache.insert(key(name), image());
+        }
+        cache.set_budget(65 * 2);
+        assert!(!cache.contains(key("a")));
+        assert_eq!(cache.stats().evictions, 1);
+
+        cache.set_budget(65);
+        assert!(cache.contains(key("c")));
+        assert_eq!(cache.stats().entries, 1);
+
+        cache.set_budget(0);
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.bytes, stats.budget, stats.evictions), (0, 0, 0, 3));
+        cache.insert(key("d"), image());
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn remove_and_lookups_update_the_counters() {
+        let mut cache = ImageCache::new(1024);
+        assert!(cache.get(key("a")).is_none());
+        cache.insert(key("a"), image());
+        assert!(cache.get(key("a")).is_some());
+        assert!(cache.remove(key("a")));
+        assert!(!cache.remove(key("a")));
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.bytes), (0, 0));
+        assert_eq!((stats.hits, stats.misses, stats.evictions), (1, 1, 0));
+
+        cache.insert(key("b"), image());
+        cache.purge();
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.bytes, stats.hits, stats.misses), (0, 0, 1, 1));
+    }
+
+    #[test]
+    fn sources_of_different_kinds_dont_match() {
+        let mut cache = ImageCache::new(1024);
+        cache.insert(key("x"), image());
+        assert!(!cache.contains(ImageCacheSource::DataUrl("x")));
+        assert!(!cache.contains(ImageCacheSource::Bytes(b"x")));
+        assert!(!cache.remove(ImageCacheSource::DataUrl("x")));
+        assert!(cache.contains(key("x")));
+    }
+}
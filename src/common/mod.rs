@@ -1,9 +1,31 @@
+pub use self::color::*;
 pub use self::core::*;
+pub use self::data_url::*;
+pub use self::exif::*;
+pub use self::filter::*;
+pub use self::gradient::*;
 pub use self::image_asset::*;
+pub use self::image_asset_loader::*;
+pub use self::image_bitmap::*;
+pub use self::image_cache::*;
+pub use self::svg::*;
+pub use self::tessellation::*;
 pub use self::text_decoder::*;
 pub use self::text_encoder::*;
+pub use self::webp::*;
 
+mod color;
 mod core;
+mod data_url;
+mod exif;
+mod filter;
+mod gradient;
 mod text_decoder;
 mod text_encoder;
-mod image_asset;
\ No newline at end of file
+mod image_asset;
+mod image_asset_loader;
+mod image_bitmap;
+mod image_cache;
+mod svg;
+mod tessellation;
+mod webp;
\ No newline at end of file
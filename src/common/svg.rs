@@ -1,279 +1,624 @@
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_longlong};
+
+use image::RgbaImage;
+use quick_xml::events::BytesStart;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use skia_safe::{AlphaType, Canvas, Color, ColorSpace, ColorType, IPoint, ISize, ImageInfo, Matrix, Paint, Path, Point, RRect, Rect, Surface, gpu::Context, image::CachingHint, paint::{Cap, Join, Style}, path::FillType};
+
+// The size browsers give SVG documents that declare neither a size nor a viewBox.
+const DEFAULT_WIDTH: f32 = 300.0;
+const DEFAULT_HEIGHT: f32 = 150.0;
+
 #[repr(C)]
 pub struct SVGCanvasNative {
     pub(crate) surface: Surface,
     pub(crate) context: Option<Context>,
 }
 
-
 pub(crate) fn draw_svg_image(svg_canvas_native_ptr: c_longlong, svg: *const c_char) -> c_longlong {
     if svg_canvas_native_ptr == 0 {
         return 0;
     }
     let mut svg_canvas_native: Box<SVGCanvasNative> =
         unsafe { Box::from_raw(svg_canvas_native_ptr as *mut _) };
-    let svg_surface = &mut svg_canvas_native.surface;
-    let canvas = svg_surface.canvas();
-    let mut rect = Rect::new_empty();
-    let mut svg_canvas = Canvas::new(rect.clone(), None);
     if !svg.is_null() {
         let svg_string = unsafe { CStr::from_ptr(svg as _) };
         let string = svg_string.to_str().unwrap_or("");
         if !string.is_empty() {
-            let mut reader = Reader::from_str(string);
-            let mut buf = Vec::new();
-            loop {
-                match reader.read_event(&mut buf) {
-                    Ok(Event::Start(ref e)) => match e.name() {
-                        b"svg" => {
-                            let attributes = e.attributes().map(|a| a.unwrap()).collect::<Vec<_>>();
-                            for attribute in attributes.iter() {
-                                let key = String::from_utf8_lossy(attribute.key).to_string();
-                                let val = attribute.unescape_and_decode_value(&reader).unwrap();
-                                match key.as_str() {
-                                    "width" => {
-                                        &rect.set_wh(val.parse::<f32>().unwrap(), rect.height());
-                                    }
-                                    "height" => {
-                                        &rect.set_wh(rect.width(), val.parse::<f32>().unwrap());
-                                    }
-                                    _ => {}
-                                }
-                            }
-                            svg_canvas = Canvas::new(rect.clone(), None);
-                        }
-                        b"circle" => {
-                            let attributes = e.attributes().map(|a| a.unwrap()).collect::<Vec<_>>();
-                            let mut path = Path::new();
-                            let mut fill_paint = Paint::default();
-                            fill_paint.set_anti_alias(true);
-                            fill_paint.set_style(Style::Fill);
-                            let mut stroke_paint = Paint::default();
-                            stroke_paint.set_anti_alias(true);
-                            stroke_paint.set_style(Style::Stroke);
-                            let mut point = Point::new(0.0, 0.0);
-                            let mut radius = 0f32;
-                            for attribute in attributes.iter() {
-                                let key = String::from_utf8_lossy(attribute.key).to_string();
-                                let val = attribute.unescape_and_decode_value(&reader).unwrap();
-                                match key.as_str() {
-                                    "cx" => {
-                                        point.x = val.parse::<f32>().unwrap();
-                                    }
-                                    "cy" => {
-                                        point.y = val.parse::<f32>().unwrap();
-                                    }
-                                    "r" => {
-                                        radius = val.parse::<f32>().unwrap();
-                                    }
-                                    "stroke" => {
-                                        &stroke_paint
-                                            .set_color(ColorParser::from_str(val.as_str()));
-                                    }
-                                    "stroke-width" => {
-                                        &stroke_paint.set_stroke_width(val.parse::<f32>().unwrap());
-                                    }
-                                    "fill" => {
-                                        &fill_paint.set_color(ColorParser::from_str(val.as_str()));
-                                    }
-                                    _ => {}
-                                }
-                            }
-                            path.add_circle(point, radius, None);
-                            &svg_canvas.draw_path(&path, &fill_paint);
-                            &svg_canvas.draw_path(&path, &stroke_paint);
-                        }
-                        b"text" => {}
-                        _ => {}
-                    },
-                    Ok(Event::Empty(ref e)) => match e.name() {
-                        b"circle" => {
-                            let attributes = e.attributes().map(|a| a.unwrap()).collect::<Vec<_>>();
-                            let mut path = Path::new();
-                            let mut fill_paint = Paint::default();
-                            fill_paint.set_anti_alias(true);
-                            fill_paint.set_anti_alias(true);
-                            fill_paint.set_style(Style::Fill);
-                            let mut stroke_paint = Paint::default();
-                            stroke_paint.set_anti_alias(true);
-                            stroke_paint.set_style(Style::Stroke);
-                            let mut point = Point::new(0.0, 0.0);
-                            let mut radius = 0f32;
-                            for attribute in attributes.iter() {
-                                let key = String::from_utf8_lossy(attribute.key).to_string();
-                                let val = attribute.unescape_and_decode_value(&reader).unwrap();
-                                match key.as_str() {
-                                    "cx" => {
-                                        point.x = val.parse::<f32>().unwrap();
-                                    }
-                                    "cy" => {
-                                        point.y = val.parse::<f32>().unwrap();
-                                    }
-                                    "r" => {
-                                        radius = val.parse::<f32>().unwrap();
-                                    }
-                                    "stroke" => {
-                                        &stroke_paint
-                                            .set_color(ColorParser::from_str(val.as_str()));
-                                    }
-                                    "stroke-width" => {
-                                        &stroke_paint.set_stroke_width(val.parse::<f32>().unwrap());
-                                    }
-                                    "fill" => {
-                                        &fill_paint.set_color(ColorParser::from_str(val.as_str()));
-                                    }
-                                    _ => {}
-                                }
+            let surface = &mut svg_canvas_native.surface;
+            let (width, height) = (surface.width() as f32, surface.height() as f32);
+            let canvas = surface.canvas();
+            let _ = render_svg(canvas, string, width, height);
+            canvas.flush();
+        }
+    }
+    Box::into_raw(svg_canvas_native) as *mut _ as i64
+}
+
+/// Rasterizes an SVG document to `width` by `height` pixels, mapping its viewBox
+/// into that size. A size of 0 falls back to the document's own, keeping its aspect
+/// ratio when only the other side is given.
+pub(crate) fn rasterize_svg(svg: &[u8], width: u32, height: u32) -> Result<RgbaImage, String> {
+    let svg = std::str::from_utf8(svg).map_err(|e| e.to_string())?;
+    let root = SvgRoot::parse(svg)?;
+    let (intrinsic_width, intrinsic_height) = root.intrinsic_size();
+    let (width, height) = match (width, height) {
+        (0, 0) => (intrinsic_width.ceil() as u32, intrinsic_height.ceil() as u32),
+        (0, height) => (
+            (height as f32 * intrinsic_width / intrinsic_height).round() as u32,
+            height,
+        ),
+        (width, 0) => (
+            width,
+            (width as f32 * intrinsic_height / intrinsic_width).round() as u32,
+        ),
+        size => size,
+    };
+    if width == 0 || height == 0 {
+        return Err(format!("Invalid SVG size {}x{}", width, height));
+    }
+
+    let size = ISize::new(width as i32, height as i32);
+    let surface_info = ImageInfo::new(size, ColorType::RGBA8888, AlphaType::Premul, Some(ColorSpace::new_srgb()));
+    let mut surface = Surface::new_raster(&surface_info, None, None)
+        .ok_or_else(|| format!("Failed to create a {}x{} surface", width, height))?;
+    let canvas = surface.canvas();
+    canvas.clear(Color::TRANSPARENT);
+    render_svg(canvas, svg, width as f32, height as f32)?;
+
+    let read_info = ImageInfo::new(size, ColorType::RGBA8888, AlphaType::Unpremul, Some(ColorSpace::new_srgb()));
+    let row_bytes = (width * 4) as usize;
+    let mut pixels = vec![0u8; row_bytes * height as usize];
+    let snapshot = surface.image_snapshot();
+    if !snapshot.read_pixels(&read_info, pixels.as_mut_slice(), row_bytes, IPoint::new(0, 0), CachingHint::Allow) {
+        return Err("Failed to read the rendered SVG".to_string());
+    }
+    RgbaImage::from_raw(width, height, pixels).ok_or_else(|| "Failed to read the rendered SVG".to_string())
+}
+
+/// Draws an SVG document into the `width` by `height` area at the canvas origin.
+///
+/// Supports the basic shapes, paths, groups and nested `svg` elements with their
+/// transforms and fill and stroke properties. Gradients, patterns, text, `use`,
+/// clipping and masking are not rendered, and group opacity is applied to each
+/// shape rather than to the group as a whole.
+pub(crate) fn render_svg(canvas: &mut Canvas, svg: &str, width: f32, height: f32) -> Result<(), String> {
+    let mut reader = Reader::from_str(svg);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut styles = vec![SvgStyle::default()];
+    // One entry per open element, recording whether its end restores the canvas.
+    let mut open: Vec<bool> = Vec::new();
+    let mut skip_depth = 0usize;
+    let mut is_root = true;
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                if skip_depth > 0 || is_skipped(e.local_name()) {
+                    skip_depth += 1;
+                } else {
+                    let attributes = read_attributes(e, &reader);
+                    let style = styles.last().unwrap().inherit(&attributes);
+                    let saved = match e.local_name() {
+                        b"svg" | b"g" | b"a" | b"switch" => {
+                            canvas.save();
+                            apply_transform(canvas, &attributes);
+                            if e.local_name() == b"svg" {
+                                apply_viewport(canvas, &attributes, is_root, width, height);
+                                is_root = false;
                             }
-                            path.add_circle(point, radius, None);
-                            &canvas.draw_path(&path, &fill_paint);
-                            &canvas.draw_path(&path, &stroke_paint);
+                            true
                         }
-                        b"rect" => {
-                            let attributes = e.attributes().map(|a| a.unwrap()).collect::<Vec<_>>();
-                            let mut path = Path::new();
-                            let mut fill_paint = Paint::default();
-                            fill_paint.set_anti_alias(true);
-                            fill_paint.set_style(Style::Fill);
-                            let mut stroke_paint = Paint::default();
-                            stroke_paint.set_anti_alias(true);
-                            stroke_paint.set_style(Style::Stroke);
-                            let mut rect = Rect::new_empty();
-                            for attribute in attributes.iter() {
-                                let key = String::from_utf8_lossy(attribute.key).to_string();
-                                let val = attribute.unescape_and_decode_value(&reader).unwrap();
-                                match key.as_str() {
-                                    "width" => {
-                                        rect.right = val.parse::<f32>().unwrap();
-                                    }
-                                    "height" => {
-                                        rect.bottom = val.parse::<f32>().unwrap();
-                                    }
-                                    "style" => {
-                                        let mut styles = StyleParser::from_str(val.as_ref());
-                                        for style in styles.iter() {
-                                            match style.0 {
-                                                "width" => {
-                                                    rect.right = style.1.parse::<f32>().unwrap();
-                                                }
-                                                "height" => {
-                                                    rect.bottom = style.1.parse::<f32>().unwrap();
-                                                }
-                                                "stroke" => {
-                                                    &stroke_paint
-                                                        .set_color(ColorParser::from_str(style.1));
-                                                }
-                                                "stroke-width" => {
-                                                    &stroke_paint.set_stroke_width(
-                                                        style.1.parse::<f32>().unwrap(),
-                                                    );
-                                                }
-                                                "fill" => {
-                                                    &fill_paint
-                                                        .set_color(ColorParser::from_str(style.1));
-                                                }
-                                                "stroke-opacity" => {
-                                                    &stroke_paint.set_alpha(
-                                                        (style.1.parse::<f32>().unwrap_or(1.0)
-                                                            * 255.0)
-                                                            as u8,
-                                                    );
-                                                }
-                                                "fill-opacity" => {
-                                                    &fill_paint.set_alpha(
-                                                        (style.1.parse::<f32>().unwrap_or(1.0)
-                                                            * 255.0)
-                                                            as u8,
-                                                    );
-                                                }
-                                                _ => {}
-                                            }
-                                        }
-                                    }
-                                    "stroke" => {
-                                        &stroke_paint
-                                            .set_color(ColorParser::from_str(val.as_str()));
-                                    }
-                                    "stroke-width" => {
-                                        &stroke_paint.set_stroke_width(val.parse::<f32>().unwrap());
-                                    }
-                                    "fill" => {
-                                        &fill_paint.set_color(ColorParser::from_str(val.as_str()));
-                                    }
-                                    "stroke-opacity" => {
-                                        &stroke_paint.set_alpha(val.parse::<u8>().unwrap_or(255));
-                                    }
-                                    "fill-opacity" => {
-                                        &fill_paint.set_alpha(val.parse::<u8>().unwrap_or(255));
-                                    }
-                                    _ => {}
-                                }
-                            }
-                            path.add_rect(rect, None);
-                            &canvas.draw_path(&path, &fill_paint);
-                            &canvas.draw_path(&path, &stroke_paint);
+                        _ => {
+                            draw_shape(canvas, e.local_name(), &attributes, &style);
+                            false
                         }
-                        b"path" => {
-                            let attributes = e.attributes().map(|a| a.unwrap()).collect::<Vec<_>>();
-                            let mut path = Path::new();
-                            let mut fill_paint = Paint::default();
-                            fill_paint.set_anti_alias(true);
-                            fill_paint.set_style(Style::Fill);
-                            let mut stroke_paint = Paint::default();
-                            stroke_paint.set_anti_alias(true);
-                            stroke_paint.set_style(Style::Stroke);
-                            let mut fill = false;
-                            let mut stroke = false;
-                            for attribute in attributes.iter() {
-                                let key = String::from_utf8_lossy(attribute.key).to_string();
-                                let val = attribute.unescape_and_decode_value(&reader).unwrap();
-                                match key.as_str() {
-                                    "d" => path = from_svg(val.as_str()).unwrap_or(Path::new()),
-                                    "stroke" => {
-                                        let value = val.as_str();
-                                        stroke = !value.eq("none");
-                                        &stroke_paint.set_color(ColorParser::from_str(value));
-                                    }
-                                    "stroke-width" => {
-                                        &stroke_paint.set_stroke_width(val.parse::<f32>().unwrap());
-                                    }
-                                    "fill" => {
-                                        let value = val.as_str();
-                                        fill = !value.eq("none");
-                                        &fill_paint.set_color(ColorParser::from_str(value));
-                                    }
-                                    _ => {}
-                                }
-                            }
-                            if fill {
-                                &canvas.draw_path(&path, &fill_paint);
-                            }
+                    };
+                    styles.push(style);
+                    open.push(saved);
+                }
+            }
+            Ok(Event::Empty(ref e)) => {
+                if skip_depth == 0 && !is_skipped(e.local_name()) {
+                    let attributes = read_attributes(e, &reader);
+                    let style = styles.last().unwrap().inherit(&attributes);
+                    draw_shape(canvas, e.local_name(), &attributes, &style);
+                }
+            }
+            Ok(Event::End(_)) => {
+                if skip_depth > 0 {
+                    skip_depth -= 1;
+                } else if let Some(saved) = open.pop() {
+                    styles.pop();
+                    if saved {
+                        canvas.restore();
+                    }
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => {
+                // Leave the canvas as it was for whatever follows.
+                for saved in open {
+                    if saved {
+                        canvas.restore();
+                    }
+                }
+                return Err(format!("Error at position {}: {:?}", reader.buffer_position(), e));
+            }
+            _ => (),
+        }
+        buf.clear();
+    }
+    Ok(())
+}
 
-                            if stroke {
-                                &canvas.draw_path(&path, &stroke_paint);
-                            }
-                        }
-                        _ => {}
-                    },
-                    Ok(Event::Text(e)) => {
-                        /* let font_native_type_face = Typeface::from_name("sans-serif", font_native_style).unwrap_or(Typeface::default());
-                        let font = Font::from_typeface(&font_native_type_face, 10.0);
-                        let blob = TextBlob::from_str(e.unescape_and_decode(&reader).unwrap(), &font);
-                        let mut paint = Paint::default();
-                        let mut point = Point::new(0.0, 0.0);
-                        &canvas.draw_text_blob(&blob.unwrap(), &point, &paint);*/
+// Elements whose content is never drawn directly, or that aren't supported.
+fn is_skipped(name: &[u8]) -> bool {
+    match name {
+        b"defs" | b"symbol" | b"clipPath" | b"mask" | b"pattern" | b"marker" | b"linearGradient"
+        | b"radialGradient" | b"filter" | b"style" | b"script" | b"title" | b"desc" | b"metadata"
+        | b"text" | b"foreignObject" => true,
+        _ => false,
+    }
+}
+
+// Attributes in document order, followed by the declarations of `style`, which take
+// precedence over them.
+fn read_attributes(element: &BytesStart, reader: &Reader<&[u8]>) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut style = None;
+    for attribute in element.attributes() {
+        let attribute = match attribute {
+            Ok(attribute) => attribute,
+            _ => continue,
+        };
+        let key = String::from_utf8_lossy(attribute.key).to_string();
+        let value = match attribute.unescape_and_decode_value(reader) {
+            Ok(value) => value,
+            _ => continue,
+        };
+        if key == "style" {
+            style = Some(value);
+        } else {
+            attributes.push((key, value));
+        }
+    }
+    if let Some(style) = style {
+        for (key, value) in StyleParser::from_str(style.as_str()) {
+            let key = key.trim();
+            if !key.is_empty() {
+                attributes.push((key.to_string(), value.trim().to_string()));
+            }
+        }
+    }
+    attributes
+}
+
+fn attribute<'a>(attributes: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .rev()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn length_attribute(attributes: &[(String, String)], name: &str, reference: f32) -> Option<f32> {
+    attribute(attributes, name).and_then(|value| parse_length(value, reference))
+}
+
+/// Parses a length in user units; percentages are relative to `reference`.
+fn parse_length(value: &str, reference: f32) -> Option<f32> {
+    let value = value.trim();
+    let (number, factor) = if value.ends_with('%') {
+        (&value[..value.len() - 1], reference / 100.0)
+    } else if value.len() > 2 && value.is_char_boundary(value.len() - 2) {
+        let (number, unit) = value.split_at(value.len() - 2);
+        match unit {
+            "px" => (number, 1.0),
+            "pt" => (number, 96.0 / 72.0),
+            "pc" => (number, 16.0),
+            "in" => (number, 96.0),
+            "cm" => (number, 96.0 / 2.54),
+            "mm" => (number, 96.0 / 25.4),
+            "em" => (number, 16.0),
+            _ => (value, 1.0),
+        }
+    } else {
+        (value, 1.0)
+    };
+    number.trim().parse::<f32>().ok().map(|number| number * factor)
+}
+
+/// Splits a list of numbers such as `points` or `viewBox`, where separators can be
+/// whitespace, commas, or left out before a sign or a second decimal point.
+fn parse_numbers(value: &str) -> Vec<f32> {
+    let mut numbers = Vec::new();
+    let mut current = String::new();
+    let mut has_point = false;
+    let mut has_exponent = false;
+    for c in value.chars() {
+        let starts_number = match c {
+            '-' | '+' => !current.is_empty() && !current.ends_with(|c| c == 'e' || c == 'E'),
+            '.' => has_point || has_exponent,
+            _ => false,
+        };
+        if starts_number || c.is_whitespace() || c == ',' {
+            if let Ok(number) = current.parse::<f32>() {
+                numbers.push(number);
+            }
+            current.clear();
+            has_point = false;
+            has_exponent = false;
+            if c.is_whitespace() || c == ',' {
+                continue;
+            }
+        }
+        match c {
+            '.' => has_point = true,
+            'e' | 'E' => has_exponent = true,
+            _ => {}
+        }
+        current.push(c);
+    }
+    if let Ok(number) = current.parse::<f32>() {
+        numbers.push(number);
+    }
+    numbers
+}
+
+// Paints inherited from parent elements. A `None` paint is `none`.
+#[derive(Clone)]
+struct SvgStyle {
+    color: Color,
+    fill: Option<Color>,
+    stroke: Option<Color>,
+    stroke_width: f32,
+    fill_opacity: f32,
+    stroke_opacity: f32,
+    opacity: f32,
+    fill_rule: FillType,
+    line_cap: Cap,
+    line_join: Join,
+    miter_limit: f32,
+}
+
+impl Default for SvgStyle {
+    fn default() -> Self {
+        Self {
+            color: Color::BLACK,
+            fill: Some(Color::BLACK),
+            stroke: None,
+            stroke_width: 1.0,
+            fill_opacity: 1.0,
+            stroke_opacity: 1.0,
+            opacity: 1.0,
+            fill_rule: FillType::Winding,
+            line_cap: Cap::Butt,
+            line_join: Join::Miter,
+            miter_limit: 4.0,
+        }
+    }
+}
+
+impl SvgStyle {
+    fn inherit(&self, attributes: &[(String, String)]) -> Self {
+        let mut style = self.clone();
+        // Not inherited, so it applies to this element on top of its ancestors'.
+        style.opacity = self.opacity * parse_opacity(attribute(attributes, "opacity"));
+        if let Some(color) = attribute(attributes, "color") {
+            style.color = crate::common::parse_color(color).unwrap_or(self.color);
+        }
+        for (key, value) in attributes.iter() {
+            let value = value.as_str();
+            if value == "inherit" {
+                continue;
+            }
+            match key.as_str() {
+                "fill" => style.fill = style.parse_paint(value, self.fill),
+                "stroke" => style.stroke = style.parse_paint(value, self.stroke),
+                "stroke-width" => {
+                    style.stroke_width = parse_length(value, 0.0).unwrap_or(self.stroke_width)
+                }
+                "fill-opacity" => style.fill_opacity = parse_opacity(Some(value)),
+                "stroke-opacity" => style.stroke_opacity = parse_opacity(Some(value)),
+                "fill-rule" => {
+                    style.fill_rule = match value {
+                        "evenodd" => FillType::EvenOdd,
+                        _ => FillType::Winding,
+                    }
+                }
+                "stroke-linecap" => {
+                    style.line_cap = match value {
+                        "round" => Cap::Round,
+                        "square" => Cap::Square,
+                        _ => Cap::Butt,
+                    }
+                }
+                "stroke-linejoin" => {
+                    style.line_join = match value {
+                        "round" => Join::Round,
+                        "bevel" => Join::Bevel,
+                        _ => Join::Miter,
                     }
-                    Ok(Event::End(ref e)) => {}
-                    Ok(Event::Eof) => break,
-                    Err(e) => panic!("Error at position {}: {:?}", reader.buffer_position(), e),
-                    _ => (), //
                 }
-                buf.clear();
+                "stroke-miterlimit" => {
+                    style.miter_limit = value.parse::<f32>().unwrap_or(self.miter_limit)
+                }
+                _ => {}
             }
         }
+        style
+    }
 
-        canvas.flush();
+    fn parse_paint(&self, value: &str, inherited: Option<Color>) -> Option<Color> {
+        match value {
+            "none" => None,
+            "currentColor" => Some(self.color),
+            // Paint servers aren't supported; a fallback color after the reference
+            // is used when given.
+            value if value.starts_with("url(") => value
+                .find(')')
+                .and_then(|end| crate::common::parse_color(&value[end + 1..]).ok()),
+            value => crate::common::parse_color(value).ok().or(inherited),
+        }
+    }
+
+    fn paint(&self, color: Color, opacity: f32, style: Style) -> Paint {
+        let mut paint = Paint::default();
+        paint.set_anti_alias(true);
+        paint.set_style(style);
+        paint.set_color(color);
+        paint.set_alpha_f(color.a() as f32 / 255.0 * opacity * self.opacity);
+        paint
+    }
+}
+
+fn parse_opacity(value: Option<&str>) -> f32 {
+    value
+        .and_then(|value| parse_length(value, 1.0))
+        .map_or(1.0, |opacity| opacity.max(0.0).min(1.0))
+}
+
+// Applies a `transform` list, leftmost function outermost.
+fn apply_transform(canvas: &mut Canvas, attributes: &[(String, String)]) {
+    let transform = match attribute(attributes, "transform") {
+        Some(transform) => transform,
+        None => return,
+    };
+    for function in transform.split(')') {
+        let mut parts = function.splitn(2, '(');
+        let name = parts.next().unwrap_or("").trim_matches(|c: char| c.is_whitespace() || c == ',');
+        let args = parse_numbers(parts.next().unwrap_or(""));
+        let arg = |index: usize| args.get(index).cloned().unwrap_or(0.0);
+        let matrix = match (name, args.len()) {
+            ("matrix", 6) => Matrix::new_all(arg(0), arg(2), arg(4), arg(1), arg(3), arg(5), 0.0, 0.0, 1.0),
+            ("translate", 1) | ("translate", 2) => Matrix::new_all(1.0, 0.0, arg(0), 0.0, 1.0, arg(1), 0.0, 0.0, 1.0),
+            ("scale", 1) => Matrix::new_all(arg(0), 0.0, 0.0, 0.0, arg(0), 0.0, 0.0, 0.0, 1.0),
+            ("scale", 2) => Matrix::new_all(arg(0), 0.0, 0.0, 0.0, arg(1), 0.0, 0.0, 0.0, 1.0),
+            ("rotate", 1) | ("rotate", 3) => {
+                let (sin, cos) = arg(0).to_radians().sin_cos();
+                let (cx, cy) = (arg(1), arg(2));
+                // rotate(a, cx, cy) is translate(cx, cy) rotate(a) translate(-cx, -cy).
+                Matrix::new_all(
+                    cos, -sin, cx - cos * cx + sin * cy,
+                    sin, cos, cy - sin * cx - cos * cy,
+                    0.0, 0.0, 1.0,
+                )
+            }
+            ("skewX", 1) => Matrix::new_all(1.0, arg(0).to_radians().tan(), 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0),
+            ("skewY", 1) => Matrix::new_all(1.0, 0.0, 0.0, arg(0).to_radians().tan(), 1.0, 0.0, 0.0, 0.0, 1.0),
+            _ => continue,
+        };
+        canvas.concat(&matrix);
+    }
+}
+
+// Maps an `svg` element's viewBox into its viewport. The root's viewport is the
+// area being drawn into; nested ones are positioned by their x, y, width and height.
+fn apply_viewport(canvas: &mut Canvas, attributes: &[(String, String)], is_root: bool, width: f32, height: f32) {
+    let (x, y, width, height, view_box) = if is_root {
+        let root = SvgRoot::from_attributes(attributes);
+        let view_box = root.view_box.or_else(|| {
+            // Without a viewBox the document's own size is scaled to fit.
+            let (intrinsic_width, intrinsic_height) = root.intrinsic_size();
+            Some(Rect::from_xywh(0.0, 0.0, intrinsic_width, intrinsic_height))
+        });
+        (0.0, 0.0, width, height, view_box)
+    } else {
+        (
+            length_attribute(attributes, "x", width).unwrap_or(0.0),
+            length_attribute(attributes, "y", height).unwrap_or(0.0),
+            length_attribute(attributes, "width", width).unwrap_or(width),
+            length_attribute(attributes, "height", height).unwrap_or(height),
+            attribute(attributes, "viewBox").and_then(parse_view_box),
+        )
+    };
+    canvas.translate(Point::new(x, y));
+    let view_box = match view_box {
+        Some(view_box) => view_box,
+        None => return,
+    };
+    let mut scale_x = width / view_box.width();
+    let mut scale_y = height / view_box.height();
+    let aspect = attribute(attributes, "preserveAspectRatio").unwrap_or("xMidYMid meet").trim();
+    let mut parts = aspect.split_whitespace();
+    let align = parts.next().unwrap_or("xMidYMid");
+    let slice = parts.next() == Some("slice");
+    let (mut offset_x, mut offset_y) = (0.0, 0.0);
+    if align != "none" {
+        let scale = if slice { scale_x.max(scale_y) } else { scale_x.min(scale_y) };
+        scale_x = scale;
+        scale_y = scale;
+        let (extra_x, extra_y) = (width - view_box.width() * scale, height - view_box.height() * scale);
+        if align.contains("xMid") {
+            offset_x = extra_x / 2.0;
+        } else if align.contains("xMax") {
+            offset_x = extra_x;
+        }
+        if align.contains("YMid") {
+            offset_y = extra_y / 2.0;
+        } else if align.contains("YMax") {
+            offset_y = extra_y;
+        }
+    }
+    canvas.clip_rect(Rect::from_xywh(0.0, 0.0, width, height), None, true);
+    canvas.translate(Point::new(offset_x, offset_y));
+    canvas.scale((scale_x, scale_y));
+    canvas.translate(Point::new(-view_box.left, -view_box.top));
+}
+
+fn parse_view_box(value: &str) -> Option<Rect> {
+    match parse_numbers(value).as_slice() {
+        [x, y, width, height] if *width > 0.0 && *height > 0.0 => {
+            Some(Rect::from_xywh(*x, *y, *width, *height))
+        }
+        _ => None,
+    }
+}
+
+fn draw_shape(canvas: &mut Canvas, name: &[u8], attributes: &[(String, String)], style: &SvgStyle) {
+    let length = |name: &str| length_attribute(attributes, name, 0.0);
+    let mut path = Path::new();
+    // Lines have nothing to fill.
+    let mut can_fill = true;
+    match name {
+        b"path" => {
+            path = match attribute(attributes, "d").and_then(Path::from_svg) {
+                Some(path) => path,
+                None => return,
+            }
+        }
+        b"rect" => {
+            let (width, height) = (length("width").unwrap_or(0.0), length("height").unwrap_or(0.0));
+            if width <= 0.0 || height <= 0.0 {
+                return;
+            }
+            let rect = Rect::from_xywh(length("x").unwrap_or(0.0), length("y").unwrap_or(0.0), width, height);
+            let (rx, ry) = match (length("rx"), length("ry")) {
+                (Some(rx), Some(ry)) => (rx, ry),
+                (Some(radius), None) | (None, Some(radius)) => (radius, radius),
+                (None, None) => (0.0, 0.0),
+            };
+            let (rx, ry) = (rx.max(0.0).min(width / 2.0), ry.max(0.0).min(height / 2.0));
+            if rx > 0.0 && ry > 0.0 {
+                path.add_rrect(RRect::new_rect_xy(rect, rx, ry), None);
+            } else {
+                path.add_rect(rect, None);
+            }
+        }
+        b"circle" => {
+            let radius = length("r").unwrap_or(0.0);
+            if radius <= 0.0 {
+                return;
+            }
+            path.add_circle(Point::new(length("cx").unwrap_or(0.0), length("cy").unwrap_or(0.0)), radius, None);
+        }
+        b"ellipse" => {
+            let (rx, ry) = (length("rx").unwrap_or(0.0), length("ry").unwrap_or(0.0));
+            if rx <= 0.0 || ry <= 0.0 {
+                return;
+            }
+            let (cx, cy) = (length("cx").unwrap_or(0.0), length("cy").unwrap_or(0.0));
+            path.add_oval(Rect::new(cx - rx, cy - ry, cx + rx, cy + ry), None);
+        }
+        b"line" => {
+            path.move_to(Point::new(length("x1").unwrap_or(0.0), length("y1").unwrap_or(0.0)));
+            path.line_to(Point::new(length("x2").unwrap_or(0.0), length("y2").unwrap_or(0.0)));
+            can_fill = false;
+        }
+        b"polyline" | b"polygon" => {
+            let points = parse_numbers(attribute(attributes, "points").unwrap_or(""));
+            let mut points = points.chunks_exact(2);
+            match points.next() {
+                Some(point) => path.move_to(Point::new(point[0], point[1])),
+                None => return,
+            };
+            for point in points {
+                path.line_to(Point::new(point[0], point[1]));
+            }
+            if name == b"polygon" {
+                path.close();
+            }
+        }
+        _ => return,
+    }
+    path.set_fill_type(style.fill_rule);
+
+    if let (Some(fill), true) = (style.fill, can_fill) {
+        canvas.draw_path(&path, &style.paint(fill, style.fill_opacity, Style::Fill));
+    }
+    if let Some(stroke) = style.stroke {
+        if style.stroke_width > 0.0 {
+            let mut paint = style.paint(stroke, style.stroke_opacity, Style::Stroke);
+            paint.set_stroke_width(style.stroke_width);
+            paint.set_stroke_cap(style.line_cap);
+            paint.set_stroke_join(style.line_join);
+            paint.set_stroke_miter(style.miter_limit);
+            canvas.draw_path(&path, &paint);
+        }
+    }
+}
+
+// The size information on the outermost `svg` element.
+struct SvgRoot {
+    width: Option<f32>,
+    height: Option<f32>,
+    view_box: Option<Rect>,
+}
+
+impl SvgRoot {
+    fn parse(svg: &str) -> Result<Self, String> {
+        let mut reader = Reader::from_str(svg);
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event(&mut buf) {
+                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => {
+                    if e.local_name() != b"svg" {
+                        return Err("Not an SVG document".to_string());
+                    }
+                    return Ok(Self::from_attributes(&read_attributes(e, &reader)));
+                }
+                Ok(Event::Eof) => return Err("Not an SVG document".to_string()),
+                Err(e) => return Err(format!("Error at position {}: {:?}", reader.buffer_position(), e)),
+                _ => (),
+            }
+            buf.clear();
+        }
+    }
+
+    fn from_attributes(attributes: &[(String, String)]) -> Self {
+        // Percentages depend on where the document is placed, so count as unset.
+        let size = |name: &str| {
+            attribute(attributes, name)
+                .filter(|value| !value.trim().ends_with('%'))
+                .and_then(|value| parse_length(value, 0.0))
+                .filter(|value| *value > 0.0)
+        };
+        Self {
+            width: size("width"),
+            height: size("height"),
+            view_box: attribute(attributes, "viewBox").and_then(parse_view_box),
+        }
+    }
+
+    fn intrinsic_size(&self) -> (f32, f32) {
+        let ratio = self.view_box.map(|view_box| view_box.width() / view_box.height());
+        match (self.width, self.height, ratio) {
+            (Some(width), Some(height), _) => (width, height),
+            (Some(width), None, Some(ratio)) => (width, width / ratio),
+            (None, Some(height), Some(ratio)) => (height * ratio, height),
+            (None, None, Some(_)) => {
+                let view_box = self.view_box.unwrap();
+                (view_box.width(), view_box.height())
+            }
+            (width, height, None) => (
+                width.unwrap_or(DEFAULT_WIDTH),
+                height.unwrap_or(DEFAULT_HEIGHT),
+            ),
+        }
     }
-    Box::into_raw(svg_canvas_native) as *mut _ as i64
 }
 
 pub(crate) struct StyleParser {}
@@ -281,10 +626,10 @@ pub(crate) struct StyleParser {}
 impl StyleParser {
     pub fn from_str(style: &str) -> Vec<(&str, &str)> {
         let mut values: Vec<(_, _)> = Vec::new();
-        let mut styles: Vec<&str> = style.split(";").collect();
+        let styles: Vec<&str> = style.split(";").collect();
         for style in styles.iter() {
             let value = *style;
-            let key_value: Vec<_> = value.split(":").collect();
+            let key_value: Vec<_> = value.splitn(2, ":").collect();
             let default = "";
             let k = key_value.get(0).unwrap_or(&default).to_owned();
             let v = key_value.get(1).unwrap_or(&default).to_owned();
@@ -298,34 +643,110 @@ pub(crate) struct ColorParser {}
 
 impl ColorParser {
     pub fn is_color(value: &str) -> bool {
-        value.starts_with("#") || value.starts_with("rgb") || value.starts_with("hsl")
+        crate::common::parse_color(value).is_ok()
     }
     pub fn from_str(color: &str) -> Color {
-        let mut value = color.to_lowercase();
-        if value.starts_with("#") {
-            Color::BLACK
-        } else if value.starts_with("rgb") {
-            value = value.replace("rgba(", "");
-            value = value.replace("rgb(", "");
-            value = value.replace(")", "");
-            let mut rgb_rgba: Vec<_> = value.split(",").collect();
-            let default = "255";
-            let mut r = rgb_rgba.get(0).unwrap_or(&default).parse().unwrap_or(255);
-            let mut g = rgb_rgba.get(1).unwrap_or(&default).parse().unwrap_or(255);
-            let mut b = rgb_rgba.get(2).unwrap_or(&default).parse().unwrap_or(255);
-            let mut a = rgb_rgba.get(3).unwrap_or(&default).parse().unwrap_or(255);
-
-            Color::from_argb(a, r, g, b)
-        } else if value.starts_with("hsl") {
-            Color::BLACK
-        } else {
-            match value.as_str() {
-                "red" => Color::RED,
-                "blue" => Color::BLUE,
-                "green" => Color::GREEN,
-                "pink" => Color::from_rgb(255, 192, 203),
-                _ => Color::BLACK,
-            }
-        }
+        crate::common::parse_color(color).unwrap_or(Color::BLACK)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    fn pixel(image: &RgbaImage, x: u32, y: u32) -> [u8; 4] {
+        image.get_pixel(x, y).0
+    }
+
+    // A 10x10 viewBox with a red top half and a blue bottom half.
+    fn halves(attributes: &str) -> String {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10" {}>
+                <rect width="10" height="5" fill="red"/>
+                <rect y="5" width="10" height="5" fill="blue"/>
+            </svg>"#,
+            attributes
+        )
+    }
+
+    #[test]
+    fn view_box_maps_onto_the_requested_size() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="10 20 10 10">
+            <rect x="10" y="20" width="5" height="10" fill="red"/>
+        </svg>"#;
+        let image = rasterize_svg(svg.as_bytes(), 20, 20).unwrap();
+        assert_eq!(image.dimensions(), (20, 20));
+        assert_eq!(pixel(&image, 4, 10), RED);
+        assert_eq!(pixel(&image, 15, 10), CLEAR);
+    }
+
+    #[test]
+    fn meet_centers_the_view_box() {
+        let image = rasterize_svg(halves("").as_bytes(), 40, 20).unwrap();
+        assert_eq!(pixel(&image, 5, 5), CLEAR);
+        assert_eq!(pixel(&image, 20, 5), RED);
+        assert_eq!(pixel(&image, 20, 15), BLUE);
+        assert_eq!(pixel(&image, 35, 15), CLEAR);
+    }
+
+    #[test]
+    fn meet_aligns_to_the_requested_edge() {
+        let svg = halves(r#"preserveAspectRatio="xMaxYMin meet""#);
+        let image = rasterize_svg(svg.as_bytes(), 40, 20).unwrap();
+        assert_eq!(pixel(&image, 15, 5), CLEAR);
+        assert_eq!(pixel(&image, 35, 5), RED);
+        assert_eq!(pixel(&image, 35, 15), BLUE);
+    }
+
+    #[test]
+    fn slice_covers_the_viewport_and_crops() {
+        // Scaled to 20x20 into a 20x10 viewport, only the middle rows of each half show.
+        let svg = halves(r#"preserveAspectRatio="xMidYMid slice""#);
+        let image = rasterize_svg(svg.as_bytes(), 20, 10).unwrap();
+        assert_eq!(pixel(&image, 1, 2), RED);
+        assert_eq!(pixel(&image, 18, 2), RED);
+        assert_eq!(pixel(&image, 1, 8), BLUE);
+        assert_eq!(pixel(&image, 18, 8), BLUE);
+    }
+
+    #[test]
+    fn none_stretches_the_view_box() {
+        let svg = halves(r#"preserveAspectRatio="none""#);
+        let image = rasterize_svg(svg.as_bytes(), 40, 10).unwrap();
+        assert_eq!(pixel(&image, 1, 2), RED);
+        assert_eq!(pixel(&image, 38, 2), RED);
+        assert_eq!(pixel(&image, 1, 8), BLUE);
+        assert_eq!(pixel(&image, 38, 8), BLUE);
+    }
+
+    #[test]
+    fn zero_sizes_fall_back_to_the_document_size() {
+        let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" width="30" height="15"/>"#;
+        assert_eq!(rasterize_svg(svg, 0, 0).unwrap().dimensions(), (30, 15));
+        assert_eq!(rasterize_svg(svg, 0, 10).unwrap().dimensions(), (20, 10));
+        assert_eq!(rasterize_svg(svg, 12, 0).unwrap().dimensions(), (12, 6));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn zero_sizes_use_the_view_box_or_the_default_size() {
+        let view_box = br#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 8 4"/>"#;
+        assert_eq!(rasterize_svg(view_box, 0, 0).unwrap().dimensions(), (8, 4));
+        let width_only = br#"<svg xmlns="http://www.w3.org/2000/svg" width="16" viewBox="0 0 8 4"/>"#;
+        assert_eq!(rasterize_svg(width_only, 0, 0).unwrap().dimensions(), (16, 8));
+        let bare = br#"<svg xmlns="http://www.w3.org/2000/svg"/>"#;
+        assert_eq!(
+            rasterize_svg(bare, 0, 0).unwrap().dimensions(),
+            (DEFAULT_WIDTH as u32, DEFAULT_HEIGHT as u32)
+        );
+    }
+
+    #[test]
+    fn rejects_documents_that_are_not_svg() {
+        assert!(rasterize_svg(b"<html/>", 10, 10).is_err());
+        assert!(rasterize_svg(b"", 10, 10).is_err());
+    }
+}
@@ -0,0 +1,635 @@
+use std::os::raw::{c_char, c_uint};
+
+use libc::{c_float, c_longlong, size_t};
+use skia_safe::{Path, Point};
+use skia_safe::path::{FillType, Iter, Verb};
+
+use crate::common::{CanvasArray, fill_type_from_rule};
+
+const DEFAULT_TOLERANCE: f32 = 0.25;
+const MAX_SEGMENTS: f32 = 1024.0;
+
+/// A flattened contour. Points are stored as interleaved x, y pairs and a closed
+/// contour repeats its first point at the end.
+pub struct PathContour {
+    pub points: Vec<f32>,
+    pub closed: bool,
+}
+
+/// Triangles covering the filled area of a path, ready to be uploaded as an array
+/// buffer (x, y pairs) and an element array buffer.
+pub struct PathTriangulation {
+    pub vertices: Vec<f32>,
+    pub indices: Vec<u32>,
+}
+
+impl PathTriangulation {
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len() / 2
+    }
+
+    /// The indices as `u16`, which WebGL1 can draw without OES_element_index_uint,
+    /// or `None` when there are too many vertices for them.
+    pub fn indices_u16(&self) -> Option<Vec<u16>> {
+        if self.vertex_count() > u16::MAX as usize {
+            return None;
+        }
+        Some(self.indices.iter().map(|index| *index as u16).collect())
+    }
+}
+
+/// `closed` holds one flag per contour.
+#[repr(C)]
+pub struct CanvasPathContours {
+    pub contours: *const CanvasArray,
+    pub closed: *const bool,
+    pub length: size_t,
+}
+
+/// `index_size` is the size in bytes of each index, 2 or 4.
+#[repr(C)]
+pub struct CanvasPathTriangulation {
+    pub vertices: CanvasArray,
+    pub indices: CanvasArray,
+    pub index_size: c_uint,
+}
+
+fn tolerance_or_default(tolerance: f32) -> f32 {
+    if tolerance.is_finite() && tolerance > 0.0 {
+        tolerance
+    } else {
+        DEFAULT_TOLERANCE
+    }
+}
+
+fn distance(x: f32, y: f32) -> f32 {
+    (x * x + y * y).sqrt()
+}
+
+fn segments_for(deviation: f32, tolerance: f32) -> usize {
+    let count = (deviation / tolerance).sqrt().ceil();
+    if count.is_finite() {
+        count.max(1.0).min(MAX_SEGMENTS) as usize
+    } else {
+        1
+    }
+}
+
+fn push_point(points: &mut Vec<Point>, point: Point) {
+    if let Some(last) = points.last() {
+        if last.x == point.x && last.y == point.y {
+            return;
+        }
+    }
+    points.push(point);
+}
+
+fn flatten_quad(points: &mut Vec<Point>, p: &[Point], tolerance: f32) {
+    // The chord of a quadratic deviates from the curve by at most |p0 - 2p1 + p2| / 4,
+    // and splitting it into n pieces divides that by n².
+    let dd = distance(p[0].x - 2.0 * p[1].x + p[2].x, p[0].y - 2.0 * p[1].y + p[2].y);
+    let count = segments_for(dd / 4.0, tolerance);
+    for i in 1..=count {
+        let t = i as f32 / count as f32;
+        let mt = 1.0 - t;
+        push_point(points, Point::new(
+            mt * mt * p[0].x + 2.0 * mt * t * p[1].x + t * t * p[2].x,
+            mt * mt * p[0].y + 2.0 * mt * t * p[1].y + t * t * p[2].y,
+        ));
+    }
+}
+
+fn flatten_conic(points: &mut Vec<Point>, p: &[Point], weight: f32, tolerance: f32) {
+    let dd = distance(p[0].x - 2.0 * p[1].x + p[2].x, p[0].y - 2.0 * p[1].y + p[2].y);
+    let count = segments_for(dd * weight.max(1.0) / 4.0, tolerance);
+    for i in 1..=count {
+        let t = i as f32 / count as f32;
+        let mt = 1.0 - t;
+        let a = mt * mt;
+        let b = 2.0 * mt * t * weight;
+        let c = t * t;
+        let denominator = a + b + c;
+        push_point(points, Point::new(
+            (a * p[0].x + b * p[1].x + c * p[2].x) / denominator,
+            (a * p[0].y + b * p[1].y + c * p[2].y) / denominator,
+        ));
+    }
+}
+
+fn flatten_cubic(points: &mut Vec<Point>, p: &[Point], tolerance: f32) {
+    let dd = distance(p[0].x - 2.0 * p[1].x + p[2].x, p[0].y - 2.0 * p[1].y + p[2].y).max(
+        distance(p[1].x - 2.0 * p[2].x + p[3].x, p[1].y - 2.0 * p[2].y + p[3].y),
+    );
+    let count = segments_for(dd * 0.75, tolerance);
+    for i in 1..=count {
+        let t = i as f32 / count as f32;
+        let mt = 1.0 - t;
+        let a = mt * mt * mt;
+        let b = 3.0 * mt * mt * t;
+        let c = 3.0 * mt * t * t;
+        let d = t * t * t;
+        push_point(points, Point::new(
+            a * p[0].x + b * p[1].x + c * p[2].x + d * p[3].x,
+            a * p[0].y + b * p[1].y + c * p[2].y + d * p[3].y,
+        ));
+    }
+}
+
+// Returns every contour of the path as a polyline along with whether it was closed.
+fn flatten(path: &Path, tolerance: f32) -> Vec<(Vec<Point>, bool)> {
+    let tolerance = tolerance_or_default(tolerance);
+    let mut contours: Vec<(Vec<Point>, bool)> = Vec::new();
+    let mut current: Vec<Point> = Vec::new();
+    let mut iter = Iter::new(path, false);
+    while let Some((verb, points)) = iter.next() {
+        match verb {
+            Verb::Move => {
+                if current.len() > 1 {
+                    contours.push((current, false));
+                }
+                current = Vec::new();
+                push_point(&mut current, points[0]);
+            }
+            Verb::Line => {
+                if current.is_empty() {
+                    push_point(&mut current, points[0]);
+                }
+                push_point(&mut current, points[1]);
+            }
+            Verb::Quad => flatten_quad(&mut current, &points, tolerance),
+            Verb::Conic => {
+                let weight = iter.conic_weight().unwrap_or(1.0);
+                flatten_conic(&mut current, &points, weight, tolerance)
+            }
+            Verb::Cubic => flatten_cubic(&mut current, &points, tolerance),
+            Verb::Close => {
+                if current.len() > 1 {
+                    let first = current[0];
+                    push_point(&mut current, first);
+                    contours.push((current, true));
+                }
+                current = Vec::new();
+            }
+            _ => {}
+        }
+    }
+    if current.len() > 1 {
+        contours.push((current, false));
+    }
+    contours
+}
+
+pub(crate) fn flatten_path(path: &Path, tolerance: f32) -> Vec<PathContour> {
+    flatten(path, tolerance)
+        .into_iter()
+        .map(|(points, closed)| PathContour {
+            points: points.iter().flat_map(|point| vec![point.x, point.y]).collect(),
+            closed,
+        })
+        .collect()
+}
+
+fn signed_area(points: &[Point]) -> f32 {
+    let mut area = 0.0;
+    let mut previous = match points.last() {
+        Some(point) => *point,
+        _ => return 0.0,
+    };
+    for point in points {
+        area += previous.x * point.y - point.x * previous.y;
+        previous = *point;
+    }
+    area / 2.0
+}
+
+fn contains(polygon: &[Point], point: Point) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let a = polygon[i];
+        let b = polygon[j];
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+fn cross(a: Point, b: Point, c: Point) -> f32 {
+    (b.x - a.x) * (c.y - b.y) - (b.y - a.y) * (c.x - b.x)
+}
+
+fn same(a: Point, b: Point) -> bool {
+    a.x == b.x && a.y == b.y
+}
+
+fn in_triangle(a: Point, b: Point, c: Point, p: Point) -> bool {
+    cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0
+}
+
+// Joins a hole to the ring it sits in with a pair of coincident edges so the
+// result can be ear clipped as one polygon (Eberly, "Triangulation by Ear Clipping").
+fn bridge_hole(ring: &mut Vec<u32>, hole: &[u32], vertices: &[Point]) {
+    let (m_position, m) = hole
+        .iter()
+        .enumerate()
+        .fold((0, vertices[hole[0] as usize]), |best, (position, index)| {
+            let point = vertices[*index as usize];
+            if point.x > best.1.x { (position, point) } else { best }
+        });
+
+    let mut nearest_x = std::f32::INFINITY;
+    let mut candidate: Option<usize> = None;
+    for i in 0..ring.len() {
+        let a = vertices[ring[i] as usize];
+        let b = vertices[ring[(i + 1) % ring.len()] as usize];
+        if (a.y <= m.y && b.y >= m.y) || (b.y <= m.y && a.y >= m.y) {
+            let x = if a.y == b.y {
+                a.x.min(b.x)
+            } else {
+                a.x + (m.y - a.y) * (b.x - a.x) / (b.y - a.y)
+            };
+            if x >= m.x && x < nearest_x {
+                nearest_x = x;
+                candidate = Some(if a.x > b.x { i } else { (i + 1) % ring.len() });
+            }
+        }
+    }
+    let mut p_position = match candidate {
+        Some(position) => position,
+        _ => return,
+    };
+
+    // Another ring vertex inside the triangle formed by the hole vertex, the ray hit and
+    // the candidate would block the bridge; take the one closest in angle to the ray.
+    let intersection = Point::new(nearest_x, m.y);
+    let p = vertices[ring[p_position] as usize];
+    let (t1, t2) = if m.y < p.y { (intersection, p) } else { (p, intersection) };
+    let mut best_tan = std::f32::INFINITY;
+    for (position, index) in ring.iter().enumerate() {
+        let point = vertices[*index as usize];
+        if position == p_position || same(point, p) || point.x <= m.x {
+            continue;
+        }
+        if in_triangle(m, t1, t2, point) || in_triangle(m, t2, t1, point) {
+            let tan = (m.y - point.y).abs() / (point.x - m.x);
+            if tan < best_tan {
+                best_tan = tan;
+                p_position = position;
+            }
+        }
+    }
+
+    let mut bridged: Vec<u32> = Vec::with_capacity(ring.len() + hole.len() + 2);
+    bridged.extend_from_slice(&ring[..=p_position]);
+    bridged.extend_from_slice(&hole[m_position..]);
+    bridged.extend_from_slice(&hole[..=m_position]);
+    bridged.extend_from_slice(&ring[p_position..]);
+    *ring = bridged;
+}
+
+// `next` links the vertices still in the ring, so only those are tested.
+fn is_ear(ring: &[u32], next: &[usize], vertices: &[Point], prev: usize, cur: usize, after: usize) -> bool {
+    let a = vertices[ring[prev] as usize];
+    let b = vertices[ring[cur] as usize];
+    let c = vertices[ring[after] as usize];
+    if cross(a, b, c) <= 0.0 {
+        return false;
+    }
+    let mut position = next[after];
+    while position != prev {
+        let point = vertices[ring[position] as usize];
+        if !same(point, a) && !same(point, b) && !same(point, c) && in_triangle(a, b, c, point) {
+            return false;
+        }
+        position = next[position];
+    }
+    true
+}
+
+// Clips ears off a ring kept as a doubly linked list of positions, so removing a
+// vertex is constant time.
+fn clip_ears(ring: Vec<u32>, vertices: &[Point], indices: &mut Vec<u32>) {
+    let count = ring.len();
+    if count < 3 {
+        return;
+    }
+    let mut prev: Vec<usize> = (0..count).map(|position| (position + count - 1) % count).collect();
+    let mut next: Vec<usize> = (0..count).map(|position| (position + 1) % count).collect();
+    let mut remaining = count;
+    let mut position = 0;
+    let mut attempts = 0;
+    while remaining > 3 {
+        let before = prev[position];
+        let after = next[position];
+        let is_ear = is_ear(&ring, &next, vertices, before, position, after);
+        if is_ear || attempts >= remaining {
+            // When nothing left is a clean ear, which only happens with degenerate
+            // input, the current vertex is cut anyway so the loop always terminates.
+            let a = vertices[ring[before] as usize];
+            let b = vertices[ring[position] as usize];
+            let c = vertices[ring[after] as usize];
+            if is_ear || cross(a, b, c) > 0.0 {
+                indices.extend_from_slice(&[ring[before], ring[position], ring[after]]);
+            }
+            next[before] = after;
+            prev[after] = before;
+            remaining -= 1;
+            position = if is_ear { before } else { after };
+            attempts = 0;
+        } else {
+            position = after;
+            attempts += 1;
+        }
+    }
+    let (before, after) = (prev[position], next[position]);
+    let a = vertices[ring[before] as usize];
+    let b = vertices[ring[position] as usize];
+    let c = vertices[ring[after] as usize];
+    if cross(a, b, c) > 0.0 {
+        indices.extend_from_slice(&[ring[before], ring[position], ring[after]]);
+    }
+}
+
+/// Open contours are closed implicitly, as `fill` does.
+pub(crate) fn triangulate_path(path: &Path, fill_type: FillType, tolerance: f32) -> PathTriangulation {
+    let mut source = path.clone();
+    source.set_fill_type(fill_type);
+    // Simplifying resolves self intersections and overlaps, leaving contours that only
+    // nest. Its result says which fill rule it should be read with.
+    let simple = source.simplify().unwrap_or(source);
+    let fill_type = simple.fill_type();
+
+    let mut vertices: Vec<Point> = Vec::new();
+    // Each ring is kept with positive area, along with the direction it was drawn in.
+    let mut polygons: Vec<(Vec<u32>, f32, i32)> = Vec::new();
+    for (mut points, _) in flatten(&simple, tolerance) {
+        if points.len() > 1 && same(points[0], points[points.len() - 1]) {
+            points.pop();
+        }
+        let area = signed_area(&points);
+        if points.len() < 3 || area == 0.0 || !area.is_finite() {
+            continue;
+        }
+        let start = vertices.len() as u32;
+        let mut ring: Vec<u32> = (start..start + points.len() as u32).collect();
+        vertices.extend_from_slice(&points);
+        if area < 0.0 {
+            ring.reverse();
+        }
+        polygons.push((ring, area.abs(), if area < 0.0 { -1 } else { 1 }));
+    }
+
+    let outlines: Vec<Vec<Point>> = polygons
+        .iter()
+        .map(|(ring, _, _)| ring.iter().map(|index| vertices[*index as usize]).collect())
+        .collect();
+    let mut parents: Vec<Option<usize>> = vec![None; polygons.len()];
+    for i in 0..polygons.len() {
+        let sample = outlines[i][0];
+        for j in 0..polygons.len() {
+            if i != j && polygons[j].1 > polygons[i].1 && contains(&outlines[j], sample) {
+                let closer = match parents[i] {
+                    Some(parent) => polygons[j].1 < polygons[parent].1,
+                    _ => true,
+                };
+                if closer {
+                    parents[i] = Some(j);
+                }
+            }
+        }
+    }
+
+    // The area just inside a contour is filled according to the contours around it:
+    // how many there are for evenodd, and the sum of their directions for nonzero.
+    let filled: Vec<bool> = (0..polygons.len())
+        .map(|i| {
+            let (mut depth, mut winding) = (1, polygons[i].2);
+            let mut parent = parents[i];
+            while let Some(j) = parent {
+                depth += 1;
+                winding += polygons[j].2;
+                parent = parents[j];
+            }
+            match fill_type {
+                FillType::EvenOdd | FillType::InverseEvenOdd => depth % 2 == 1,
+                _ => winding != 0,
+            }
+        })
+        .collect();
+
+    // Every filled contour is triangulated with its direct children cut out; children
+    // that are filled as well are triangulated on their own.
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); polygons.len()];
+    for i in 0..polygons.len() {
+        if let Some(parent) = parents[i] {
+            children[parent].push(i);
+        }
+    }
+
+    let mut indices: Vec<u32> = Vec::new();
+    for i in 0..polygons.len() {
+        if !filled[i] {
+            continue;
+        }
+        let mut ring = polygons[i].0.clone();
+        let mut holes: Vec<Vec<u32>> = children[i]
+            .iter()
+            .map(|hole| polygons[*hole].0.iter().rev().cloned().collect())
+            .collect();
+        let max_x = |hole: &Vec<u32>| {
+            hole.iter()
+                .map(|index| vertices[*index as usize].x)
+                .fold(std::f32::NEG_INFINITY, f32::max)
+        };
+        holes.sort_by(|a, b| max_x(b).partial_cmp(&max_x(a)).unwrap_or(std::cmp::Ordering::Equal));
+        for hole in &holes {
+            bridge_hole(&mut ring, hole, &vertices);
+        }
+        clip_ears(ring, &vertices, &mut indices);
+    }
+
+    PathTriangulation {
+        vertices: vertices.iter().flat_map(|point| vec![point.x, point.y]).collect(),
+        indices,
+    }
+}
+
+#[inline]
+pub(crate) fn path_flatten(path: c_longlong, tolerance: c_float) -> Vec<PathContour> {
+    if path == 0 {
+        return Vec::new();
+    }
+    let path: Box<Path> = unsafe { Box::from_raw(path as *mut _) };
+    let result = flatten_path(&path, tolerance);
+    Box::into_raw(path);
+    result
+}
+
+#[inline]
+pub(crate) fn path_triangulate(
+    path: c_longlong,
+    fill_rule: *const c_char,
+    tolerance: c_float,
+) -> PathTriangulation {
+    if path == 0 {
+        return PathTriangulation {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        };
+    }
+    let path: Box<Path> = unsafe { Box::from_raw(path as *mut _) };
+    let result = triangulate_path(&path, fill_type_from_rule(fill_rule), tolerance);
+    Box::into_raw(path);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use skia_safe::Path;
+    use skia_safe::path::FillType;
+
+    use super::*;
+
+    fn square(path: &mut Path, x: f32, y: f32, size: f32, clockwise: bool) {
+        let corners = [(x, y), (x + size, y), (x + size, y + size), (x, y + size)];
+        path.move_to(corners[0]);
+        if clockwise {
+            for corner in corners[1..].iter() {
+                path.line_to(*corner);
+            }
+        } else {
+            for corner in corners[1..].iter().rev() {
+                path.line_to(*corner);
+            }
+        }
+        path.close();
+    }
+
+    fn covered_area(triangulation: &PathTriangulation) -> f32 {
+        let vertex = |index: u32| {
+            let index = index as usize * 2;
+            (triangulation.vertices[index], triangulation.vertices[index + 1])
+        };
+        triangulation
+            .indices
+            .chunks(3)
+            .map(|triangle| {
+                let (a, b, c) = (vertex(triangle[0]), vertex(triangle[1]), vertex(triangle[2]));
+                ((b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1)).abs() / 2.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn square_is_two_triangles() {
+        let mut path = Path::new();
+        square(&mut path, 0.0, 0.0, 10.0, true);
+        let triangulation = triangulate_path(&path, FillType::Winding, 0.0);
+        assert_eq!(triangulation.indices.len(), 6);
+        assert_eq!(covered_area(&triangulation), 100.0);
+    }
+
+    #[test]
+    fn square_with_hole() {
+        let mut path = Path::new();
+        square(&mut path, 0.0, 0.0, 10.0, true);
+        square(&mut path, 3.0, 3.0, 4.0, false);
+        for fill_type in [FillType::Winding, FillType::EvenOdd].iter() {
+            let triangulation = triangulate_path(&path, *fill_type, 0.0);
+            assert_eq!(covered_area(&triangulation), 84.0);
+        }
+    }
+
+    #[test]
+    fn evenodd_and_nonzero_differ_for_nested_contours_in_the_same_direction() {
+        let mut path = Path::new();
+        square(&mut path, 0.0, 0.0, 10.0, true);
+        square(&mut path, 3.0, 3.0, 4.0, true);
+        let nonzero = triangulate_path(&path, FillType::Winding, 0.0);
+        assert_eq!(covered_area(&nonzero), 100.0);
+        let evenodd = triangulate_path(&path, FillType::EvenOdd, 0.0);
+        assert_eq!(covered_area(&evenodd), 84.0);
+    }
+
+    #[test]
+    fn island_inside_a_hole_is_filled() {
+        let mut path = Path::new();
+        square(&mut path, 0.0, 0.0, 10.0, true);
+        square(&mut path, 2.0, 2.0, 6.0, false);
+        square(&mut path, 4.0, 4.0, 2.0, true);
+        let triangulation = triangulate_path(&path, FillType::Winding, 0.0);
+        assert_eq!(covered_area(&triangulation), 100.0 - 36.0 + 4.0);
+    }
+
+    #[test]
+    fn degenerate_input_has_no_triangles() {
+        let mut collinear = Path::new();
+        collinear.move_to((0.0, 0.0));
+        collinear.line_to((5.0, 5.0));
+        collinear.line_to((10.0, 10.0));
+        collinear.close();
+        assert!(triangulate_path(&collinear, FillType::Winding, 0.0).indices.is_empty());
+
+        let mut line = Path::new();
+        line.move_to((0.0, 0.0));
+        line.line_to((10.0, 0.0));
+        assert!(triangulate_path(&line, FillType::Winding, 0.0).indices.is_empty());
+
+        assert!(triangulate_path(&Path::new(), FillType::Winding, 0.0).indices.is_empty());
+    }
+
+    #[test]
+    fn concave_contour_stays_inside() {
+        // An L shape, which has a reflex corner that can't be an ear.
+        let mut path = Path::new();
+        path.move_to((0.0, 0.0));
+        for point in [(10.0, 0.0), (10.0, 4.0), (4.0, 4.0), (4.0, 10.0), (0.0, 10.0)].iter() {
+            path.line_to(*point);
+        }
+        path.close();
+        let triangulation = triangulate_path(&path, FillType::Winding, 0.0);
+        assert_eq!(triangulation.indices.len(), 12);
+        assert_eq!(covered_area(&triangulation), 64.0);
+    }
+
+    #[test]
+    fn flatten_keeps_whether_contours_are_closed() {
+        let mut path = Path::new();
+        path.move_to((0.0, 0.0));
+        path.line_to((10.0, 0.0));
+        path.line_to((10.0, 10.0));
+        square(&mut path, 20.0, 0.0, 10.0, true);
+        let contours = flatten_path(&path, 0.0);
+        assert_eq!(contours.len(), 2);
+        assert!(!contours[0].closed);
+        assert_eq!(contours[0].points, vec![0.0, 0.0, 10.0, 0.0, 10.0, 10.0]);
+        assert!(contours[1].closed);
+        assert_eq!(contours[1].points.len(), 10);
+    }
+
+    #[test]
+    fn small_triangulations_fit_u16_indices() {
+        let mut path = Path::new();
+        path.move_to((100.0, 0.0));
+        for step in 1..360 {
+            let (sin, cos) = (step as f32).to_radians().sin_cos();
+            path.line_to((100.0 * cos, 100.0 * sin));
+        }
+        path.close();
+        let triangulation = triangulate_path(&path, FillType::Winding, 0.0);
+        assert_eq!(triangulation.indices.len(), 358 * 3);
+        let indices = triangulation.indices_u16().unwrap();
+        assert_eq!(indices.len(), triangulation.indices.len());
+        assert!(indices.iter().zip(triangulation.indices.iter()).all(|(a, b)| *a as u32 == *b));
+
+        let large = PathTriangulation {
+            vertices: vec![0.0; (u16::MAX as usize + 1) * 2],
+            indices: vec![0, 1, u16::MAX as u32],
+        };
+        assert!(large.indices_u16().is_none());
+    }
+}
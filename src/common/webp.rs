@@ -0,0 +1,487 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use image::RgbaImage;
+
+// A minimal lossless (VP8L) WebP encoder: the subtract-green transform followed by
+// one Huffman code per channel, built from that channel's histogram. There are no
+// backward references, so it compresses less than libwebp but round-trips exactly.
+
+const MAX_DIMENSION: u32 = 1 << 14;
+const GREEN_ALPHABET_SIZE: usize = 256 + 24;
+const DISTANCE_ALPHABET_SIZE: usize = 40;
+const CODE_LENGTH_CODES: usize = 19;
+const CODE_LENGTH_ORDER: [usize; CODE_LENGTH_CODES] = [
+    17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+const SUBTRACT_GREEN_TRANSFORM: u32 = 2;
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    // Bits are packed least significant first.
+    fn write(&mut self, value: u32, count: u32) {
+        if count == 0 {
+            return;
+        }
+        let mask = if count == 32 { u32::MAX } else { (1 << count) - 1 };
+        self.bits |= ((value & mask) as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
+
+// A prefix code ready for writing: the length and bit-reversed canonical code of each
+// symbol, since codes are read one bit at a time starting from the most significant.
+struct PrefixCode {
+    lengths: Vec<u8>,
+    codes: Vec<u32>,
+}
+
+impl PrefixCode {
+    fn new(lengths: Vec<u8>) -> Self {
+        let mut count_per_length = [0u32; 16];
+        for length in lengths.iter() {
+            count_per_length[*length as usize] += 1;
+        }
+        count_per_length[0] = 0;
+        let mut next_code = [0u32; 16];
+        let mut code = 0;
+        for length in 1..16 {
+            code = (code + count_per_length[length - 1]) << 1;
+            next_code[length] = code;
+        }
+        let codes = lengths
+            .iter()
+            .map(|length| {
+                let length = *length as usize;
+                if length == 0 {
+                    return 0;
+                }
+                let code = next_code[length];
+                next_code[length] += 1;
+                code.reverse_bits() >> (32 - length)
+            })
+            .collect();
+        Self { lengths, codes }
+    }
+
+    // A code with a single symbol takes no bits at all.
+    fn single() -> Self {
+        Self {
+            lengths: Vec::new(),
+            codes: Vec::new(),
+        }
+    }
+
+    fn write(&self, writer: &mut BitWriter, symbol: usize) {
+        if let Some(length) = self.lengths.get(symbol) {
+            writer.write(self.codes[symbol], *length as u32);
+        }
+    }
+}
+
+// Huffman code lengths no longer than `limit`. When the tree gets too deep, small
+// counts are raised and the tree rebuilt, which flattens it until it fits.
+fn code_lengths(histogram: &[u32], limit: u8) -> Vec<u8> {
+    let mut min_count = 1;
+    loop {
+        let lengths = huffman_lengths(histogram, min_count);
+        if lengths.iter().all(|length| *length <= limit) {
+            return lengths;
+        }
+        min_count *= 2;
+    }
+}
+
+fn huffman_lengths(histogram: &[u32], min_count: u32) -> Vec<u8> {
+    let mut lengths = vec![0u8; histogram.len()];
+    let mut parents: Vec<usize> = Vec::new();
+    let mut heap = BinaryHeap::new();
+    for (symbol, count) in histogram.iter().enumerate() {
+        if *count > 0 {
+            // Leaves are numbered after their symbol; their parent is filled in below.
+            heap.push(Reverse(((*count).max(min_count) as u64, parents.len(), symbol)));
+            parents.push(usize::MAX);
+        }
+    }
+    let leaves = parents.len();
+    if leaves < 2 {
+        for (symbol, count) in histogram.iter().enumerate() {
+            if *count > 0 {
+                lengths[symbol] = 1;
+            }
+        }
+        return lengths;
+    }
+    let mut symbols = vec![0usize; leaves];
+    for Reverse((_, node, symbol)) in heap.iter() {
+        symbols[*node] = *symbol;
+    }
+    while heap.len() > 1 {
+        let Reverse((first_weight, first, _)) = heap.pop().unwrap();
+        let Reverse((second_weight, second, _)) = heap.pop().unwrap();
+        let node = parents.len();
+        parents.push(usize::MAX);
+        parents[first] = node;
+        parents[second] = node;
+        heap.push(Reverse((first_weight + second_weight, node, usize::MAX)));
+    }
+    for leaf in 0..leaves {
+        let mut depth = 0u8;
+        let mut node = leaf;
+        while parents[node] != usize::MAX {
+            node = parents[node];
+            depth = depth.saturating_add(1);
+        }
+        lengths[symbols[leaf]] = depth;
+    }
+    lengths
+}
+
+// Writes the code for `histogram` into the stream and returns it for encoding symbols.
+fn write_prefix_code(writer: &mut BitWriter, histogram: &[u32]) -> PrefixCode {
+    let used: Vec<usize> = histogram
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .map(|(symbol, _)| symbol)
+        .collect();
+
+    if used.len() <= 2 && used.iter().all(|symbol| *symbol < 256) {
+        // Simple code: one or two 8-bit symbols.
+        let first = used.first().cloned().unwrap_or(0) as u32;
+        writer.write(1, 1);
+        writer.write(used.len().max(1) as u32 - 1, 1);
+        if first < 2 {
+            writer.write(0, 1);
+            writer.write(first, 1);
+        } else {
+            writer.write(1, 1);
+            writer.write(first, 8);
+        }
+        if used.len() == 2 {
+            writer.write(used[1] as u32, 8);
+            let mut lengths = vec![0u8; histogram.len()];
+            lengths[used[0]] = 1;
+            lengths[used[1]] = 1;
+            return PrefixCode::new(lengths);
+        }
+        return PrefixCode::single();
+    }
+
+    let lengths = code_lengths(histogram, 15);
+
+    // The code lengths are themselves written with a prefix code of at most 7 bits.
+    let mut length_histogram = [0u32; CODE_LENGTH_CODES];
+    for length in lengths.iter() {
+        length_histogram[*length as usize] += 1;
+    }
+    let length_code_lengths = code_lengths(&length_histogram, 7);
+    let distinct = length_histogram.iter().filter(|count| **count > 0).count();
+    let length_code = if distinct > 1 {
+        PrefixCode::new(length_code_lengths.clone())
+    } else {
+        PrefixCode::single()
+    };
+    let count = CODE_LENGTH_ORDER
+        .iter()
+        .rposition(|symbol| length_code_lengths[*symbol] > 0)
+        .map_or(0, |position| position + 1)
+        .max(4);
+
+    writer.write(0, 1);
+    writer.write(count as u32 - 4, 4);
+    for symbol in CODE_LENGTH_ORDER.iter().take(count) {
+        writer.write(length_code_lengths[*symbol] as u32, 3);
+    }
+    // Every symbol of the alphabet has its length written.
+    writer.write(0, 1);
+    for length in lengths.iter() {
+        length_code.write(writer, *length as usize);
+    }
+    PrefixCode::new(lengths)
+}
+
+/// Encodes `image` as a lossless WebP file.
+pub(crate) fn encode_webp_lossless(image: &RgbaImage) -> Result<Vec<u8>, String> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(format!("Image size {}x{} can't be encoded as WebP", width, height));
+    }
+
+    let pixels: Vec<[u8; 4]> = image
+        .pixels()
+        .map(|pixel| {
+            let [red, green, blue, alpha] = pixel.0;
+            [red.wrapping_sub(green), green, blue.wrapping_sub(green), alpha]
+        })
+        .collect();
+    let alpha_used = pixels.iter().any(|pixel| pixel[3] != 255);
+
+    let mut green = vec![0u32; GREEN_ALPHABET_SIZE];
+    let mut red = vec![0u32; 256];
+    let mut blue = vec![0u32; 256];
+    let mut alpha = vec![0u32; 256];
+    for [r, g, b, a] in pixels.iter() {
+        green[*g as usize] += 1;
+        red[*r as usize] += 1;
+        blue[*b as usize] += 1;
+        alpha[*a as usize] += 1;
+    }
+
+    let mut writer = BitWriter::new();
+    writer.write(0x2f, 8);
+    writer.write(width - 1, 14);
+    writer.write(height - 1, 14);
+    writer.write(alpha_used as u32, 1);
+    writer.write(0, 3);
+
+    writer.write(1, 1);
+    writer.write(SUBTRACT_GREEN_TRANSFORM, 2);
+    writer.write(0, 1);
+    // No color cache and a single set of prefix codes for the whole image.
+    writer.write(0, 1);
+    writer.write(0, 1);
+
+    let green_code = write_prefix_code(&mut writer, &green);
+    let red_code = write_prefix_code(&mut writer, &red);
+    let blue_code = write_prefix_code(&mut writer, &blue);
+    let alpha_code = write_prefix_code(&mut writer, &alpha);
+    write_prefix_code(&mut writer, &[0u32; DISTANCE_ALPHABET_SIZE]);
+
+    for [r, g, b, a] in pixels.iter() {
+        green_code.write(&mut writer, *g as usize);
+        red_code.write(&mut writer, *r as usize);
+        blue_code.write(&mut writer, *b as usize);
+        alpha_code.write(&mut writer, *a as usize);
+    }
+
+    let data = writer.finish();
+    let padding = data.len() & 1;
+    let mut file = Vec::with_capacity(20 + data.len() + padding);
+    file.extend_from_slice(b"RIFF");
+    file.extend_from_slice(&((12 + data.len() + padding) as u32).to_le_bytes());
+    file.extend_from_slice(b"WEBPVP8L");
+    file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    file.extend_from_slice(&data);
+    if padding == 1 {
+        file.push(0);
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{Rgba, RgbImage, RgbaImage};
+    use image::buffer::ConvertBuffer;
+    use image_webp::WebPDecoder;
+
+    use super::*;
+
+    // Decodes with image-webp, which doesn't share any code with the encoder.
+    fn decode(webp: &[u8]) -> RgbaImage {
+        let mut decoder = WebPDecoder::new(Cursor::new(webp)).unwrap();
+        let (width, height) = decoder.dimensions();
+        let mut buffer = vec![0; decoder.output_buffer_size().unwrap()];
+        decoder.read_image(&mut buffer).unwrap();
+        if decoder.has_alpha() {
+            RgbaImage::from_raw(width, height, buffer).unwrap()
+        } else {
+            RgbImage::from_raw(width, height, buffer).unwrap().convert()
+        }
+    }
+
+    fn bits(write: impl FnOnce(&mut BitWriter)) -> Vec<u8> {
+        let mut writer = BitWriter::new();
+        write(&mut writer);
+        writer.finish()
+    }
+
+    // Whether the lengths describe a complete prefix code: the Kraft sum is exactly 1.
+    fn is_complete(lengths: &[u8]) -> bool {
+        let sum: u64 = lengths
+            .iter()
+            .filter(|length| **length > 0)
+            .map(|length| 1u64 << (32 - *length as u32))
+            .sum();
+        sum == 1u64 << 32
+    }
+
+    // Counts that grow like the Fibonacci sequence give the deepest possible tree.
+    fn skewed_histogram(size: usize) -> Vec<u32> {
+        let mut histogram = vec![1u32, 1];
+        while histogram.len() < size {
+            let next = histogram[histogram.len() - 1] + histogram[histogram.len() - 2];
+            histogram.push(next);
+        }
+        histogram
+    }
+
+    #[test]
+    fn bits_are_packed_least_significant_first() {
+        assert_eq!(bits(|writer| writer.write(0b101, 3)), vec![0b101]);
+        assert_eq!(
+            bits(|writer| {
+                writer.write(1, 1);
+                writer.write(0b11, 2);
+                writer.write(0x1f, 5);
+            }),
+            vec![0xff]
+        );
+        // Values cross byte boundaries and only the low `count` bits are written.
+        assert_eq!(
+            bits(|writer| {
+                writer.write(0b1, 1);
+                writer.write(0xffff_fe00, 9);
+                writer.write(0b111, 3);
+            }),
+            vec![0x01, 0x1c]
+        );
+        assert_eq!(
+            bits(|writer| {
+                writer.write(0, 4);
+                writer.write(0x1234_5678, 32);
+            }),
+            vec![0x80, 0x67, 0x45, 0x23, 0x01]
+        );
+        assert_eq!(bits(|writer| writer.write(0xff, 0)), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn canonical_codes_are_bit_reversed() {
+        // Lengths 1, 2, 3, 3 give the canonical codes 0, 10, 110 and 111.
+        let code = PrefixCode::new(vec![1, 2, 3, 3]);
+        assert_eq!(code.codes, vec![0b0, 0b01, 0b011, 0b111]);
+    }
+
+    #[test]
+    fn huffman_lengths_follow_the_counts() {
+        assert_eq!(huffman_lengths(&[1, 1, 2, 4], 1), vec![3, 3, 2, 1]);
+        assert_eq!(huffman_lengths(&[0, 5, 0, 5], 1), vec![0, 1, 0, 1]);
+        assert_eq!(huffman_lengths(&[0, 0, 7], 1), vec![0, 0, 1]);
+        assert_eq!(huffman_lengths(&[0, 0, 0], 1), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn code_lengths_respect_the_limit() {
+        let histogram = skewed_histogram(30);
+        assert!(huffman_lengths(&histogram, 1).iter().any(|length| *length > 15));
+        let lengths = code_lengths(&histogram, 15);
+        assert!(lengths.iter().all(|length| *length > 0 && *length <= 15));
+        assert!(is_complete(&lengths));
+
+        let histogram = skewed_histogram(CODE_LENGTH_CODES);
+        let lengths = code_lengths(&histogram, 7);
+        assert!(lengths.iter().all(|length| *length > 0 && *length <= 7));
+        assert!(is_complete(&lengths));
+    }
+
+    #[test]
+    fn one_symbol_uses_a_simple_code() {
+        let mut histogram = vec![0u32; 256];
+        histogram[1] = 9;
+        // Simple code, one symbol, 1-bit symbol, symbol 1.
+        let mut writer = BitWriter::new();
+        let code = write_prefix_code(&mut writer, &histogram);
+        assert_eq!(writer.finish(), vec![0b1001]);
+        assert_eq!(bits(|writer| code.write(writer, 1)), Vec::<u8>::new());
+
+        histogram[1] = 0;
+        histogram[200] = 3;
+        // Simple code, one symbol, 8-bit symbol, symbol 200.
+        let mut writer = BitWriter::new();
+        write_prefix_code(&mut writer, &histogram);
+        assert_eq!(writer.finish(), vec![0b0100_0101, 0b110]);
+
+        // An unused alphabet is written as a single symbol 0.
+        let mut writer = BitWriter::new();
+        write_prefix_code(&mut writer, &[0u32; DISTANCE_ALPHABET_SIZE]);
+        assert_eq!(writer.finish(), vec![0b0001]);
+    }
+
+    #[test]
+    fn two_symbols_use_a_simple_code() {
+        let mut histogram = vec![0u32; 256];
+        histogram[3] = 1;
+        histogram[9] = 100;
+        // Simple code, two symbols, 8-bit first symbol 3, then 9.
+        let mut writer = BitWriter::new();
+        let code = write_prefix_code(&mut writer, &histogram);
+        assert_eq!(writer.finish(), vec![0b0001_1111, 0b0100_1000, 0b0000_0000]);
+        assert_eq!(bits(|writer| code.write(writer, 3)), vec![0]);
+        assert_eq!(bits(|writer| code.write(writer, 9)), vec![1]);
+
+        // Symbols past 255 can't use a simple code.
+        let mut histogram = vec![0u32; GREEN_ALPHABET_SIZE];
+        histogram[0] = 1;
+        histogram[256] = 1;
+        let mut writer = BitWriter::new();
+        write_prefix_code(&mut writer, &histogram);
+        assert_eq!(writer.finish()[0] & 1, 0);
+    }
+
+    #[test]
+    fn rejects_sizes_webp_cannot_hold() {
+        assert!(encode_webp_lossless(&RgbaImage::new(0, 1)).is_err());
+        assert!(encode_webp_lossless(&RgbaImage::new(MAX_DIMENSION + 1, 1)).is_err());
+    }
+
+    #[test]
+    fn single_pixel_round_trips() {
+        let image = RgbaImage::from_pixel(1, 1, Rgba([10, 200, 30, 255]));
+        assert_eq!(decode(&encode_webp_lossless(&image).unwrap()), image);
+    }
+
+    #[test]
+    fn translucent_gradient_round_trips() {
+        let image = RgbaImage::from_fn(4, 3, |x, y| {
+            let alpha = if x == 3 { 128 } else { 255 };
+            Rgba([(x * 60) as u8, (y * 90) as u8, (x * 20 + y * 40) as u8, alpha])
+        });
+        assert_eq!(decode(&encode_webp_lossless(&image).unwrap()), image);
+    }
+
+    #[test]
+    fn solid_color_round_trips() {
+        let image = RgbaImage::from_pixel(17, 9, Rgba([0, 0, 0, 0]));
+        assert_eq!(decode(&encode_webp_lossless(&image).unwrap()), image);
+    }
+
+    // Enough distinct values in every channel that none of them fit a simple code.
+    #[test]
+    fn noisy_image_round_trips() {
+        let mut seed = 1u32;
+        let image = RgbaImage::from_fn(61, 37, |_, _| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let [r, g, b, a] = seed.to_le_bytes();
+            Rgba([r, g ^ a, b, a])
+        });
+        assert_eq!(decode(&encode_webp_lossless(&image).unwrap()), image);
+    }
+}
@@ -14,7 +14,7 @@ use skia_safe::gpu;
 use skia_safe::gpu::{BackendRenderTarget, Context};
 use skia_safe::paint::{Cap, Join, Style};
 
-use crate::common::{add_path_to_path, add_path_to_path_with_matrix, adjust_end_angle, arc, arc_to, begin_path, bezier_curve_to, CanvasArray, CanvasCompositeOperationType, CanvasNative, CanvasState, CanvasStateItem, CanvasTextMetrics, clear_canvas, clear_rect, clip, clip_path_rule, clip_rule, close_path, COLOR_BLACK, COLOR_TRANSPARENT, create_image_data, create_matrix, create_path_2d, create_path_2d_from_path_data, create_path_from_path, create_pattern, create_pattern_encoded, draw_image, draw_image_dw, draw_image_dw_encoded, draw_image_encoded, draw_image_sw, draw_image_sw_encoded, draw_rect, draw_text, ellipse, fill, fill_path_rule, fill_rule, flush, free_byte_array, free_char, free_path_2d, free_pattern, free_text_decoder, free_text_encoder, get_current_transform, get_image_data, get_matrix, get_measure_text, image_asset_flip_x, image_asset_flip_x_in_place_owned, image_asset_flip_y, image_asset_flip_y_in_place_owned, image_asset_free_bytes, image_asset_get_bytes, image_asset_get_error, image_asset_height, image_asset_load_from_path, image_asset_load_from_raw, image_asset_release, image_asset_save_path, image_asset_scale, image_asset_width, is_font_size, is_font_weight, is_point_in_path, is_point_in_stroke, line_to, move_to, NativeByteArray, NativeImageAsset, put_image_data, quadratic_curve_to, rect, reset_transform, restore, rotate, save, scale, set_current_transform, set_fill_color, set_fill_color_rgba, set_fill_pattern, set_font, set_global_alpha, set_global_composite_operation, set_gradient_linear, set_gradient_radial, set_image_smoothing_enabled, set_image_smoothing_quality, set_line_cap, set_line_dash, set_line_dash_offset, set_line_join, set_line_width, set_matrix, set_miter_limit, set_pattern_transform, set_shadow_blur, set_shadow_color, set_shadow_offset_x, set_shadow_offset_y, set_stroke_color, set_stroke_color_rgba, set_stroke_pattern, set_text_align, set_transform, snapshot_canvas, stroke, stroke_path, text_decoder_decode, text_decoder_get_encoding, text_encoder_encode, text_encoder_get_encoding, TextDecoder, TextEncoder, to_data_url, transform, translate, SurfaceKind};
+use crate::common::{add_path_to_path, add_path_to_path_with_matrix, adjust_end_angle, arc, arc_to, begin_path, bezier_curve_to, CanvasArray, CanvasCompositeOperationType, CanvasNative, CanvasState, CanvasStateItem, CanvasTextMetrics, clear_canvas, clear_rect, clip, clip_path_rule, clip_rule, close_path, COLOR_BLACK, COLOR_TRANSPARENT, create_image_data, create_matrix, create_path_2d, create_path_2d_from_path_data, create_path_from_path, create_pattern, create_pattern_encoded, draw_image, draw_image_dw, draw_image_dw_encoded, draw_image_encoded, draw_image_sw, draw_image_sw_encoded, draw_rect, draw_text, ellipse, fill, fill_path_rule, fill_rule, flush, free_byte_array, free_char, free_path_2d, free_pattern, free_text_decoder, free_text_encoder, get_current_transform, get_image_data, get_matrix, get_measure_text, image_asset_flip_x, image_asset_flip_x_in_place_owned, image_asset_flip_y, image_asset_flip_y_in_place_owned, image_asset_free_bytes, image_asset_get_bytes, image_asset_get_error, image_asset_height, image_asset_load_from_path, image_asset_load_from_raw, image_asset_release, image_asset_save_path, image_asset_scale, image_asset_width, is_font_size, is_font_weight, is_point_in_path, is_point_in_stroke, line_to, move_to, NativeByteArray, NativeImageAsset, put_image_data, quadratic_curve_to, rect, reset_transform, restore, rotate, save, scale, set_current_transform, set_fill_color, set_fill_color_rgba, set_fill_pattern, set_font, set_global_alpha, set_global_composite_operation, set_gradient_linear, set_gradient_radial, set_image_smoothing_enabled, set_image_smoothing_quality, set_line_cap, set_line_dash, set_line_dash_offset, set_line_join, set_line_width, set_matrix, set_miter_limit, set_pattern_transform, set_shadow_blur, set_shadow_color, set_shadow_offset_x, set_shadow_offset_y, set_stroke_color, set_stroke_color_rgba, set_stroke_pattern, set_text_align, set_transform, snapshot_canvas, stroke, stroke_path, text_decoder_decode, text_decoder_get_encoding, text_encoder_encode, text_encoder_get_encoding, TextDecoder, TextEncoder, to_data_url, transform, translate, SurfaceKind, stroke_to_path, hit_test_paths, add_hit_region, hit_region_at, remove_hit_region, clear_hit_regions, path_flatten, path_triangulate, CanvasPathContours, CanvasPathTriangulation, get_line_dash, set_corner_path_effect, set_discrete_path_effect, set_stamp_path_effect, clear_path_effects, set_fill_color_string, set_stroke_color_string, set_shadow_color_string, get_fill_style, get_stroke_style, get_shadow_color, PaintStyle, serialize_color, create_linear_gradient, create_radial_gradient, gradient_add_color_stop, free_gradient, set_fill_gradient, set_stroke_gradient, CanvasGradientStopResult, create_conic_gradient, set_filter, get_filter, get_global_composite_operation, get_global_alpha, create_pattern_from_canvas, create_pattern_from_asset, create_pattern_from_image_data, set_pattern_transform_2d, draw_image_bitmap, draw_image_bitmap_dw, draw_image_bitmap_sw, draw_image_keyed, image_cache_contains, image_cache_remove, image_cache_set_budget, image_cache_purge, image_cache_stats, ImageCacheStats, create_pattern_data_url, draw_image_data_url, draw_image_dw_data_url, draw_image_sw_data_url, gradient_handle, pattern_handle};
 
 struct AutoreleasePool(*mut objc::runtime::Object);
 
@@ -262,6 +262,102 @@ pub extern "C" fn native_path_2d_rect(
     rect(path, false, x, y, width, height)
 }
 
+fn into_canvas_array<T>(data: Vec<T>) -> CanvasArray {
+    let mut data = data.into_boxed_slice();
+    let ptr = data.as_mut_ptr();
+    let size = data.len();
+    std::mem::forget(data);
+    CanvasArray {
+        array: ptr as *const c_void,
+        length: size,
+    }
+}
+
+fn free_canvas_array<T>(data: &CanvasArray) {
+    if data.array.is_null() {
+        return;
+    }
+    let _ = unsafe {
+        Box::from_raw(std::slice::from_raw_parts_mut(
+            data.array as *mut T,
+            data.length,
+        ))
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn native_path_2d_flatten(path: c_longlong, tolerance: c_float) -> CanvasPathContours {
+    let _auto_release_pool = AutoreleasePool::new();
+    let contours = path_flatten(path, tolerance);
+    let closed: Vec<bool> = contours.iter().map(|contour| contour.closed).collect();
+    let contours: Vec<CanvasArray> = contours
+        .into_iter()
+        .map(|contour| into_canvas_array(contour.points))
+        .collect();
+    let contours = into_canvas_array(contours);
+    CanvasPathContours {
+        contours: contours.array as *const CanvasArray,
+        closed: into_canvas_array(closed).array as *const bool,
+        length: contours.length,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn native_free_path_contours(data: CanvasPathContours) {
+    let _auto_release_pool = AutoreleasePool::new();
+    if data.contours.is_null() {
+        return;
+    }
+    let contours = unsafe {
+        Box::from_raw(std::slice::from_raw_parts_mut(
+            data.contours as *mut CanvasArray,
+            data.length,
+        ))
+    };
+    for contour in contours.iter() {
+        free_canvas_array::<f32>(contour);
+    }
+    free_canvas_array::<bool>(&CanvasArray {
+        array: data.closed as *const c_void,
+        length: data.length,
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn native_path_2d_triangulate(
+    path: c_longlong,
+    fill_rule: *const c_char,
+    tolerance: c_float,
+    prefer_u16: bool,
+) -> CanvasPathTriangulation {
+    let _auto_release_pool = AutoreleasePool::new();
+    let triangulation = path_triangulate(path, fill_rule, tolerance);
+    let short_indices = if prefer_u16 { triangulation.indices_u16() } else { None };
+    match short_indices {
+        Some(indices) => CanvasPathTriangulation {
+            vertices: into_canvas_array(triangulation.vertices),
+            indices: into_canvas_array(indices),
+            index_size: 2,
+        },
+        None => CanvasPathTriangulation {
+            vertices: into_canvas_array(triangulation.vertices),
+            indices: into_canvas_array(triangulation.indices),
+            index_size: 4,
+        },
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn native_free_path_triangulation(data: CanvasPathTriangulation) {
+    let _auto_release_pool = AutoreleasePool::new();
+    free_canvas_array::<f32>(&data.vertices);
+    if data.index_size == 2 {
+        free_canvas_array::<u16>(&data.indices);
+    } else {
+        free_canvas_array::<u32>(&data.indices);
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn native_init_legacy(
     width: c_int,
@@ -333,7 +429,17 @@ pub extern "C" fn native_init_legacy(
         line_join: "miter".to_string(),
         direction: direction.to_string(),
         miter_limit: 10.0,
-        surface_kind: SurfaceKind::GPU
+        global_alpha: 1.0,
+        filter: "none".to_string(),
+        image_filter: None,
+        stroke_style: Default::default(),
+        fill_style: Default::default(),
+        path_effects: Default::default(),
+        line_dash: vec![],
+        clip_paths: vec![],
+        surface_kind: SurfaceKind::GPU,
+        hit_regions: vec![],
+        image_cache: Default::default(),
     };
     Box::into_raw(Box::new(canvas_native)) as *mut _ as i64
 }
@@ -404,7 +510,17 @@ pub extern "C" fn native_init(
         direction: direction.to_string(),
         ios: Box::into_raw(ios) as *mut _ as i64,
         miter_limit: 10.0,
-        surface_kind: SurfaceKind::GPU
+        global_alpha: 1.0,
+        filter: "none".to_string(),
+        image_filter: None,
+        stroke_style: Default::default(),
+        fill_style: Default::default(),
+        path_effects: Default::default(),
+        line_dash: vec![],
+        clip_paths: vec![],
+        surface_kind: SurfaceKind::GPU,
+        hit_regions: vec![],
+        image_cache: Default::default(),
     };
     Box::into_raw(Box::new(canvas_native)) as *mut _ as i64
 }
@@ -491,6 +607,8 @@ pub extern "C" fn native_surface_resized_legacy(
     canvas.draw_image(snapshot, Point::new(0f32, 0f32), None);
     //canvas.flush();
     canvas_native.context = Some(context);
+    // The new surface starts without a clip.
+    canvas_native.clip_paths.clear();
     canvas_native.surface = surface;
     Box::into_raw(canvas_native) as *mut _ as i64
 }
@@ -517,6 +635,8 @@ fn update_surface(canvas_native_ptr: c_longlong, view: *mut c_void) -> c_longlon
 
     let mut surface = surface_holder.unwrap();
     canvas_native.context = Some(context);
+    // The new surface starts without a clip.
+    canvas_native.clip_paths.clear();
     canvas_native.surface = surface;
 
     Box::into_raw(Box::new(canvas_native)) as *mut _ as i64
@@ -591,6 +711,69 @@ pub extern "C" fn native_is_point_in_stroke_with_path(
     return 0;
 }
 
+#[no_mangle]
+pub extern "C" fn native_hit_test_paths(
+    canvas_ptr: i64,
+    paths: *const c_longlong,
+    paths_size: size_t,
+    x: f32,
+    y: f32,
+    tolerance: f32,
+    fill_rule: *const c_char,
+    stroke: bool,
+    all: bool,
+) -> CanvasArray {
+    let _auto_release_pool = AutoreleasePool::new();
+    let handles: &[c_longlong] = if paths.is_null() {
+        &[]
+    } else {
+        unsafe { std::slice::from_raw_parts(paths, paths_size) }
+    };
+    into_canvas_array(hit_test_paths(canvas_ptr, handles, x, y, tolerance, fill_rule, stroke, all))
+}
+
+#[no_mangle]
+pub extern "C" fn native_free_hit_test_result(data: CanvasArray) {
+    let _auto_release_pool = AutoreleasePool::new();
+    free_canvas_array::<c_int>(&data);
+}
+
+#[no_mangle]
+pub extern "C" fn native_add_hit_region(
+    canvas_ptr: i64,
+    path: i64,
+    id: *const c_char,
+    stroke: bool,
+    fill_rule: *const c_char,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    add_hit_region(canvas_ptr, path, id, stroke, fill_rule)
+}
+
+#[no_mangle]
+pub extern "C" fn native_hit_region_at(canvas_ptr: i64, x: f32, y: f32) -> *const c_char {
+    let _auto_release_pool = AutoreleasePool::new();
+    hit_region_at(canvas_ptr, x, y)
+}
+
+#[no_mangle]
+pub extern "C" fn native_remove_hit_region(canvas_ptr: i64, id: *const c_char) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    remove_hit_region(canvas_ptr, id)
+}
+
+#[no_mangle]
+pub extern "C" fn native_clear_hit_regions(canvas_ptr: i64) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    clear_hit_regions(canvas_ptr)
+}
+
+#[no_mangle]
+pub extern "C" fn native_stroke_to_path(canvas_ptr: i64, path: i64) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    stroke_to_path(canvas_ptr, path)
+}
+
 #[no_mangle]
 pub extern "C" fn native_fill_rect(
     canvas_native_ptr: c_longlong,
@@ -827,6 +1010,41 @@ pub extern "C" fn native_create_pattern_encoded(
     )
 }
 
+#[no_mangle]
+pub extern "C" fn native_create_pattern_data_url(
+    data_url: *const c_char,
+    repetition: *const c_char,
+) -> c_longlong {
+    create_pattern_data_url(data_url, repetition)
+}
+
+#[no_mangle]
+pub extern "C" fn native_create_pattern_from_canvas(
+    canvas_native_ptr: c_longlong,
+    repetition: *const c_char,
+) -> c_longlong {
+    create_pattern_from_canvas(canvas_native_ptr, repetition)
+}
+
+#[no_mangle]
+pub extern "C" fn native_create_pattern_from_asset(
+    asset: c_longlong,
+    repetition: *const c_char,
+) -> c_longlong {
+    create_pattern_from_asset(asset, repetition)
+}
+
+#[no_mangle]
+pub extern "C" fn native_create_pattern_from_image_data(
+    data: *const u8,
+    data_size: size_t,
+    width: c_int,
+    height: c_int,
+    repetition: *const c_char,
+) -> c_longlong {
+    create_pattern_from_image_data(data, data_size, width, height, repetition)
+}
+
 #[no_mangle]
 pub extern "C" fn native_free_pattern(pattern: c_longlong) {
     free_pattern(pattern)
@@ -837,9 +1055,24 @@ pub extern "C" fn native_set_pattern_transform(
     pattern: c_longlong,
     matrix: c_longlong,
 ) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
     set_pattern_transform(pattern, matrix)
 }
 
+#[no_mangle]
+pub extern "C" fn native_set_pattern_transform_2d(
+    pattern: c_longlong,
+    a: c_float,
+    b: c_float,
+    c: c_float,
+    d: c_float,
+    e: c_float,
+    f: c_float,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    set_pattern_transform_2d(pattern, a, b, c, d, e, f)
+}
+
 #[no_mangle]
 pub extern "C" fn native_set_fill_pattern(
     canvas_native_ptr: c_longlong,
@@ -876,6 +1109,138 @@ pub extern "C" fn native_set_fill_color(canvas_native_ptr: c_longlong, color: u3
     set_fill_color(canvas_native_ptr, color as u32)
 }
 
+#[repr(C)]
+pub enum CanvasPaintStyleType {
+    Color = 0,
+    Gradient = 1,
+    Pattern = 2,
+}
+
+/// `color` is only set for colors and must be released with `native_free_char`.
+/// `handle` is a new gradient or pattern handle, to be released with
+/// `native_free_gradient` or `native_free_pattern`.
+#[repr(C)]
+pub struct CanvasPaintStyle {
+    pub value_type: CanvasPaintStyleType,
+    pub color: *const c_char,
+    pub handle: c_longlong,
+}
+
+fn to_canvas_paint_style(style: PaintStyle) -> CanvasPaintStyle {
+    match style {
+        PaintStyle::Color(color) => CanvasPaintStyle {
+            value_type: CanvasPaintStyleType::Color,
+            color: CString::new(serialize_color(color)).unwrap().into_raw(),
+            handle: 0,
+        },
+        PaintStyle::Gradient(gradient) => CanvasPaintStyle {
+            value_type: CanvasPaintStyleType::Gradient,
+            color: null(),
+            handle: gradient_handle(&gradient),
+        },
+        PaintStyle::Pattern(pattern) => CanvasPaintStyle {
+            value_type: CanvasPaintStyleType::Pattern,
+            color: null(),
+            handle: pattern_handle(&pattern),
+        },
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn native_get_fill_style(canvas_native_ptr: c_longlong) -> CanvasPaintStyle {
+    let _auto_release_pool = AutoreleasePool::new();
+    to_canvas_paint_style(get_fill_style(canvas_native_ptr))
+}
+
+#[no_mangle]
+pub extern "C" fn native_get_stroke_style(canvas_native_ptr: c_longlong) -> CanvasPaintStyle {
+    let _auto_release_pool = AutoreleasePool::new();
+    to_canvas_paint_style(get_stroke_style(canvas_native_ptr))
+}
+
+#[no_mangle]
+pub extern "C" fn native_get_shadow_color(canvas_native_ptr: c_longlong) -> *const c_char {
+    let _auto_release_pool = AutoreleasePool::new();
+    CString::new(get_shadow_color(canvas_native_ptr)).unwrap().into_raw()
+}
+
+#[no_mangle]
+pub extern "C" fn native_set_fill_color_string(
+    canvas_native_ptr: c_longlong,
+    color: *const c_char,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    set_fill_color_string(canvas_native_ptr, color)
+}
+
+#[no_mangle]
+pub extern "C" fn native_create_linear_gradient(
+    x0: c_float,
+    y0: c_float,
+    x1: c_float,
+    y1: c_float,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    create_linear_gradient(x0, y0, x1, y1)
+}
+
+#[no_mangle]
+pub extern "C" fn native_create_radial_gradient(
+    x0: c_float,
+    y0: c_float,
+    r0: c_float,
+    x1: c_float,
+    y1: c_float,
+    r1: c_float,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    create_radial_gradient(x0, y0, r0, x1, y1, r1)
+}
+
+#[no_mangle]
+pub extern "C" fn native_create_conic_gradient(
+    start_angle: c_float,
+    x: c_float,
+    y: c_float,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    create_conic_gradient(start_angle, x, y)
+}
+
+#[no_mangle]
+pub extern "C" fn native_gradient_add_color_stop(
+    gradient: c_longlong,
+    offset: c_float,
+    color: *const c_char,
+) -> CanvasGradientStopResult {
+    let _auto_release_pool = AutoreleasePool::new();
+    gradient_add_color_stop(gradient, offset, color)
+}
+
+#[no_mangle]
+pub extern "C" fn native_free_gradient(gradient: c_longlong) {
+    let _auto_release_pool = AutoreleasePool::new();
+    free_gradient(gradient)
+}
+
+#[no_mangle]
+pub extern "C" fn native_set_fill_gradient(
+    canvas_native_ptr: c_longlong,
+    gradient: c_longlong,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    set_fill_gradient(canvas_native_ptr, gradient)
+}
+
+#[no_mangle]
+pub extern "C" fn native_set_stroke_gradient(
+    canvas_native_ptr: c_longlong,
+    gradient: c_longlong,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    set_stroke_gradient(canvas_native_ptr, gradient)
+}
+
 #[no_mangle]
 pub extern "C" fn native_set_fill_gradient_radial(
     canvas_native_ptr: c_longlong,
@@ -1010,6 +1375,15 @@ pub extern "C" fn native_set_stroke_color(canvas_native_ptr: c_longlong, color:
     set_stroke_color(canvas_native_ptr, color)
 }
 
+#[no_mangle]
+pub extern "C" fn native_set_stroke_color_string(
+    canvas_native_ptr: c_longlong,
+    color: *const c_char,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    set_stroke_color_string(canvas_native_ptr, color)
+}
+
 #[no_mangle]
 pub extern "C" fn native_clear_rect(
     canvas_native_ptr: c_longlong,
@@ -1044,6 +1418,56 @@ pub extern "C" fn native_set_line_dash(
     set_line_dash(canvas_native_ptr, size, array)
 }
 
+#[no_mangle]
+pub extern "C" fn native_set_corner_path_effect(
+    canvas_native_ptr: c_longlong,
+    radius: c_float,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    set_corner_path_effect(canvas_native_ptr, radius)
+}
+
+#[no_mangle]
+pub extern "C" fn native_set_discrete_path_effect(
+    canvas_native_ptr: c_longlong,
+    segment_length: c_float,
+    deviation: c_float,
+    seed: c_uint,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    set_discrete_path_effect(canvas_native_ptr, segment_length, deviation, seed)
+}
+
+#[no_mangle]
+pub extern "C" fn native_set_stamp_path_effect(
+    canvas_native_ptr: c_longlong,
+    path: c_longlong,
+    advance: c_float,
+    phase: c_float,
+    style: *const c_char,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    set_stamp_path_effect(canvas_native_ptr, path, advance, phase, style)
+}
+
+#[no_mangle]
+pub extern "C" fn native_clear_path_effects(canvas_native_ptr: c_longlong) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    clear_path_effects(canvas_native_ptr)
+}
+
+#[no_mangle]
+pub extern "C" fn native_get_line_dash(canvas_native_ptr: c_longlong) -> CanvasArray {
+    let _auto_release_pool = AutoreleasePool::new();
+    into_canvas_array(get_line_dash(canvas_native_ptr))
+}
+
+#[no_mangle]
+pub extern "C" fn native_free_line_dash(data: CanvasArray) {
+    let _auto_release_pool = AutoreleasePool::new();
+    free_canvas_array::<f32>(&data);
+}
+
 #[no_mangle]
 pub extern "C" fn native_set_global_composite_operation(
     canvas_native_ptr: c_longlong,
@@ -1053,6 +1477,12 @@ pub extern "C" fn native_set_global_composite_operation(
     set_global_composite_operation(canvas_native_ptr, composite)
 }
 
+#[no_mangle]
+pub extern "C" fn native_get_global_composite_operation(canvas_native_ptr: c_longlong) -> *const c_char {
+    let _auto_release_pool = AutoreleasePool::new();
+    CString::new(get_global_composite_operation(canvas_native_ptr)).unwrap().into_raw()
+}
+
 #[no_mangle]
 pub extern "C" fn native_set_font(
     canvas_native_ptr: c_longlong,
@@ -1352,6 +1782,133 @@ pub extern "C" fn native_draw_image_sw(
     )
 }
 
+#[no_mangle]
+pub extern "C" fn native_draw_image_data_url(
+    canvas_native_ptr: c_longlong,
+    data_url: *const c_char,
+    dx: c_float,
+    dy: c_float,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    draw_image_data_url(canvas_native_ptr, data_url, dx, dy)
+}
+
+#[no_mangle]
+pub extern "C" fn native_draw_image_dw_data_url(
+    canvas_native_ptr: c_longlong,
+    data_url: *const c_char,
+    dx: c_float,
+    dy: c_float,
+    d_width: c_float,
+    d_height: c_float,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    draw_image_dw_data_url(canvas_native_ptr, data_url, dx, dy, d_width, d_height)
+}
+
+#[no_mangle]
+pub extern "C" fn native_draw_image_sw_data_url(
+    canvas_native_ptr: c_longlong,
+    data_url: *const c_char,
+    sx: c_float,
+    sy: c_float,
+    s_width: c_float,
+    s_height: c_float,
+    dx: c_float,
+    dy: c_float,
+    d_width: c_float,
+    d_height: c_float,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    draw_image_sw_data_url(canvas_native_ptr, data_url, sx, sy, s_width, s_height, dx, dy, d_width, d_height)
+}
+
+#[no_mangle]
+pub extern "C" fn native_draw_image_bitmap(
+    canvas_native_ptr: c_longlong,
+    bitmap: c_longlong,
+    dx: c_float,
+    dy: c_float,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    draw_image_bitmap(canvas_native_ptr, bitmap, dx, dy)
+}
+
+#[no_mangle]
+pub extern "C" fn native_draw_image_bitmap_dw(
+    canvas_native_ptr: c_longlong,
+    bitmap: c_longlong,
+    dx: c_float,
+    dy: c_float,
+    d_width: c_float,
+    d_height: c_float,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    draw_image_bitmap_dw(canvas_native_ptr, bitmap, dx, dy, d_width, d_height)
+}
+
+#[no_mangle]
+pub extern "C" fn native_draw_image_bitmap_sw(
+    canvas_native_ptr: c_longlong,
+    bitmap: c_longlong,
+    sx: c_float,
+    sy: c_float,
+    s_width: c_float,
+    s_height: c_float,
+    dx: c_float,
+    dy: c_float,
+    d_width: c_float,
+    d_height: c_float,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    draw_image_bitmap_sw(canvas_native_ptr, bitmap, sx, sy, s_width, s_height, dx, dy, d_width, d_height)
+}
+
+#[no_mangle]
+pub extern "C" fn native_draw_image_keyed(
+    canvas_native_ptr: c_longlong,
+    key: *const c_char,
+    image_array: *const u8,
+    image_size: size_t,
+    sx: c_float,
+    sy: c_float,
+    s_width: c_float,
+    s_height: c_float,
+    dx: c_float,
+    dy: c_float,
+    d_width: c_float,
+    d_height: c_float,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    draw_image_keyed(canvas_native_ptr, key, image_array, image_size, sx, sy, s_width, s_height, dx, dy, d_width, d_height)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_cache_contains(canvas_native_ptr: c_longlong, key: *const c_char) -> bool {
+    image_cache_contains(canvas_native_ptr, key)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_cache_remove(canvas_native_ptr: c_longlong, key: *const c_char) -> bool {
+    image_cache_remove(canvas_native_ptr, key)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_cache_set_budget(canvas_native_ptr: c_longlong, budget: size_t) -> c_longlong {
+    image_cache_set_budget(canvas_native_ptr, budget)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_cache_purge(canvas_native_ptr: c_longlong) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    image_cache_purge(canvas_native_ptr)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_cache_stats(canvas_native_ptr: c_longlong) -> ImageCacheStats {
+    image_cache_stats(canvas_native_ptr)
+}
+
 #[no_mangle]
 pub extern "C" fn native_save(canvas_native_ptr: c_longlong) -> c_longlong {
     let _auto_release_pool = AutoreleasePool::new();
@@ -1383,11 +1940,17 @@ pub extern "C" fn native_set_line_cap(
 }
 
 #[no_mangle]
-pub extern "C" fn native_set_global_alpha(canvas_native_ptr: c_longlong, alpha: u8) -> c_longlong {
+pub extern "C" fn native_set_global_alpha(canvas_native_ptr: c_longlong, alpha: f32) -> c_longlong {
     let _auto_release_pool = AutoreleasePool::new();
     set_global_alpha(canvas_native_ptr, alpha)
 }
 
+#[no_mangle]
+pub extern "C" fn native_get_global_alpha(canvas_native_ptr: c_longlong) -> f32 {
+    let _auto_release_pool = AutoreleasePool::new();
+    get_global_alpha(canvas_native_ptr)
+}
+
 #[no_mangle]
 pub extern "C" fn native_image_smoothing_enabled(
     canvas_native_ptr: c_longlong,
@@ -1441,6 +2004,30 @@ pub extern "C" fn native_shadow_color(canvas_native_ptr: c_longlong, color: u32)
     set_shadow_color(canvas_native_ptr, color)
 }
 
+#[no_mangle]
+pub extern "C" fn native_shadow_color_string(
+    canvas_native_ptr: c_longlong,
+    color: *const c_char,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    set_shadow_color_string(canvas_native_ptr, color)
+}
+
+#[no_mangle]
+pub extern "C" fn native_set_filter(
+    canvas_native_ptr: c_longlong,
+    filter: *const c_char,
+) -> c_longlong {
+    let _auto_release_pool = AutoreleasePool::new();
+    set_filter(canvas_native_ptr, filter)
+}
+
+#[no_mangle]
+pub extern "C" fn native_get_filter(canvas_native_ptr: c_longlong) -> *const c_char {
+    let _auto_release_pool = AutoreleasePool::new();
+    CString::new(get_filter(canvas_native_ptr)).unwrap().into_raw()
+}
+
 #[no_mangle]
 pub extern "C" fn native_shadow_offset_x(canvas_native_ptr: c_longlong, x: f32) -> c_longlong {
     let _auto_release_pool = AutoreleasePool::new();
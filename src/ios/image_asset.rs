@@ -1,8 +1,8 @@
-use std::os::raw::{c_char, c_longlong, c_uchar, c_uint};
+use std::os::raw::{c_char, c_longlong, c_uchar, c_uint, c_void};
 
 use libc::size_t;
 
-use crate::common::{image_asset_flip_x, image_asset_flip_x_in_place_owned, image_asset_flip_y, image_asset_flip_y_in_place_owned, image_asset_free_bytes, image_asset_get_bytes, image_asset_get_error, image_asset_height, image_asset_load_from_path, image_asset_load_from_raw, image_asset_release, image_asset_save_path, image_asset_scale, image_asset_width, NativeByteArray, NativeImageAsset};
+use crate::common::{image_asset_flip_x, image_asset_flip_x_in_place_owned, image_asset_flip_y, image_asset_flip_y_in_place_owned, image_asset_free_bytes, image_asset_get_bytes, image_asset_get_error, image_asset_height, image_asset_load_from_path, image_asset_load_from_raw, image_asset_release, image_asset_save_path, image_asset_scale, image_asset_width, image_asset_frame_count, image_asset_loop_count, image_asset_frame_delay, image_asset_get_frame_bytes, image_asset_select_frame, image_asset_set_auto_orient, image_asset_get_orientation, image_asset_get_exif, image_asset_free_exif, image_asset_resize, image_asset_crop, image_asset_rotate, image_asset_grayscale, image_asset_blur, image_asset_brighten, image_asset_contrast, image_asset_hue_rotate, image_asset_encode, image_asset_save_path_with_options, ImageAssetEncodeOptions, image_asset_load_from_path_async, image_asset_load_from_raw_async, image_asset_load_status, image_asset_load_cancel, image_asset_load_take, image_asset_load_release, ImageAssetLoadCallback, ImageAssetLoadStatus, image_asset_load_from_data_url, image_asset_load_svg, ImageAssetExif, NativeByteArray, NativeImageAsset};
 
 #[no_mangle]
 pub extern "C" fn native_image_asset_flip_y_in_place_owned(
@@ -46,6 +46,63 @@ pub extern "C" fn native_image_asset_load_from_raw(
     image_asset_load_from_raw(asset, array, size)
 }
 
+#[no_mangle]
+pub extern "C" fn native_image_asset_load_from_path_async(
+    path: *const c_char,
+    auto_orient: bool,
+    callback: Option<ImageAssetLoadCallback>,
+    user_data: *mut c_void,
+) -> c_longlong {
+    image_asset_load_from_path_async(path, auto_orient, callback, user_data)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_load_svg(
+    asset: c_longlong,
+    array: *const u8,
+    size: size_t,
+    width: c_uint,
+    height: c_uint,
+) -> c_uint {
+    image_asset_load_svg(asset, array, size, width, height)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_load_from_raw_async(
+    array: *const u8,
+    size: size_t,
+    auto_orient: bool,
+    callback: Option<ImageAssetLoadCallback>,
+    user_data: *mut c_void,
+) -> c_longlong {
+    image_asset_load_from_raw_async(array, size, auto_orient, callback, user_data)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_load_status(load: c_longlong) -> ImageAssetLoadStatus {
+    image_asset_load_status(load)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_load_cancel(load: c_longlong) -> bool {
+    image_asset_load_cancel(load)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_load_take(load: c_longlong) -> c_longlong {
+    image_asset_load_take(load)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_load_release(load: c_longlong) {
+    image_asset_load_release(load)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_load_from_data_url(asset: c_longlong, url: *const c_char) -> c_uint {
+    image_asset_load_from_data_url(asset, url)
+}
+
 #[no_mangle]
 pub extern "C" fn native_image_asset_get_bytes(asset: c_longlong) -> NativeByteArray {
     image_asset_get_bytes(asset)
@@ -66,11 +123,108 @@ pub extern "C" fn native_image_asset_get_height(asset: c_longlong) -> c_uint {
     image_asset_height(asset)
 }
 
+#[no_mangle]
+pub extern "C" fn native_image_asset_get_frame_count(asset: c_longlong) -> c_uint {
+    image_asset_frame_count(asset)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_get_loop_count(asset: c_longlong) -> c_uint {
+    image_asset_loop_count(asset)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_get_frame_delay(asset: c_longlong, index: c_uint) -> c_uint {
+    image_asset_frame_delay(asset, index)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_get_frame_bytes(asset: c_longlong, index: c_uint) -> NativeByteArray {
+    image_asset_get_frame_bytes(asset, index)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_select_frame(asset: c_longlong, index: c_uint) -> c_uint {
+    image_asset_select_frame(asset, index)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_set_auto_orient(asset: c_longlong, auto_orient: bool) -> c_longlong {
+    image_asset_set_auto_orient(asset, auto_orient)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_get_orientation(asset: c_longlong) -> c_uint {
+    image_asset_get_orientation(asset)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_get_exif(asset: c_longlong) -> ImageAssetExif {
+    image_asset_get_exif(asset)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_free_exif(exif: ImageAssetExif) {
+    image_asset_free_exif(exif)
+}
+
 #[no_mangle]
 pub extern "C" fn native_image_asset_scale(asset: c_longlong, x: c_uint, y: c_uint) -> c_longlong {
     image_asset_scale(asset, x, y)
 }
 
+#[no_mangle]
+pub extern "C" fn native_image_asset_resize(
+    asset: c_longlong,
+    width: c_uint,
+    height: c_uint,
+    filter: c_uint,
+    mode: c_uint,
+) -> c_uint {
+    image_asset_resize(asset, width, height, filter, mode)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_crop(
+    asset: c_longlong,
+    x: c_uint,
+    y: c_uint,
+    width: c_uint,
+    height: c_uint,
+) -> c_uint {
+    image_asset_crop(asset, x, y, width, height)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_rotate(asset: c_longlong, degrees: f32) -> c_uint {
+    image_asset_rotate(asset, degrees)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_grayscale(asset: c_longlong) -> c_uint {
+    image_asset_grayscale(asset)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_blur(asset: c_longlong, sigma: f32) -> c_uint {
+    image_asset_blur(asset, sigma)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_brighten(asset: c_longlong, value: i32) -> c_uint {
+    image_asset_brighten(asset, value)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_contrast(asset: c_longlong, value: f32) -> c_uint {
+    image_asset_contrast(asset, value)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_hue_rotate(asset: c_longlong, degrees: i32) -> c_uint {
+    image_asset_hue_rotate(asset, degrees)
+}
+
 #[no_mangle]
 pub extern "C" fn native_image_asset_flip_x(asset: c_longlong) -> c_longlong {
     image_asset_flip_x(asset)
@@ -90,6 +244,25 @@ pub extern "C" fn native_native_image_asset_save_path(
     image_asset_save_path(asset, path, format)
 }
 
+#[no_mangle]
+pub extern "C" fn native_image_asset_save_path_with_options(
+    asset: c_longlong,
+    path: *const c_char,
+    format: c_uint,
+    options: ImageAssetEncodeOptions,
+) -> c_uint {
+    image_asset_save_path_with_options(asset, path, format, options)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_asset_encode(
+    asset: c_longlong,
+    format: c_uint,
+    options: ImageAssetEncodeOptions,
+) -> NativeByteArray {
+    image_asset_encode(asset, format, options)
+}
+
 #[no_mangle]
 pub extern "C" fn native_image_asset_get_error(asset: c_longlong) -> *const c_char {
     image_asset_get_error(asset)
@@ -0,0 +1,81 @@
+use std::os::raw::{c_char, c_int, c_longlong};
+
+use libc::size_t;
+
+use crate::common::{create_image_bitmap_encoded, create_image_bitmap_from_asset, create_image_bitmap_from_bitmap, create_image_bitmap_from_canvas, create_image_bitmap_from_image_data, create_pattern_from_image_bitmap, image_bitmap_close, image_bitmap_get_bytes, image_bitmap_height, image_bitmap_width, ImageBitmapOptions, NativeByteArray};
+
+#[no_mangle]
+pub extern "C" fn native_create_image_bitmap_encoded(
+    image_data: *const u8,
+    image_size: size_t,
+    options: ImageBitmapOptions,
+) -> c_longlong {
+    create_image_bitmap_encoded(image_data, image_size, options)
+}
+
+#[no_mangle]
+pub extern "C" fn native_create_image_bitmap_from_asset(
+    asset: c_longlong,
+    options: ImageBitmapOptions,
+) -> c_longlong {
+    create_image_bitmap_from_asset(asset, options)
+}
+
+#[no_mangle]
+pub extern "C" fn native_create_image_bitmap_from_image_data(
+    data: *const u8,
+    data_size: size_t,
+    width: c_int,
+    height: c_int,
+    options: ImageBitmapOptions,
+) -> c_longlong {
+    create_image_bitmap_from_image_data(data, data_size, width, height, options)
+}
+
+#[no_mangle]
+pub extern "C" fn native_create_image_bitmap_from_canvas(
+    canvas_native_ptr: c_longlong,
+    options: ImageBitmapOptions,
+) -> c_longlong {
+    create_image_bitmap_from_canvas(canvas_native_ptr, options)
+}
+
+#[no_mangle]
+pub extern "C" fn native_create_image_bitmap_from_bitmap(
+    bitmap: c_longlong,
+    options: ImageBitmapOptions,
+) -> c_longlong {
+    create_image_bitmap_from_bitmap(bitmap, options)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_bitmap_get_width(bitmap: c_longlong) -> c_int {
+    image_bitmap_width(bitmap)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_bitmap_get_height(bitmap: c_longlong) -> c_int {
+    image_bitmap_height(bitmap)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_bitmap_get_bytes(
+    bitmap: c_longlong,
+    flip_y: bool,
+    premultiply: bool,
+) -> NativeByteArray {
+    image_bitmap_get_bytes(bitmap, flip_y, premultiply)
+}
+
+#[no_mangle]
+pub extern "C" fn native_image_bitmap_close(bitmap: c_longlong) {
+    image_bitmap_close(bitmap)
+}
+
+#[no_mangle]
+pub extern "C" fn native_create_pattern_from_image_bitmap(
+    bitmap: c_longlong,
+    repetition: *const c_char,
+) -> c_longlong {
+    create_pattern_from_image_bitmap(bitmap, repetition)
+}
@@ -2,3 +2,4 @@ pub mod core;
 pub mod text_encoder;
 pub mod text_decoder;
 pub mod image_asset;
+pub mod image_bitmap;